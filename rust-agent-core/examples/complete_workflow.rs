@@ -16,7 +16,7 @@ use rust_agent_core::{
     memory::MemoryStore,
     planner::Planner,
     policy::{Permission, PolicyEngine},
-    types::{Event, Habit, HabitFrequency, LlmOptions},
+    types::{Context, Event, Habit, HabitFrequency, LlmOptions},
     voice::{SimpleWakeWordDetector, WakeWordDetector},
 };
 use chrono::{Duration, Utc};
@@ -140,7 +140,7 @@ fn main() -> Result<()> {
 
     // 6. Policy enforcement
     println!("\n7. Testing policy enforcement...");
-    let mut policy = PolicyEngine::new(vec!["device".to_string(), "notification".to_string()]);
+    let policy = PolicyEngine::new(vec!["device".to_string(), "notification".to_string()]);
     
     // Try without permission
     match policy.check_intent(&device_intent) {
@@ -155,6 +155,9 @@ fn main() -> Result<()> {
         scope: vec!["living_room".to_string()],
         granted_at: Utc::now(),
         expires_at: Some(Utc::now() + Duration::hours(1)),
+        active_hours: None,
+        max_uses: None,
+        uses: 0,
     };
     policy.grant_permission(permission);
     
@@ -168,15 +171,13 @@ fn main() -> Result<()> {
     println!("\n8. Context-aware planning...");
     let planner = Planner::new();
     
-    let recent_events = memory.get_recent_events(5)?;
-    let summary = planner.compress_events(&recent_events);
+    let context = Context::load_from(&memory, "demo-user".to_string(), 5)?
+        .with_location("living room".to_string());
+    let summary = planner.compress_events(&context.recent_events);
     println!("   {}", summary);
     
     // Evaluate if intent is appropriate
-    let (appropriate, reason) = planner.evaluate_intent(
-        &device_intent,
-        &rust_agent_core::types::Context::new("demo-user".to_string()),
-    );
+    let (appropriate, reason) = planner.evaluate_intent(&device_intent, &context);
     println!("   Intent appropriate: {}", appropriate);
     println!("   Reason: {}", reason);
 
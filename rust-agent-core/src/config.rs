@@ -0,0 +1,249 @@
+//! Agent configuration
+//!
+//! One file describing every module, so deployments don't need code changes.
+//! Both TOML and JSON are accepted; unknown fields are ignored and missing
+//! ones take the same defaults as the in-code constructors.
+
+use crate::error::{AgentError, Result};
+use crate::intent::IntentGenerator;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Settings for the whole agent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    pub policy: PolicyConfig,
+    pub intent: IntentConfig,
+    pub llm: LlmConfig,
+    pub voice: VoiceConfig,
+}
+
+/// `PolicyEngine` settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    /// Modules intents may target
+    pub allowed_modules: Vec<String>,
+    /// Deny patterns per module, as accepted by `PolicyEngine::deny`
+    pub deny: HashMap<String, Vec<String>>,
+}
+
+/// `IntentGenerator` settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntentConfig {
+    pub min_confidence: f32,
+    /// Intent type prefixes that require permission
+    pub permission_prefixes: Vec<String>,
+}
+
+impl Default for IntentConfig {
+    fn default() -> Self {
+        Self {
+            min_confidence: 0.5,
+            permission_prefixes: IntentGenerator::default_permission_prefixes(),
+        }
+    }
+}
+
+/// `OllamaProvider` settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    pub model: String,
+    pub endpoint: String,
+    /// Request timeout in seconds; none waits indefinitely
+    pub timeout_secs: Option<u64>,
+    pub retries: u32,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            model: "llama2".to_string(),
+            endpoint: "http://localhost:11434".to_string(),
+            timeout_secs: None,
+            retries: 0,
+        }
+    }
+}
+
+/// `SimpleWakeWordDetector` settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceConfig {
+    pub wake_words: Vec<String>,
+    /// Energy threshold for detection (0.0 - 1.0)
+    pub wake_threshold: f32,
+    /// Only measure energy in the speech band
+    pub bandpass: bool,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            wake_words: vec!["hey agent".to_string()],
+            wake_threshold: 0.1,
+            bandpass: false,
+        }
+    }
+}
+
+impl AgentConfig {
+    /// Load a config file; `.json` files are read as JSON, anything else as TOML
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<AgentConfig> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            Self::from_json_str(&contents)
+        } else {
+            Self::from_toml_str(&contents)
+        }
+        .map_err(|e| match e {
+            AgentError::Config(message) => {
+                AgentError::Config(format!("{}: {}", path.display(), message))
+            }
+            other => other,
+        })
+    }
+
+    /// Parse a TOML config
+    pub fn from_toml_str(contents: &str) -> Result<AgentConfig> {
+        toml::from_str(contents).map_err(|e| AgentError::Config(format!("Invalid TOML config: {}", e)))
+    }
+
+    /// Parse a JSON config
+    pub fn from_json_str(contents: &str) -> Result<AgentConfig> {
+        serde_json::from_str(contents)
+            .map_err(|e| AgentError::Config(format!("Invalid JSON config: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{LlmProvider, OllamaProvider};
+    use crate::policy::PolicyEngine;
+    use crate::types::Intent;
+    use crate::voice::{SimpleWakeWordDetector, WakeWordDetector};
+    use std::io::Write;
+
+    const SAMPLE_TOML: &str = r#"
+        [policy]
+        allowed_modules = ["device", "notification"]
+
+        [policy.deny]
+        device = ["device.unlock"]
+
+        [intent]
+        min_confidence = 0.7
+        permission_prefixes = ["device."]
+
+        [llm]
+        model = "mistral"
+        endpoint = "http://gpu-box:11434"
+        timeout_secs = 30
+        retries = 2
+
+        [voice]
+        wake_words = ["computer"]
+        wake_threshold = 0.2
+        bandpass = true
+
+        [future_module]
+        enabled = true
+    "#;
+
+    fn write_config(suffix: &str, contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_load_toml_config() {
+        let file = write_config(".toml", SAMPLE_TOML);
+        let config = AgentConfig::from_file(file.path()).unwrap();
+
+        assert_eq!(config.policy.allowed_modules, vec!["device", "notification"]);
+        assert_eq!(config.intent.min_confidence, 0.7);
+        assert_eq!(config.llm.timeout_secs, Some(30));
+        assert_eq!(config.voice.wake_words, vec!["computer"]);
+    }
+
+    #[test]
+    fn test_load_json_config_with_defaults() {
+        let file = write_config(".json", r#"{"llm": {"model": "phi"}, "unknown": 1}"#);
+        let config = AgentConfig::from_file(file.path()).unwrap();
+
+        assert_eq!(config.llm.model, "phi");
+        assert_eq!(config.llm.endpoint, LlmConfig::default().endpoint);
+        assert_eq!(config.intent, IntentConfig::default());
+        assert_eq!(config.voice, VoiceConfig::default());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let file = write_config(".toml", "[intent]\nmin_confidence = \"high\"\n");
+        match AgentConfig::from_file(file.path()) {
+            Err(AgentError::Config(message)) => {
+                assert!(message.starts_with(&format!("{}: Invalid TOML config", file.path().display())));
+                assert!(!message.contains("Configuration error"));
+            }
+            other => panic!("expected Config error, got {:?}", other),
+        }
+        assert!(matches!(
+            AgentConfig::from_file("/nonexistent/agent.toml"),
+            Err(AgentError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_components_from_config() {
+        let config = AgentConfig::from_toml_str(SAMPLE_TOML).unwrap();
+
+        let policy = PolicyEngine::from_config(&config.policy);
+        assert!(policy.is_denied("device", "device.unlock"));
+        let mut intent = Intent::new("email.send".to_string(), 0.9, HashMap::new(), String::new())
+            .with_permission(true);
+        intent.target_module = Some("email".to_string());
+        match policy.check_intent(&intent) {
+            Err(AgentError::PolicyViolation(reason)) => assert!(reason.contains("allowed modules")),
+            other => panic!("expected policy violation, got {:?}", other),
+        }
+
+        let generator = IntentGenerator::from_config(&config.intent).unwrap();
+        assert!(generator
+            .generate("weather.query".to_string(), 0.6, HashMap::new(), String::new())
+            .is_err());
+        let intent = generator
+            .generate("message.send".to_string(), 0.8, HashMap::new(), String::new())
+            .unwrap();
+        assert!(!intent.requires_permission);
+
+        let provider = OllamaProvider::from_config(&config.llm);
+        assert_eq!(provider.name(), "ollama-mistral");
+        assert_eq!(provider.base_url(), "http://gpu-box:11434");
+        assert_eq!(provider.timeout(), Some(std::time::Duration::from_secs(30)));
+        assert_eq!(provider.retries(), 2);
+
+        let detector = SimpleWakeWordDetector::from_config(&config.voice);
+        assert_eq!(detector.wake_words(), vec!["computer"]);
+        assert_eq!(detector.threshold(), 0.2);
+    }
+
+    #[test]
+    fn test_intent_config_rejects_bad_confidence() {
+        let config = IntentConfig {
+            min_confidence: 1.5,
+            ..IntentConfig::default()
+        };
+        assert!(matches!(IntentGenerator::from_config(&config), Err(AgentError::Config(_))));
+    }
+}
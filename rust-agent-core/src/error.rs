@@ -17,6 +17,10 @@ pub enum AgentError {
     #[error("LLM error: {0}")]
     Llm(String),
 
+    /// The LLM backend could not be reached, timed out or failed server-side
+    #[error("LLM unavailable: {0}")]
+    LlmUnavailable(String),
+
     #[error("Voice processing error: {0}")]
     Voice(String),
 
@@ -38,3 +42,153 @@ pub enum AgentError {
 
 /// Result type alias using AgentError
 pub type Result<T> = std::result::Result<T, AgentError>;
+
+/// Broad classification of an error, for deciding how to react to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Temporary failure (unreachable LLM, busy database); worth retrying
+    Transient,
+    /// Malformed input, intent or configuration; retrying won't help
+    Invalid,
+    /// Blocked by policy
+    Policy,
+    /// Any other failure
+    Internal,
+}
+
+impl AgentError {
+    /// Classify this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AgentError::LlmUnavailable(_) => ErrorKind::Transient,
+            AgentError::Database(e) => match e.sqlite_error_code() {
+                Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => {
+                    ErrorKind::Transient
+                }
+                _ => ErrorKind::Internal,
+            },
+            AgentError::Io(e) => match e.kind() {
+                std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::Interrupted
+                | std::io::ErrorKind::WouldBlock => ErrorKind::Transient,
+                _ => ErrorKind::Internal,
+            },
+            AgentError::Serialization(_)
+            | AgentError::InvalidIntent(_)
+            | AgentError::Config(_) => ErrorKind::Invalid,
+            AgentError::PolicyViolation(_) => ErrorKind::Policy,
+            AgentError::Memory(_)
+            | AgentError::Llm(_)
+            | AgentError::Voice(_)
+            | AgentError::Other(_) => ErrorKind::Internal,
+        }
+    }
+
+    /// Whether the failed operation may succeed if attempted again
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Transient
+    }
+
+    /// Stable identifier for non-Rust hosts to branch on
+    pub fn code(&self) -> &'static str {
+        match self {
+            AgentError::Memory(_) => "memory_error",
+            AgentError::Database(_) => "database_error",
+            AgentError::Serialization(_) => "serialization_error",
+            AgentError::Llm(_) => "llm_error",
+            AgentError::LlmUnavailable(_) => "llm_unavailable",
+            AgentError::Voice(_) => "voice_error",
+            AgentError::PolicyViolation(_) => "policy_violation",
+            AgentError::InvalidIntent(_) => "invalid_intent",
+            AgentError::Config(_) => "config_error",
+            AgentError::Io(_) => "io_error",
+            AgentError::Other(_) => "other",
+        }
+    }
+
+    /// `{"code": ..., "message": ...}` for JSON boundaries
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "code": self.code(),
+            "message": self.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqlite_error(code: i32) -> AgentError {
+        AgentError::Database(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(code),
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let io = |kind| AgentError::Io(std::io::Error::new(kind, "io"));
+        let serde = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+
+        let cases = vec![
+            (AgentError::LlmUnavailable("connection refused".to_string()), ErrorKind::Transient),
+            (AgentError::Llm("status: 404 Not Found".to_string()), ErrorKind::Internal),
+            (AgentError::Llm("Failed to parse response".to_string()), ErrorKind::Internal),
+            (sqlite_error(rusqlite::ffi::SQLITE_BUSY), ErrorKind::Transient),
+            (sqlite_error(rusqlite::ffi::SQLITE_LOCKED), ErrorKind::Transient),
+            (sqlite_error(rusqlite::ffi::SQLITE_CONSTRAINT), ErrorKind::Internal),
+            (io(std::io::ErrorKind::TimedOut), ErrorKind::Transient),
+            (io(std::io::ErrorKind::NotFound), ErrorKind::Internal),
+            (AgentError::Serialization(serde), ErrorKind::Invalid),
+            (AgentError::InvalidIntent("bad".to_string()), ErrorKind::Invalid),
+            (AgentError::Config("bad".to_string()), ErrorKind::Invalid),
+            (AgentError::PolicyViolation("no".to_string()), ErrorKind::Policy),
+            (AgentError::Memory("poisoned".to_string()), ErrorKind::Internal),
+            (AgentError::Voice("no model".to_string()), ErrorKind::Internal),
+            (AgentError::Other("other".to_string()), ErrorKind::Internal),
+        ];
+
+        for (error, kind) in cases {
+            assert_eq!(error.kind(), kind, "{}", error);
+            assert_eq!(error.is_retryable(), kind == ErrorKind::Transient, "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let serde = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let cases = vec![
+            (AgentError::Memory("m".to_string()), "memory_error"),
+            (sqlite_error(rusqlite::ffi::SQLITE_BUSY), "database_error"),
+            (AgentError::Serialization(serde), "serialization_error"),
+            (AgentError::Llm("l".to_string()), "llm_error"),
+            (AgentError::LlmUnavailable("l".to_string()), "llm_unavailable"),
+            (AgentError::Voice("v".to_string()), "voice_error"),
+            (AgentError::PolicyViolation("p".to_string()), "policy_violation"),
+            (AgentError::InvalidIntent("i".to_string()), "invalid_intent"),
+            (AgentError::Config("c".to_string()), "config_error"),
+            (AgentError::Io(std::io::Error::other("io")), "io_error"),
+            (AgentError::Other("o".to_string()), "other"),
+        ];
+
+        for (error, code) in cases {
+            assert_eq!(error.code(), code);
+        }
+    }
+
+    #[test]
+    fn test_error_to_json() {
+        let error = AgentError::PolicyViolation("No permission for device.control".to_string());
+        assert_eq!(
+            error.to_json(),
+            serde_json::json!({
+                "code": "policy_violation",
+                "message": "Policy violation: No permission for device.control",
+            })
+        );
+    }
+}
@@ -5,9 +5,64 @@
 //! - Handles variance naturally
 //! - No assumptions about "should"
 
-use crate::error::Result;
 use crate::types::{Habit, HabitFrequency};
-use chrono::{DateTime, Utc, Duration};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+
+/// Parsed habit schedule: a time of day, optionally restricted to weekdays
+/// Accepts `"07:00"` (every day) or `"mon,wed,fri 18:00"`; times are UTC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub days: Option<Vec<Weekday>>,
+    pub time: NaiveTime,
+}
+
+impl Schedule {
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut parts = spec.split_whitespace();
+        let (days, time) = match (parts.next()?, parts.next(), parts.next()) {
+            (time, None, None) => (None, time),
+            (days, Some(time), None) => {
+                let days = days
+                    .split(',')
+                    .map(|d| d.trim().parse::<Weekday>().ok())
+                    .collect::<Option<Vec<_>>>()?;
+                (Some(days), time)
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            days,
+            time: NaiveTime::parse_from_str(time, "%H:%M").ok()?,
+        })
+    }
+
+    /// Most recent scheduled occurrence at or before `now`
+    pub fn last_occurrence(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        (0..=7).find_map(|days_back| {
+            let date = (now - Duration::days(days_back)).date_naive();
+            let runs_today = self
+                .days
+                .as_ref()
+                .is_none_or(|days| days.contains(&date.weekday()));
+            let at = date.and_time(self.time).and_utc();
+            (runs_today && at <= now).then_some(at)
+        })
+    }
+
+    /// Earliest scheduled occurrence at or after `from`
+    pub fn next_occurrence(&self, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        (0..=7).find_map(|days_ahead| {
+            let date = (from + Duration::days(days_ahead)).date_naive();
+            let runs_today = self
+                .days
+                .as_ref()
+                .is_none_or(|days| days.contains(&date.weekday()));
+            let at = date.and_time(self.time).and_utc();
+            (runs_today && at >= from).then_some(at)
+        })
+    }
+}
 
 /// Habit analyzer for understanding patterns
 pub struct HabitAnalyzer {
@@ -22,30 +77,37 @@ impl HabitAnalyzer {
         }
     }
 
+    /// Minimum confidence for a pattern to be considered meaningful
+    pub fn confidence_threshold(&self) -> f32 {
+        self.confidence_threshold
+    }
+
     /// Calculate variance in habit completion times
-    /// Returns statistical variance (lower = more consistent)
+    /// Returns the variance of intervals between consecutive completions,
+    /// in hours squared (lower = more consistent)
     pub fn calculate_variance(&self, completions: &[DateTime<Utc>]) -> Option<f32> {
+        let completions = Self::normalized(completions);
         if completions.len() < 2 {
             return None;
         }
 
-        // Calculate time differences between consecutive completions
-        let mut intervals: Vec<i64> = Vec::new();
+        // Calculate time differences between consecutive completions, in hours
+        let mut intervals: Vec<f32> = Vec::new();
         for i in 1..completions.len() {
             let diff = completions[i]
                 .signed_duration_since(completions[i - 1])
-                .num_hours();
-            intervals.push(diff);
+                .num_seconds();
+            intervals.push(diff as f32 / 3600.0);
         }
 
         // Calculate mean
-        let mean = intervals.iter().sum::<i64>() as f32 / intervals.len() as f32;
+        let mean = intervals.iter().sum::<f32>() / intervals.len() as f32;
 
         // Calculate variance
         let variance = intervals
             .iter()
             .map(|&x| {
-                let diff = x as f32 - mean;
+                let diff = x - mean;
                 diff * diff
             })
             .sum::<f32>()
@@ -54,8 +116,15 @@ impl HabitAnalyzer {
         Some(variance)
     }
 
+    /// Standard deviation of intervals between completions, in hours
+    /// Same information as `calculate_variance` in more interpretable units
+    pub fn interval_std_dev(&self, completions: &[DateTime<Utc>]) -> Option<f32> {
+        self.calculate_variance(completions).map(f32::sqrt)
+    }
+
     /// Suggest habit frequency based on completion history
     pub fn suggest_frequency(&self, completions: &[DateTime<Utc>]) -> Option<HabitFrequency> {
+        let completions = Self::normalized(completions);
         if completions.len() < 3 {
             return None;
         }
@@ -88,57 +157,180 @@ impl HabitAnalyzer {
         }
     }
 
+    /// Predict when the habit will next be completed
+    /// Projects the mean interval between completions forward from the last one
+    pub fn predict_next_completion(&self, completions: &[DateTime<Utc>]) -> Option<DateTime<Utc>> {
+        let completions = Self::normalized(completions);
+        if completions.len() < 2 {
+            return None;
+        }
+
+        let first = completions.first()?;
+        let last = completions.last()?;
+        let mean_seconds =
+            last.signed_duration_since(*first).num_seconds() / (completions.len() as i64 - 1);
+
+        Some(*last + Duration::seconds(mean_seconds))
+    }
+
+    /// Find the weekday a habit is usually completed on
+    /// Returns `None` unless one weekday accounts for more than 60% of completions
+    pub fn dominant_weekday(&self, completions: &[DateTime<Utc>]) -> Option<Weekday> {
+        let completions = Self::normalized(completions);
+        if completions.len() < 2 {
+            return None;
+        }
+
+        let mut counts = [0usize; 7];
+        for completion in &completions {
+            counts[completion.weekday().num_days_from_monday() as usize] += 1;
+        }
+
+        let (day, &count) = counts.iter().enumerate().max_by_key(|(_, &count)| count)?;
+        if count as f32 / completions.len() as f32 > 0.6 {
+            Weekday::try_from(day as u8).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Co-occurrence score between two habits (0.0 - 1.0)
+    /// Fraction of completions in `a` with a completion in `b` within `window`.
+    /// Describes co-occurrence only, not causation.
+    pub fn correlation(&self, a: &[DateTime<Utc>], b: &[DateTime<Utc>], window: Duration) -> f32 {
+        if a.is_empty() {
+            return 0.0;
+        }
+
+        let matched = a
+            .iter()
+            .filter(|&&ta| {
+                b.iter()
+                    .any(|&tb| ta.signed_duration_since(tb).abs() <= window)
+            })
+            .count();
+
+        matched as f32 / a.len() as f32
+    }
+
     /// Check if a habit is due based on history
     /// Returns confidence level (0.0 - 1.0)
     pub fn is_habit_due(&self, habit: &Habit, completions: &[DateTime<Utc>]) -> f32 {
-        if completions.is_empty() {
+        let Some(last_completion) = completions.iter().max() else {
             return 0.0;
-        }
+        };
 
-        let last_completion = completions.last().unwrap();
         let hours_since = Utc::now()
             .signed_duration_since(*last_completion)
             .num_hours();
 
         // Calculate expected interval based on frequency
-        let expected_hours = match &habit.frequency {
-            HabitFrequency::Daily => 24,
-            HabitFrequency::Weekly => 168,
-            HabitFrequency::Monthly => 720,
-            HabitFrequency::Custom(s) => {
-                // Parse "every X days" format
-                if let Some(days) = s.strip_prefix("every ").and_then(|s| s.strip_suffix(" days")) {
-                    days.parse::<i64>().unwrap_or(24) * 24
-                } else {
-                    24
-                }
-            }
-        };
+        let expected_hours = Self::expected_interval_hours(&habit.frequency);
 
         // Calculate confidence based on how overdue it is
         // Use variance if available to adjust confidence
+        // `habit.variance` is in hours squared; a 10 hour std dev halves the boost
         let variance_multiplier = habit.variance.map(|v| {
             // Lower variance = higher confidence when due
-            1.0 + (1.0 / (1.0 + v / 100.0))
+            let std_dev = v.max(0.0).sqrt();
+            1.0 + (1.0 / (1.0 + (std_dev / 10.0).powi(2)))
         }).unwrap_or(1.0);
 
         let overdue_factor = hours_since as f32 / expected_hours as f32;
-        let confidence = (overdue_factor * variance_multiplier).min(1.0);
+        (overdue_factor * variance_multiplier).min(1.0)
+    }
+
+    /// Check if a habit is due at `now`, honouring its schedule when it parses
+    /// Due once a scheduled time has passed without a completion (or creation)
+    /// since; without a usable schedule, due once the frequency interval elapses.
+    pub fn is_due_by_schedule(&self, habit: &Habit, now: DateTime<Utc>) -> bool {
+        let since = habit.last_completed.unwrap_or(habit.created_at);
+
+        match habit.schedule.as_deref().and_then(Schedule::parse) {
+            Some(schedule) => schedule
+                .last_occurrence(now)
+                .is_some_and(|occurrence| since < occurrence),
+            None => {
+                habit.last_completed.is_none()
+                    || now.signed_duration_since(since).num_hours()
+                        >= Self::expected_interval_hours(&habit.frequency)
+            }
+        }
+    }
+
+    /// Next time a schedule such as `"mon,wed,fri 18:00"` comes round,
+    /// at or after `from`
+    /// Returns `None` if the schedule does not parse.
+    pub fn next_scheduled(&self, schedule: &str, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        Schedule::parse(schedule)?.next_occurrence(from)
+    }
+
+    /// Fraction of expected occurrences completed between `since` and now
+    /// Expected occurrences come from the frequency and window length; the
+    /// result is clamped to [0, 1] and is 0 for an empty window.
+    pub fn completion_rate(
+        &self,
+        habit: &Habit,
+        completions: &[DateTime<Utc>],
+        since: DateTime<Utc>,
+    ) -> f32 {
+        let now = Utc::now();
+        let window_hours = now.signed_duration_since(since).num_minutes() as f32 / 60.0;
+        if window_hours <= 0.0 {
+            return 0.0;
+        }
+
+        let expected = window_hours / Self::expected_interval_hours(&habit.frequency) as f32;
+        let actual = Self::normalized(completions)
+            .iter()
+            .filter(|&&c| c >= since && c <= now)
+            .count();
 
-        confidence
+        (actual as f32 / expected).clamp(0.0, 1.0)
+    }
+
+    /// Completions sorted oldest first with exact duplicates removed
+    /// Callers may merge sources or load rows in any order.
+    fn normalized(completions: &[DateTime<Utc>]) -> Vec<DateTime<Utc>> {
+        let mut sorted = completions.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        sorted
+    }
+
+    /// Usual hours between completions for a frequency
+    ///
+    /// Custom frequencies of the form `"every N days"` with a positive N are
+    /// understood; anything else is treated as daily.
+    pub fn expected_interval_hours(frequency: &HabitFrequency) -> i64 {
+        match frequency {
+            HabitFrequency::Daily => 24,
+            HabitFrequency::Weekly => 168,
+            HabitFrequency::Monthly => 720,
+            HabitFrequency::Custom(s) => {
+                // Parse "every X days" format
+                s.strip_prefix("every ")
+                    .and_then(|s| s.strip_suffix(" days"))
+                    .and_then(|days| days.parse::<u32>().ok())
+                    .filter(|&days| days > 0)
+                    .map_or(24, |days| i64::from(days) * 24)
+            }
+        }
     }
 
     /// Summarize habit patterns for user
     pub fn summarize_habit(&self, habit: &Habit, completions: &[DateTime<Utc>]) -> String {
+        let completions = Self::normalized(completions);
         if completions.is_empty() {
             return format!("{}: No completions yet", habit.name);
         }
 
-        let variance = self.calculate_variance(completions);
-        let consistency = variance.map(|v| {
-            if v < 50.0 {
+        // Spread of intervals in hours
+        let std_dev = self.interval_std_dev(&completions);
+        let consistency = std_dev.map(|sd| {
+            if sd < 3.0 {
                 "very consistent"
-            } else if v < 200.0 {
+            } else if sd < 12.0 {
                 "moderately consistent"
             } else {
                 "variable"
@@ -167,7 +359,6 @@ impl Default for HabitAnalyzer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Duration;
 
     #[test]
     fn test_calculate_variance_consistent() {
@@ -240,4 +431,325 @@ mod tests {
         let confidence = analyzer.is_habit_due(&habit, &completions);
         assert!(confidence > 0.5);
     }
+
+    #[test]
+    fn test_predict_next_completion_daily() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now();
+
+        let completions = vec![
+            base - Duration::days(3),
+            base - Duration::days(2),
+            base - Duration::days(1),
+            base,
+        ];
+
+        let predicted = analyzer.predict_next_completion(&completions).unwrap();
+        let hours_after = predicted.signed_duration_since(base).num_hours();
+        assert_eq!(hours_after, 24);
+    }
+
+    #[test]
+    fn test_predict_next_completion_insufficient_data() {
+        let analyzer = HabitAnalyzer::new();
+        assert!(analyzer.predict_next_completion(&[]).is_none());
+        assert!(analyzer.predict_next_completion(&[Utc::now()]).is_none());
+    }
+
+    #[test]
+    fn test_dominant_weekday_sunday() {
+        let analyzer = HabitAnalyzer::new();
+        // 2024-01-07 is a Sunday
+        let sunday = DateTime::parse_from_rfc3339("2024-01-07T10:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let completions = vec![
+            sunday,
+            sunday + Duration::weeks(1),
+            sunday + Duration::weeks(2) + Duration::days(1),
+            sunday + Duration::weeks(3),
+            sunday + Duration::weeks(4),
+        ];
+
+        assert_eq!(analyzer.dominant_weekday(&completions), Some(Weekday::Sun));
+    }
+
+    #[test]
+    fn test_dominant_weekday_uniform() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now();
+
+        let completions: Vec<_> = (0..14).map(|i| base + Duration::days(i)).collect();
+        assert_eq!(analyzer.dominant_weekday(&completions), None);
+    }
+
+    #[test]
+    fn test_correlation_aligned() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now();
+
+        let runs: Vec<_> = (0..4).map(|i| base - Duration::days(i)).collect();
+        let sleep: Vec<_> = runs.iter().map(|&t| t + Duration::hours(2)).collect();
+
+        let score = analyzer.correlation(&runs, &sleep, Duration::hours(3));
+        assert_eq!(score, 1.0);
+    }
+
+    #[test]
+    fn test_correlation_disjoint() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now();
+
+        let runs: Vec<_> = (0..4).map(|i| base - Duration::days(i)).collect();
+        let sleep: Vec<_> = (10..14).map(|i| base - Duration::days(i)).collect();
+
+        assert_eq!(analyzer.correlation(&runs, &sleep, Duration::hours(3)), 0.0);
+        assert_eq!(analyzer.correlation(&[], &sleep, Duration::hours(3)), 0.0);
+    }
+
+    #[test]
+    fn test_correlation_partial() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now();
+
+        let runs: Vec<_> = (0..4).map(|i| base - Duration::days(i)).collect();
+        let sleep = vec![base + Duration::hours(1), base - Duration::days(1)];
+
+        assert_eq!(analyzer.correlation(&runs, &sleep, Duration::hours(3)), 0.5);
+    }
+
+    #[test]
+    fn test_interval_std_dev_with_outlier() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now();
+
+        // Intervals of 24, 24, 24 and 48 hours: mean 30, variance 108 hours²
+        let completions = vec![
+            base - Duration::days(5),
+            base - Duration::days(4),
+            base - Duration::days(3),
+            base - Duration::days(2),
+            base,
+        ];
+
+        let variance = analyzer.calculate_variance(&completions).unwrap();
+        assert!((variance - 108.0).abs() < 0.01);
+
+        let std_dev = analyzer.interval_std_dev(&completions).unwrap();
+        assert!((std_dev - 108f32.sqrt()).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_summarize_habit_consistency() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now();
+        let habit = Habit::new(
+            "Test".to_string(),
+            "Test habit".to_string(),
+            HabitFrequency::Daily,
+        );
+
+        let regular: Vec<_> = (0..5).rev().map(|i| base - Duration::days(i)).collect();
+        assert!(analyzer.summarize_habit(&habit, &regular).contains("very consistent"));
+
+        let irregular = vec![
+            base - Duration::days(5),
+            base - Duration::days(4),
+            base - Duration::days(3),
+            base - Duration::days(2),
+            base,
+        ];
+        assert!(analyzer
+            .summarize_habit(&habit, &irregular)
+            .contains("moderately consistent"));
+    }
+
+    fn at(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_schedule_parse() {
+        let schedule = Schedule::parse("07:00").unwrap();
+        assert_eq!(schedule.days, None);
+        assert_eq!(schedule.time, NaiveTime::from_hms_opt(7, 0, 0).unwrap());
+
+        let schedule = Schedule::parse("mon,wed,fri 18:00").unwrap();
+        assert_eq!(
+            schedule.days,
+            Some(vec![Weekday::Mon, Weekday::Wed, Weekday::Fri])
+        );
+
+        assert!(Schedule::parse("").is_none());
+        assert!(Schedule::parse("25:00").is_none());
+        assert!(Schedule::parse("someday 07:00").is_none());
+    }
+
+    #[test]
+    fn test_is_due_by_schedule_daily() {
+        let analyzer = HabitAnalyzer::new();
+        let mut habit = Habit::new(
+            "Stretch".to_string(),
+            "Morning stretch".to_string(),
+            HabitFrequency::Daily,
+        );
+        habit.schedule = Some("07:00".to_string());
+        habit.created_at = at("2024-01-01T00:00:00Z");
+        habit.last_completed = Some(at("2024-01-09T07:05:00Z"));
+
+        assert!(!analyzer.is_due_by_schedule(&habit, at("2024-01-10T06:59:00Z")));
+        assert!(analyzer.is_due_by_schedule(&habit, at("2024-01-10T07:01:00Z")));
+
+        habit.last_completed = Some(at("2024-01-10T07:02:00Z"));
+        assert!(!analyzer.is_due_by_schedule(&habit, at("2024-01-10T07:03:00Z")));
+    }
+
+    #[test]
+    fn test_is_due_by_schedule_weekdays() {
+        let analyzer = HabitAnalyzer::new();
+        let mut habit = Habit::new(
+            "Gym".to_string(),
+            "Workout".to_string(),
+            HabitFrequency::Weekly,
+        );
+        habit.schedule = Some("mon,wed,fri 18:00".to_string());
+        habit.created_at = at("2024-01-01T00:00:00Z");
+        // Completed Monday 2024-01-08 evening
+        habit.last_completed = Some(at("2024-01-08T18:30:00Z"));
+
+        // Tuesday evening: no session scheduled
+        assert!(!analyzer.is_due_by_schedule(&habit, at("2024-01-09T19:00:00Z")));
+        // Wednesday evening: due again
+        assert!(analyzer.is_due_by_schedule(&habit, at("2024-01-10T18:01:00Z")));
+    }
+
+    #[test]
+    fn test_is_due_by_schedule_falls_back_to_frequency() {
+        let analyzer = HabitAnalyzer::new();
+        let mut habit = Habit::new(
+            "Read".to_string(),
+            "Read a chapter".to_string(),
+            HabitFrequency::Daily,
+        );
+        habit.schedule = Some("whenever".to_string());
+        habit.last_completed = Some(at("2024-01-10T08:00:00Z"));
+
+        assert!(!analyzer.is_due_by_schedule(&habit, at("2024-01-10T20:00:00Z")));
+        assert!(analyzer.is_due_by_schedule(&habit, at("2024-01-11T08:00:00Z")));
+    }
+
+    #[test]
+    fn test_completion_rate() {
+        let analyzer = HabitAnalyzer::new();
+        let habit = Habit::new(
+            "Meditate".to_string(),
+            "Ten minutes".to_string(),
+            HabitFrequency::Daily,
+        );
+        let now = Utc::now();
+        let since = now - Duration::days(7);
+
+        // Every day of the week
+        let perfect: Vec<_> = (0..7).map(|d| since + Duration::days(d) + Duration::hours(8)).collect();
+        assert!((analyzer.completion_rate(&habit, &perfect, since) - 1.0).abs() < 0.01);
+
+        // Every other day over two weeks
+        let since = now - Duration::days(14);
+        let half: Vec<_> = (0..7).map(|d| since + Duration::days(d * 2) + Duration::hours(8)).collect();
+        assert!((analyzer.completion_rate(&habit, &half, since) - 0.5).abs() < 0.01);
+
+        // Completions before the window don't count
+        let old = vec![since - Duration::days(1)];
+        assert_eq!(analyzer.completion_rate(&habit, &old, since), 0.0);
+
+        // Empty window
+        assert_eq!(analyzer.completion_rate(&habit, &perfect, now + Duration::hours(1)), 0.0);
+    }
+
+    #[test]
+    fn test_unsorted_and_duplicate_completions() {
+        let analyzer = HabitAnalyzer::new();
+        let base = Utc::now() - Duration::days(10);
+        let sorted: Vec<DateTime<Utc>> = (0..5).map(|i| base + Duration::days(i * 2)).collect();
+        let shuffled = vec![
+            sorted[3], sorted[0], sorted[4], sorted[0], sorted[2], sorted[1], sorted[3],
+        ];
+
+        assert_eq!(analyzer.calculate_variance(&shuffled), Some(0.0));
+        assert_eq!(analyzer.calculate_variance(&shuffled), analyzer.calculate_variance(&sorted));
+        assert!(matches!(analyzer.suggest_frequency(&shuffled), Some(HabitFrequency::Weekly)));
+        assert_eq!(
+            analyzer.predict_next_completion(&shuffled),
+            Some(sorted[4] + Duration::days(2))
+        );
+
+        let habit = Habit::new("Run".to_string(), "Go running".to_string(), HabitFrequency::Daily);
+        assert_eq!(
+            analyzer.is_habit_due(&habit, &shuffled),
+            analyzer.is_habit_due(&habit, &sorted)
+        );
+        assert_eq!(
+            analyzer.summarize_habit(&habit, &shuffled),
+            analyzer.summarize_habit(&habit, &sorted)
+        );
+        assert!(analyzer.summarize_habit(&habit, &shuffled).contains("very consistent"));
+    }
+
+    #[test]
+    fn test_duplicates_only_are_not_a_pattern() {
+        let analyzer = HabitAnalyzer::new();
+        let when = Utc::now() - Duration::hours(3);
+
+        assert_eq!(analyzer.calculate_variance(&[when, when, when]), None);
+        assert!(analyzer.suggest_frequency(&[when, when, when]).is_none());
+        assert_eq!(analyzer.predict_next_completion(&[when, when]), None);
+    }
+
+    #[test]
+    fn test_next_scheduled_weekdays() {
+        let analyzer = HabitAnalyzer::new();
+
+        // Tuesday 2024-01-09 -> Wednesday evening
+        assert_eq!(
+            analyzer.next_scheduled("mon,wed,fri 18:00", at("2024-01-09T12:00:00Z")),
+            Some(at("2024-01-10T18:00:00Z"))
+        );
+        // Friday after the session -> the following Monday
+        assert_eq!(
+            analyzer.next_scheduled("mon,wed,fri 18:00", at("2024-01-12T19:00:00Z")),
+            Some(at("2024-01-15T18:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_next_scheduled_same_day() {
+        let analyzer = HabitAnalyzer::new();
+
+        // Wednesday morning, before the session
+        assert_eq!(
+            analyzer.next_scheduled("mon,wed,fri 18:00", at("2024-01-10T08:00:00Z")),
+            Some(at("2024-01-10T18:00:00Z"))
+        );
+        // Exactly on time counts
+        assert_eq!(
+            analyzer.next_scheduled("07:00", at("2024-01-10T07:00:00Z")),
+            Some(at("2024-01-10T07:00:00Z"))
+        );
+        assert_eq!(
+            analyzer.next_scheduled("07:00", at("2024-01-10T07:01:00Z")),
+            Some(at("2024-01-11T07:00:00Z"))
+        );
+        assert_eq!(analyzer.next_scheduled("someday 07:00", at("2024-01-10T07:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_expected_interval_hours_custom() {
+        let every = |s: &str| HabitFrequency::Custom(s.to_string());
+        assert_eq!(HabitAnalyzer::expected_interval_hours(&every("every 3 days")), 72);
+        assert_eq!(HabitAnalyzer::expected_interval_hours(&every("every x days")), 24);
+        assert_eq!(HabitAnalyzer::expected_interval_hours(&every("every 0 days")), 24);
+        assert_eq!(HabitAnalyzer::expected_interval_hours(&every("every -2 days")), 24);
+        assert_eq!(HabitAnalyzer::expected_interval_hours(&every("fortnightly")), 24);
+    }
 }
@@ -3,22 +3,261 @@
 //! The agent emits structured intents (JSON) but NEVER executes actions.
 //! This is the security boundary between thinking and acting.
 
+pub mod mqtt;
+pub mod time;
+
+use crate::config::IntentConfig;
 use crate::error::{AgentError, Result};
-use crate::types::Intent;
+use crate::llm::LlmProvider;
+use crate::types::{Intent, LlmOptions};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::Deserialize;
 use serde_json::Value;
-use std::collections::HashMap;
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+
+/// Intent as proposed by an LLM, before validation
+#[derive(Debug, Deserialize)]
+struct LlmIntent {
+    intent_type: String,
+    confidence: f32,
+    #[serde(default)]
+    parameters: HashMap<String, Value>,
+    reasoning: String,
+}
+
+/// Text pattern that produces an intent when it matches
+///
+/// Named capture groups become intent parameters automatically; numbered
+/// groups can be mapped to parameters with `with_param`. `{name}` in the
+/// reasoning is replaced by the extracted parameter value. `with_time_param`
+/// splits a trailing time phrase off a parameter into a `time` parameter.
+#[derive(Debug, Clone)]
+pub struct IntentPattern {
+    pub regex: Regex,
+    pub intent_type: String,
+    pub confidence: f32,
+    pub reasoning: String,
+    pub param_extractors: Vec<(String, usize)>,
+    pub lowercase_values: bool,
+    pub time_param: Option<String>,
+}
+
+impl IntentPattern {
+    pub fn new(pattern: &str, intent_type: &str, confidence: f32, reasoning: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| AgentError::Config(format!("Invalid intent pattern '{}': {}", pattern, e)))?;
+
+        Ok(Self {
+            regex,
+            intent_type: intent_type.to_string(),
+            confidence,
+            reasoning: reasoning.to_string(),
+            param_extractors: Vec::new(),
+            lowercase_values: false,
+            time_param: None,
+        })
+    }
+
+    /// Lowercase extracted values (useful for case-insensitive keywords)
+    pub fn with_lowercase_values(mut self) -> Self {
+        self.lowercase_values = true;
+        self
+    }
+
+    /// Map a numbered capture group to a parameter
+    pub fn with_param(mut self, name: &str, group: usize) -> Self {
+        self.param_extractors.push((name.to_string(), group));
+        self
+    }
+
+    /// Move a trailing time phrase in parameter `name` into an RFC3339 `time`
+    /// parameter, e.g. "call mom at 5pm" -> "call mom" + today's 17:00 local time.
+    /// Phrases that can't be parsed are left in place and no `time` is set.
+    pub fn with_time_param(mut self, name: &str) -> Self {
+        self.time_param = Some(name.to_string());
+        self
+    }
+
+    /// Extract parameters if the pattern matches the text
+    fn extract(&self, text: &str) -> Option<HashMap<String, Value>> {
+        let captures = self.regex.captures(text)?;
+        let mut params = HashMap::new();
+
+        for name in self.regex.capture_names().flatten() {
+            if let Some(value) = captures.name(name) {
+                params.insert(name.to_string(), Value::String(value.as_str().to_string()));
+            }
+        }
+
+        for (name, group) in &self.param_extractors {
+            if let Some(value) = captures.get(*group) {
+                params.insert(name.clone(), Value::String(value.as_str().to_string()));
+            }
+        }
+
+        if self.lowercase_values {
+            for value in params.values_mut() {
+                if let Value::String(s) = value {
+                    *s = s.to_lowercase();
+                }
+            }
+        }
+
+        if let Some(name) = &self.time_param {
+            if let Some(Value::String(value)) = params.get(name) {
+                let value = value.trim().trim_end_matches(['.', '!', '?']);
+                let (rest, when) = time::split_time_phrase(value, Utc::now());
+                let rest = rest.to_string();
+                if let Some(when) = when {
+                    params.insert("time".to_string(), Value::String(when.to_rfc3339()));
+                }
+                if rest.is_empty() {
+                    params.remove(name);
+                } else {
+                    params.insert(name.clone(), Value::String(rest));
+                }
+            }
+        }
+
+        Some(params)
+    }
+
+    fn render_reasoning(&self, params: &HashMap<String, Value>) -> String {
+        params.iter().fold(self.reasoning.clone(), |reasoning, (name, value)| {
+            let value = value.as_str().map(String::from).unwrap_or_else(|| value.to_string());
+            reasoning.replace(&format!("{{{}}}", name), &value)
+        })
+    }
+}
 
 /// Intent generator - converts reasoning into structured intents
 pub struct IntentGenerator {
     // Configuration for intent generation
     min_confidence: f32,
+    patterns: Vec<IntentPattern>,
+    permission_prefixes: Vec<String>,
+    calibration: Option<Box<dyn Fn(f32) -> f32 + Send + Sync>>,
 }
 
 impl IntentGenerator {
     pub fn new() -> Self {
         Self {
             min_confidence: 0.5,
+            patterns: Self::default_patterns(),
+            permission_prefixes: Self::default_permission_prefixes(),
+            calibration: None,
+        }
+    }
+
+    /// Create a generator with the thresholds and permission prefixes from a config
+    pub fn from_config(config: &IntentConfig) -> Result<Self> {
+        Ok(Self::new()
+            .with_min_confidence(config.min_confidence)?
+            .with_permission_prefixes(config.permission_prefixes.clone()))
+    }
+
+    /// Set the minimum confidence `generate` accepts (default 0.5)
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Result<Self> {
+        self.set_min_confidence(min_confidence)?;
+        Ok(self)
+    }
+
+    /// Set the minimum confidence `generate` accepts; must be within [0, 1]
+    pub fn set_min_confidence(&mut self, min_confidence: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&min_confidence) {
+            return Err(AgentError::Config(format!(
+                "Minimum confidence must be between 0.0 and 1.0, got {}",
+                min_confidence
+            )));
         }
+
+        self.min_confidence = min_confidence;
+        Ok(())
+    }
+
+    /// Remap raw confidences before the minimum-confidence gate
+    /// The result is clamped to [0, 1]; the default is the identity.
+    pub fn set_calibration(&mut self, f: Box<dyn Fn(f32) -> f32 + Send + Sync>) {
+        self.calibration = Some(f);
+    }
+
+    fn calibrate(&self, confidence: f32) -> f32 {
+        match &self.calibration {
+            Some(f) => f(confidence).clamp(0.0, 1.0),
+            None => confidence,
+        }
+    }
+
+    /// Intent type prefixes that require permission by default
+    /// Actions that modify state or interact with external systems require permission
+    pub fn default_permission_prefixes() -> Vec<String> {
+        [
+            "device.",
+            "message.",
+            "email.",
+            "calendar.",
+            "file.",
+            "network.",
+            "location.",
+            "camera.",
+            "microphone.",
+            "notification.",
+        ]
+        .iter()
+        .map(|prefix| prefix.to_string())
+        .collect()
+    }
+
+    /// Replace the list of intent type prefixes that require permission
+    pub fn with_permission_prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.permission_prefixes = prefixes;
+        self
+    }
+
+    /// Mark an additional intent type prefix (e.g. `smarthome.`) as requiring permission
+    pub fn add_permission_prefix(&mut self, prefix: &str) {
+        self.permission_prefixes.push(prefix.to_string());
+    }
+
+    /// Built-in patterns for reminders, device control, weather and time
+    pub fn default_patterns() -> Vec<IntentPattern> {
+        let pattern = |regex, intent_type, confidence, reasoning| {
+            IntentPattern::new(regex, intent_type, confidence, reasoning)
+                .expect("built-in intent pattern is valid")
+        };
+
+        vec![
+            pattern(
+                r"(?is)^(.*?(?:remind me to|reminder)(?:\s+to)?(?P<task>.*))$",
+                "reminder.create",
+                0.8,
+                "User requested a reminder",
+            )
+            .with_param("text", 1)
+            .with_time_param("task"),
+            pattern(
+                r"(?i)turn (?P<action>on|off)",
+                "device.control",
+                0.7,
+                "User wants to turn {action} a device",
+            )
+            .with_lowercase_values(),
+            pattern(r"(?i)weather", "weather.query", 0.9, "User asking about weather"),
+            pattern(
+                r"(?i)what time|current time",
+                "time.query",
+                0.95,
+                "User asking about current time",
+            ),
+        ]
+    }
+
+    /// Register an additional pattern for `parse_from_text`
+    pub fn add_pattern(&mut self, pattern: IntentPattern) {
+        self.patterns.push(pattern);
     }
 
     /// Generate an intent from parsed understanding
@@ -29,6 +268,7 @@ impl IntentGenerator {
         parameters: HashMap<String, Value>,
         reasoning: String,
     ) -> Result<Intent> {
+        let confidence = self.calibrate(confidence);
         if confidence < self.min_confidence {
             return Err(AgentError::InvalidIntent(format!(
                 "Confidence {} below minimum {}",
@@ -36,10 +276,9 @@ impl IntentGenerator {
             )));
         }
 
-        let mut intent = Intent::new(intent_type.clone(), confidence, parameters, reasoning);
-
         // Determine if permission is required based on intent type
-        intent.requires_permission = self.requires_permission(&intent_type);
+        let mut intent = Intent::new(intent_type.clone(), confidence, parameters, reasoning)
+            .with_permission(self.requires_permission(&intent_type));
 
         // Extract target module from intent type (e.g., "device.control" -> "device")
         if let Some(module) = intent_type.split('.').next() {
@@ -49,31 +288,109 @@ impl IntentGenerator {
         Ok(intent)
     }
 
+    /// Collapse intents whose types the caller declares equivalent
+    /// Equivalence is transitive, so `("light.on", "device.control")` and
+    /// `("device.control", "switch.on")` make all three types one group.
+    /// Intents merge only when their shared parameters agree; the merged
+    /// intent is the more confident one with the union of both parameter
+    /// sets, and requires permission if either did.
+    pub fn merge_equivalent(&self, intents: Vec<Intent>, equivalences: &[(&str, &str)]) -> Vec<Intent> {
+        // Group id per intent type
+        let mut groups: HashMap<&str, usize> = HashMap::new();
+        for (i, &(a, b)) in equivalences.iter().enumerate() {
+            match (groups.get(a).copied(), groups.get(b).copied()) {
+                (None, None) => {
+                    groups.insert(a, i);
+                    groups.insert(b, i);
+                }
+                (Some(group), None) => {
+                    groups.insert(b, group);
+                }
+                (None, Some(group)) => {
+                    groups.insert(a, group);
+                }
+                (Some(keep), Some(old)) => {
+                    for group in groups.values_mut() {
+                        if *group == old {
+                            *group = keep;
+                        }
+                    }
+                }
+            }
+        }
+        let equivalent = |a: &str, b: &str| {
+            a == b || groups.get(a).is_some_and(|group| groups.get(b) == Some(group))
+        };
+        let compatible = |a: &Intent, b: &Intent| {
+            a.parameters
+                .iter()
+                .all(|(key, value)| b.parameters.get(key).is_none_or(|other| other == value))
+        };
+
+        let mut merged: Vec<Intent> = Vec::new();
+        for intent in intents {
+            let existing = merged.iter_mut().find(|existing| {
+                equivalent(&existing.intent_type, &intent.intent_type) && compatible(existing, &intent)
+            });
+
+            match existing {
+                Some(existing) => {
+                    let (mut kept, other) = if intent.confidence > existing.confidence {
+                        (intent, existing.clone())
+                    } else {
+                        (existing.clone(), intent)
+                    };
+                    kept.requires_permission |= other.requires_permission;
+                    for (key, value) in other.parameters {
+                        kept.parameters.entry(key).or_insert(value);
+                    }
+                    *existing = kept;
+                }
+                None => merged.push(intent),
+            }
+        }
+
+        merged
+    }
+
+    /// Order intents by priority, highest first (ties broken by confidence)
+    pub fn rank(&self, mut intents: Vec<Intent>) -> Vec<Intent> {
+        intents.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| b.confidence.total_cmp(&a.confidence))
+        });
+        intents
+    }
+
     /// Serialize intent to JSON for transmission to device agents
     pub fn to_json(&self, intent: &Intent) -> Result<String> {
         let json = serde_json::to_string_pretty(intent)?;
         Ok(json)
     }
 
+    /// Serialize intent to compact JSON with sorted object keys
+    /// Output is byte-stable for equal intents, unlike `to_json`, so it is
+    /// suitable for diffing, signing and snapshot tests.
+    pub fn to_canonical_json(&self, intent: &Intent) -> Result<String> {
+        canonical_json(intent)
+    }
+
+    /// Serialize intents as newline-delimited JSON, one compact intent per line
+    pub fn to_ndjson(&self, intents: &[Intent]) -> Result<String> {
+        let mut out = String::new();
+        for intent in intents {
+            out.push_str(&serde_json::to_string(intent)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
     /// Determine if an intent type requires permission
     fn requires_permission(&self, intent_type: &str) -> bool {
-        // Actions that modify state or interact with external systems require permission
-        let requires_permission_prefixes = [
-            "device.",
-            "message.",
-            "email.",
-            "calendar.",
-            "file.",
-            "network.",
-            "location.",
-            "camera.",
-            "microphone.",
-            "notification.",
-        ];
-
-        requires_permission_prefixes
+        self.permission_prefixes
             .iter()
-            .any(|prefix| intent_type.starts_with(prefix))
+            .any(|prefix| intent_type.starts_with(prefix.as_str()))
     }
 
     /// Validate intent structure
@@ -99,69 +416,145 @@ impl IntentGenerator {
         Ok(())
     }
 
-    /// Parse common intent patterns from text
+    /// Parse intents from text using the registered patterns
     /// This is a simple pattern matcher - in production, use LLM for better understanding
     pub fn parse_from_text(&self, text: &str) -> Vec<Intent> {
         let mut intents = Vec::new();
-        let text_lower = text.to_lowercase();
 
-        // Pattern: "remind me to..."
-        if text_lower.contains("remind me to") || text_lower.contains("reminder") {
-            let mut params = HashMap::new();
-            params.insert("text".to_string(), Value::String(text.to_string()));
-            
-            if let Ok(intent) = self.generate(
-                "reminder.create".to_string(),
-                0.8,
-                params,
-                "User requested a reminder".to_string(),
-            ) {
-                intents.push(intent);
-            }
-        }
+        for pattern in &self.patterns {
+            let Some(params) = pattern.extract(text) else {
+                continue;
+            };
 
-        // Pattern: "turn on/off..."
-        if text_lower.contains("turn on") || text_lower.contains("turn off") {
-            let action = if text_lower.contains("turn on") { "on" } else { "off" };
-            let mut params = HashMap::new();
-            params.insert("action".to_string(), Value::String(action.to_string()));
-            
+            let reasoning = pattern.render_reasoning(&params);
             if let Ok(intent) = self.generate(
-                "device.control".to_string(),
-                0.7,
+                pattern.intent_type.clone(),
+                pattern.confidence,
                 params,
-                format!("User wants to turn {} a device", action),
+                reasoning,
             ) {
                 intents.push(intent);
             }
         }
 
-        // Pattern: "what's the weather" / "weather"
-        if text_lower.contains("weather") {
-            if let Ok(intent) = self.generate(
-                "weather.query".to_string(),
-                0.9,
-                HashMap::new(),
-                "User asking about weather".to_string(),
-            ) {
-                intents.push(intent);
-            }
-        }
+        dedup_intents(intents)
+    }
 
-        // Pattern: "what time" / "current time"
-        if text_lower.contains("what time") || text_lower.contains("current time") {
-            if let Ok(intent) = self.generate(
-                "time.query".to_string(),
-                0.95,
-                HashMap::new(),
-                "User asking about current time".to_string(),
-            ) {
-                intents.push(intent);
-            }
-        }
+    /// Extract intents from text using an LLM
+    ///
+    /// The model is asked for a JSON array of intents; the first valid array
+    /// in its response is used, so surrounding prose is tolerated. Each
+    /// proposed intent goes through `generate` and `validate`, and those that
+    /// are malformed or fail (e.g. low confidence) are dropped.
+    pub fn parse_with_llm(&self, text: &str, llm: &dyn LlmProvider) -> Result<Vec<Intent>> {
+        let prompt = format!(
+            "Extract the user's intents from the text below.\n\
+             Respond with a JSON array of objects with the fields \
+             \"intent_type\" (dotted, e.g. \"device.control\"), \
+             \"confidence\" (0.0 - 1.0), \"parameters\" (object) and \"reasoning\" (string).\n\
+             Respond with [] if there is no actionable intent.\n\n\
+             Text: {}",
+            text
+        );
+        let options = LlmOptions {
+            temperature: 0.0,
+            ..LlmOptions::default()
+        };
 
-        intents
+        let response = llm.complete(&prompt, &options)?;
+        let Some(Value::Array(items)) = extract_json_lenient(&response.text) else {
+            return Err(AgentError::InvalidIntent(
+                "LLM response did not contain a JSON array".to_string(),
+            ));
+        };
+
+        let intents = items
+            .into_iter()
+            .filter_map(|item| serde_json::from_value::<LlmIntent>(item).ok())
+            .filter_map(|p| {
+                self.generate(p.intent_type, p.confidence, p.parameters, p.reasoning)
+                    .ok()
+            })
+            .filter(|intent| self.validate(intent).is_ok())
+            .collect();
+
+        Ok(dedup_intents(intents))
+    }
+}
+
+/// Find the first valid JSON array embedded in text
+pub(crate) fn extract_json_array(text: &str) -> Option<Value> {
+    text.match_indices('[').find_map(|(start, _)| {
+        serde_json::Deserializer::from_str(&text[start..])
+            .into_iter::<Value>()
+            .next()
+            .and_then(|value| value.ok())
+            .filter(|value| value.is_array())
+    })
+}
+
+/// Worked examples for `json_intent_prompt`, used when their type is allowed
+const PROMPT_EXAMPLES: &[(&str, &str, &str)] = &[
+    (
+        "device.control",
+        "turn on the kitchen lights",
+        r#"[{"intent_type":"device.control","confidence":0.9,"parameters":{"device":"kitchen lights","action":"on"},"reasoning":"User asked to switch the lights on"}]"#,
+    ),
+    (
+        "reminder.create",
+        "remind me to call mom at 5pm",
+        r#"[{"intent_type":"reminder.create","confidence":0.85,"parameters":{"task":"call mom","time":"5pm"},"reasoning":"User asked for a reminder"}]"#,
+    ),
+    (
+        "weather.query",
+        "will it rain tomorrow?",
+        r#"[{"intent_type":"weather.query","confidence":0.9,"parameters":{"when":"tomorrow"},"reasoning":"User asked about the weather"}]"#,
+    ),
+];
+
+/// Build a prompt asking any model for a bare JSON array of intents
+/// Only `allowed_types` may appear in the answer; pair the response with
+/// `extract_json_lenient`, since models still tend to add code fences.
+pub fn json_intent_prompt(user_text: &str, allowed_types: &[&str]) -> String {
+    let mut prompt = String::from(
+        "You convert user requests into intents for a home assistant.\n\
+         Respond with ONLY a JSON array. No prose, no markdown, no code fences.\n\
+         Each element must be an object with exactly these fields:\n\
+         - \"intent_type\": one of the allowed types below\n\
+         - \"confidence\": number between 0.0 and 1.0\n\
+         - \"parameters\": object of string keys to JSON values\n\
+         - \"reasoning\": short string\n\
+         If the text contains no request matching an allowed type, respond with [].\n\n",
+    );
+
+    prompt.push_str("Allowed types:\n");
+    for intent_type in allowed_types {
+        prompt.push_str(&format!("- {}\n", intent_type));
     }
+
+    prompt.push_str("\nExamples:\nText: thanks, that's all\nJSON: []\n");
+    for (intent_type, text, json) in PROMPT_EXAMPLES {
+        if allowed_types.contains(intent_type) {
+            prompt.push_str(&format!("Text: {}\nJSON: {}\n", text, json));
+        }
+    }
+
+    prompt.push_str(&format!("\nText: {}\nJSON:", user_text.trim()));
+    prompt
+}
+
+/// Find a JSON array in a model response, looking inside ``` code fences first
+pub fn extract_json_lenient(text: &str) -> Option<Value> {
+    let fenced = text.split("```").skip(1).step_by(2).find_map(|block| {
+        // Drop the language tag line, e.g. "json"
+        let body = match block.split_once('\n') {
+            Some((tag, body)) if !tag.trim_start().starts_with('[') => body,
+            _ => block,
+        };
+        extract_json_array(body)
+    });
+
+    fenced.or_else(|| extract_json_array(text))
 }
 
 impl Default for IntentGenerator {
@@ -170,6 +563,93 @@ impl Default for IntentGenerator {
     }
 }
 
+/// Parse newline-delimited JSON produced by `IntentGenerator::to_ndjson`
+/// Blank lines are skipped.
+pub fn parse_ndjson(data: &str) -> Result<Vec<Intent>> {
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                AgentError::InvalidIntent(format!("Invalid intent on line {}: {}", i + 1, e))
+            })
+        })
+        .collect()
+}
+
+/// Serialize an intent compactly with object keys sorted at every level
+fn canonical_json(intent: &Intent) -> Result<String> {
+    fn sort_keys(value: Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let sorted: BTreeMap<String, Value> =
+                    map.into_iter().map(|(k, v)| (k, sort_keys(v))).collect();
+                Value::Object(sorted.into_iter().collect())
+            }
+            Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+            other => other,
+        }
+    }
+
+    Ok(serde_json::to_string(&sort_keys(serde_json::to_value(intent)?))?)
+}
+
+fn intent_mac(intent: &Intent, key: &[u8]) -> Result<Hmac<Sha256>> {
+    let json = canonical_json(intent)?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .map_err(|e| AgentError::Config(format!("Invalid signing key: {}", e)))?;
+    mac.update(json.as_bytes());
+    Ok(mac)
+}
+
+/// HMAC-SHA256 of the intent's canonical JSON, hex encoded
+/// Lets the executor detect intents that were altered in transit.
+pub fn sign(intent: &Intent, key: &[u8]) -> Result<String> {
+    Ok(intent_mac(intent, key)?
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Check a signature produced by `sign` (constant-time comparison)
+pub fn verify(intent: &Intent, sig: &str, key: &[u8]) -> bool {
+    if sig.len() != 64 || !sig.is_ascii() {
+        return false;
+    }
+
+    let Ok(expected) = (0..sig.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&sig[i..i + 2], 16))
+        .collect::<std::result::Result<Vec<u8>, _>>()
+    else {
+        return false;
+    };
+
+    intent_mac(intent, key)
+        .map(|mac| mac.verify_slice(&expected).is_ok())
+        .unwrap_or(false)
+}
+
+/// Collapse intents with the same type and parameters, keeping the most confident
+/// Order of first appearance is preserved.
+pub fn dedup_intents(intents: Vec<Intent>) -> Vec<Intent> {
+    let mut unique: Vec<Intent> = Vec::new();
+
+    for intent in intents {
+        match unique.iter_mut().find(|existing| {
+            existing.intent_type == intent.intent_type && existing.parameters == intent.parameters
+        }) {
+            Some(existing) if intent.confidence > existing.confidence => *existing = intent,
+            Some(_) => {}
+            None => unique.push(intent),
+        }
+    }
+
+    unique
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,4 +751,502 @@ mod tests {
         assert!(json.contains("device.control"));
         assert!(json.contains("light"));
     }
+
+    #[test]
+    fn test_parse_from_text_device_action_param() {
+        let generator = IntentGenerator::new();
+        let intents = generator.parse_from_text("Turn Off the kitchen light");
+
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].parameters["action"], Value::String("off".to_string()));
+        assert_eq!(intents[0].reasoning, "User wants to turn off a device");
+    }
+
+    #[test]
+    fn test_custom_pattern_named_group() {
+        let mut generator = IntentGenerator::new();
+        generator.add_pattern(
+            IntentPattern::new(r"call (?P<name>\w+)", "phone.call", 0.8, "User wants to call {name}")
+                .unwrap(),
+        );
+
+        let intents = generator.parse_from_text("please call alice now");
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].intent_type, "phone.call");
+        assert_eq!(intents[0].parameters["name"], Value::String("alice".to_string()));
+        assert_eq!(intents[0].reasoning, "User wants to call alice");
+    }
+
+    #[test]
+    fn test_custom_pattern_numbered_group() {
+        let mut generator = IntentGenerator::new();
+        generator.add_pattern(
+            IntentPattern::new(r"set volume to (\d+)", "media.volume", 0.8, "Adjust volume")
+                .unwrap()
+                .with_param("level", 1),
+        );
+
+        let intents = generator.parse_from_text("set volume to 40");
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].parameters["level"], Value::String("40".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let result = IntentPattern::new(r"call (", "phone.call", 0.8, "Call");
+        assert!(matches!(result, Err(AgentError::Config(_))));
+    }
+
+    #[test]
+    fn test_custom_permission_prefix() {
+        let mut generator = IntentGenerator::new();
+        assert!(!generator.requires_permission("smarthome.lock"));
+
+        generator.add_permission_prefix("smarthome.");
+        let intent = generator
+            .generate(
+                "smarthome.lock".to_string(),
+                0.8,
+                HashMap::new(),
+                "Lock the front door".to_string(),
+            )
+            .unwrap();
+        assert!(intent.requires_permission);
+
+        // Defaults are still in place
+        assert!(generator.requires_permission("device.control"));
+    }
+
+    #[test]
+    fn test_with_permission_prefixes_replaces_defaults() {
+        let generator = IntentGenerator::new().with_permission_prefixes(vec!["vault.".to_string()]);
+
+        assert!(generator.requires_permission("vault.open"));
+        assert!(!generator.requires_permission("device.control"));
+    }
+
+    #[test]
+    fn test_parse_from_text_dedups_intents() {
+        let mut generator = IntentGenerator::new();
+        generator.add_pattern(
+            IntentPattern::new(r"(?i)switch (?P<action>on|off)", "device.control", 0.9, "Switch device")
+                .unwrap()
+                .with_lowercase_values(),
+        );
+
+        let intents = generator.parse_from_text("turn on the lights, switch on the fan");
+        let device: Vec<_> = intents
+            .iter()
+            .filter(|i| i.intent_type == "device.control")
+            .collect();
+        assert_eq!(device.len(), 1);
+        assert_eq!(device[0].confidence, 0.9);
+    }
+
+    #[test]
+    fn test_dedup_intents_keeps_distinct_parameters() {
+        let generator = IntentGenerator::new();
+        let mut on = HashMap::new();
+        on.insert("action".to_string(), Value::String("on".to_string()));
+        let mut off = HashMap::new();
+        off.insert("action".to_string(), Value::String("off".to_string()));
+
+        let intents = vec![
+            generator.generate("device.control".to_string(), 0.6, on.clone(), "a".to_string()).unwrap(),
+            generator.generate("device.control".to_string(), 0.7, off, "b".to_string()).unwrap(),
+            generator.generate("device.control".to_string(), 0.8, on, "c".to_string()).unwrap(),
+        ];
+
+        let unique = dedup_intents(intents);
+        assert_eq!(unique.len(), 2);
+        assert_eq!(unique[0].confidence, 0.8);
+        assert_eq!(unique[1].confidence, 0.7);
+    }
+
+    #[test]
+    fn test_rank_by_priority() {
+        let generator = IntentGenerator::new();
+        let low = generator
+            .generate("weather.query".to_string(), 0.9, HashMap::new(), "Weather".to_string())
+            .unwrap()
+            .with_priority(10);
+        let high = generator
+            .generate("time.query".to_string(), 0.6, HashMap::new(), "Time".to_string())
+            .unwrap()
+            .with_priority(200);
+
+        let ranked = generator.rank(vec![low, high]);
+        assert_eq!(ranked[0].intent_type, "time.query");
+        assert_eq!(ranked[1].intent_type, "weather.query");
+    }
+
+    #[test]
+    fn test_default_priority_favors_permission_gated_intents() {
+        let generator = IntentGenerator::new();
+        let device = generator
+            .generate("device.control".to_string(), 0.8, HashMap::new(), "Device".to_string())
+            .unwrap();
+        let weather = generator
+            .generate("weather.query".to_string(), 0.8, HashMap::new(), "Weather".to_string())
+            .unwrap();
+
+        assert!(device.priority > weather.priority);
+    }
+
+    #[test]
+    fn test_deserialize_intent_without_priority() {
+        let json = r#"{
+            "id": "6f1c2a34-8d2b-4a8e-9a55-1f7e0c9b1d23",
+            "intent_type": "weather.query",
+            "confidence": 0.9,
+            "parameters": {},
+            "reasoning": "User asking about weather",
+            "requires_permission": false,
+            "target_module": "weather",
+            "created_at": "2024-01-01T00:00:00Z"
+        }"#;
+
+        let intent: Intent = serde_json::from_str(json).unwrap();
+        assert_eq!(intent.priority, 0);
+        assert_eq!(intent.intent_type, "weather.query");
+    }
+
+    struct FixedLlm(&'static str);
+
+    impl LlmProvider for FixedLlm {
+        fn complete(&self, _prompt: &str, _options: &LlmOptions) -> Result<crate::types::LlmResponse> {
+            Ok(crate::types::LlmResponse {
+                text: self.0.to_string(),
+                finish_reason: "stop".to_string(),
+                usage: crate::types::LlmUsage {
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    total_tokens: 0,
+                },
+            })
+        }
+
+        fn name(&self) -> &str {
+            "fixed"
+        }
+    }
+
+    #[test]
+    fn test_parse_with_llm() {
+        let generator = IntentGenerator::new();
+        let llm = FixedLlm(
+            r#"Sure! Here are the intents [in order]:
+            [
+                {"intent_type": "device.control", "confidence": 0.85,
+                 "parameters": {"device": "lights", "action": "on"},
+                 "reasoning": "User wants the lights on"},
+                {"intent_type": "weather.query", "confidence": 0.9,
+                 "parameters": {}, "reasoning": "User asked about weather"},
+                {"intent_type": "time.query", "confidence": 0.2,
+                 "parameters": {}, "reasoning": "Unsure"},
+                {"intent_type": "music.play", "parameters": {}},
+                "not an intent"
+            ]
+            Let me know if you need anything else."#,
+        );
+
+        let intents = generator
+            .parse_with_llm("turn on the lights and what's the weather", &llm)
+            .unwrap();
+        assert_eq!(intents.len(), 2);
+        assert_eq!(intents[0].intent_type, "device.control");
+        assert_eq!(intents[0].parameters["device"], Value::String("lights".to_string()));
+        assert!(intents[0].requires_permission);
+        assert_eq!(intents[1].intent_type, "weather.query");
+    }
+
+    #[test]
+    fn test_parse_with_llm_without_array() {
+        let generator = IntentGenerator::new();
+        let llm = FixedLlm("I could not find any intent.");
+
+        let result = generator.parse_with_llm("hello", &llm);
+        assert!(matches!(result, Err(AgentError::InvalidIntent(_))));
+    }
+
+    #[test]
+    fn test_ndjson_round_trip() {
+        let generator = IntentGenerator::new();
+        let mut params = HashMap::new();
+        params.insert("device".to_string(), Value::String("lamp".to_string()));
+
+        let intents = vec![
+            generator
+                .generate("device.control".to_string(), 0.9, params, "Lamp on".to_string())
+                .unwrap(),
+            generator
+                .generate("weather.query".to_string(), 0.8, HashMap::new(), "Weather".to_string())
+                .unwrap(),
+            generator
+                .generate("time.query".to_string(), 0.95, HashMap::new(), "Time".to_string())
+                .unwrap(),
+        ];
+
+        let ndjson = generator.to_ndjson(&intents).unwrap();
+        assert_eq!(ndjson.lines().count(), 3);
+        assert!(ndjson.ends_with('\n'));
+
+        let parsed = parse_ndjson(&format!("{}\n", ndjson)).unwrap();
+        assert_eq!(parsed.len(), 3);
+        for (original, parsed) in intents.iter().zip(&parsed) {
+            assert_eq!(original.id, parsed.id);
+            assert_eq!(original.intent_type, parsed.intent_type);
+            assert_eq!(original.parameters, parsed.parameters);
+            assert_eq!(original.requires_permission, parsed.requires_permission);
+        }
+    }
+
+    #[test]
+    fn test_parse_ndjson_reports_line() {
+        match parse_ndjson("\nnot json\n") {
+            Err(AgentError::InvalidIntent(msg)) => assert!(msg.contains("line 2")),
+            other => panic!("expected InvalidIntent, got {:?}", other.map(|v| v.len())),
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = b"shared-secret";
+        let mut params = HashMap::new();
+        params.insert("device".to_string(), Value::String("heater".to_string()));
+        params.insert("action".to_string(), Value::String("on".to_string()));
+        let intent = Intent::new("device.control".to_string(), 0.9, params, "Heat".to_string());
+
+        let sig = sign(&intent, key).unwrap();
+        assert_eq!(sig.len(), 64);
+        assert!(verify(&intent, &sig, key));
+        assert!(!verify(&intent, &sig, b"other-key"));
+        assert!(!verify(&intent, "not-a-signature", key));
+
+        // Stable across re-serialization
+        let copy: Intent = serde_json::from_str(&serde_json::to_string(&intent).unwrap()).unwrap();
+        assert_eq!(sign(&copy, key).unwrap(), sig);
+    }
+
+    #[test]
+    fn test_verify_detects_tampering() {
+        let key = b"shared-secret";
+        let mut params = HashMap::new();
+        params.insert("device".to_string(), Value::String("heater".to_string()));
+        let intent = Intent::new("device.control".to_string(), 0.9, params, "Heat".to_string());
+        let sig = sign(&intent, key).unwrap();
+
+        let mut retyped = intent.clone();
+        retyped.intent_type = "device.unlock".to_string();
+        assert!(!verify(&retyped, &sig, key));
+
+        let mut reparam = intent.clone();
+        reparam
+            .parameters
+            .insert("device".to_string(), Value::String("front_door".to_string()));
+        assert!(!verify(&reparam, &sig, key));
+    }
+
+    #[test]
+    fn test_canonical_json_is_stable() {
+        let generator = IntentGenerator::new();
+        let keys = ["zone", "action", "device", "brightness", "color"];
+
+        let mut forward = HashMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            forward.insert(key.to_string(), Value::from(i));
+        }
+        let mut backward = HashMap::new();
+        for (i, key) in keys.iter().enumerate().rev() {
+            backward.insert(key.to_string(), Value::from(i));
+        }
+
+        let a = Intent::new("device.control".to_string(), 0.9, forward, "Lights".to_string());
+        let mut b = a.clone();
+        b.parameters = backward;
+
+        let json_a = generator.to_canonical_json(&a).unwrap();
+        let json_b = generator.to_canonical_json(&b).unwrap();
+        assert_eq!(json_a, json_b);
+        assert!(!json_a.contains('\n'));
+        assert!(json_a.contains(r#""parameters":{"action":1,"brightness":3,"color":4,"device":2,"zone":0}"#));
+    }
+
+    #[test]
+    fn test_calibration_applied_before_gate() {
+        let mut generator = IntentGenerator::new();
+        let generate = |generator: &IntentGenerator| {
+            generator.generate(
+                "query.time".to_string(),
+                0.8,
+                HashMap::new(),
+                "User asked for the time".to_string(),
+            )
+        };
+
+        assert!((generate(&generator).unwrap().confidence - 0.8).abs() < f32::EPSILON);
+
+        generator.set_calibration(Box::new(|c| c / 2.0));
+        assert!(matches!(generate(&generator), Err(AgentError::InvalidIntent(_))));
+
+        generator.set_calibration(Box::new(|c| c * 2.0));
+        assert_eq!(generate(&generator).unwrap().confidence, 1.0);
+    }
+
+    #[test]
+    fn test_min_confidence_configurable() {
+        let generate = |generator: &IntentGenerator| {
+            generator.generate(
+                "query.weather".to_string(),
+                0.8,
+                HashMap::new(),
+                "User asked about weather".to_string(),
+            )
+        };
+
+        assert!(generate(&IntentGenerator::new()).is_ok());
+
+        let strict = IntentGenerator::new().with_min_confidence(0.9).unwrap();
+        assert!(matches!(generate(&strict), Err(AgentError::InvalidIntent(_))));
+
+        let mut generator = IntentGenerator::new();
+        generator.set_min_confidence(0.9).unwrap();
+        assert!(generate(&generator).is_err());
+    }
+
+    #[test]
+    fn test_min_confidence_validation() {
+        assert!(matches!(
+            IntentGenerator::new().with_min_confidence(1.5),
+            Err(AgentError::Config(_))
+        ));
+
+        let mut generator = IntentGenerator::new();
+        assert!(generator.set_min_confidence(-0.1).is_err());
+        assert!(generator.set_min_confidence(f32::NAN).is_err());
+        assert!(generator.set_min_confidence(0.0).is_ok());
+    }
+
+    fn reminder_params(text: &str) -> HashMap<String, Value> {
+        let intents = IntentGenerator::new().parse_from_text(text);
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].intent_type, "reminder.create");
+        intents[0].parameters.clone()
+    }
+
+    /// The parsed `time` parameter, in local time as phrases are read
+    fn time_param(params: &HashMap<String, Value>) -> chrono::DateTime<chrono::Local> {
+        let time = params["time"].as_str().unwrap();
+        chrono::DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&chrono::Local)
+    }
+
+    #[test]
+    fn test_reminder_at_clock_time() {
+        use chrono::Timelike;
+
+        let params = reminder_params("Remind me to call mom at 5pm");
+
+        assert_eq!(params["task"], "call mom");
+        assert_eq!(params["text"], "Remind me to call mom at 5pm");
+        let time = time_param(&params);
+        assert_eq!((time.hour(), time.minute()), (17, 0));
+        assert!(time > Utc::now());
+    }
+
+    #[test]
+    fn test_reminder_tomorrow_morning() {
+        use chrono::Timelike;
+
+        let params = reminder_params("remind me to water the plants tomorrow morning.");
+
+        assert_eq!(params["task"], "water the plants");
+        let time = time_param(&params);
+        assert_eq!(time.date_naive(), chrono::Local::now().date_naive() + chrono::Duration::days(1));
+        assert_eq!(time.hour(), 9);
+    }
+
+    #[test]
+    fn test_reminder_without_time() {
+        let params = reminder_params("remind me to look at the report");
+
+        assert_eq!(params["task"], "look at the report");
+        assert!(!params.contains_key("time"));
+    }
+
+    #[test]
+    fn test_json_intent_prompt_lists_allowed_types() {
+        let prompt = json_intent_prompt("turn off the fan", &["device.control", "time.query"]);
+
+        assert!(prompt.contains("- device.control\n"));
+        assert!(prompt.contains("- time.query\n"));
+        assert!(prompt.contains("ONLY a JSON array"));
+        assert!(prompt.contains("turn on the kitchen lights"));
+        // Examples for types that aren't allowed are left out
+        assert!(!prompt.contains("reminder.create"));
+        assert!(prompt.ends_with("Text: turn off the fan\nJSON:"));
+    }
+
+    #[test]
+    fn test_extract_json_lenient_strips_fences() {
+        let response = "Sure! Here you go:\n```json\n[{\"intent_type\": \"time.query\", \"confidence\": 0.9}]\n```\nLet me know [if] you need more.";
+
+        let array = extract_json_lenient(response).unwrap();
+        assert_eq!(array[0]["intent_type"], "time.query");
+
+        let bare_fence = "```\n[]\n```";
+        assert_eq!(extract_json_lenient(bare_fence), Some(serde_json::json!([])));
+        assert_eq!(extract_json_lenient("[1, 2]"), Some(serde_json::json!([1, 2])));
+        assert_eq!(extract_json_lenient("```json\nnot json\n```"), None);
+    }
+
+    #[test]
+    fn test_merge_equivalent_intents() {
+        let generator = IntentGenerator::new();
+        let mut light_params = HashMap::new();
+        light_params.insert("device".to_string(), Value::String("lamp".to_string()));
+        let mut control_params = light_params.clone();
+        control_params.insert("action".to_string(), Value::String("on".to_string()));
+
+        let intents = vec![
+            generator.generate("light.on".to_string(), 0.6, light_params, "pattern".to_string()).unwrap(),
+            generator.generate("weather.query".to_string(), 0.9, HashMap::new(), "w".to_string()).unwrap(),
+            generator.generate("device.control".to_string(), 0.8, control_params, "llm".to_string()).unwrap(),
+        ];
+
+        let merged = generator.merge_equivalent(intents, &[("light.on", "device.control")]);
+
+        assert_eq!(merged.len(), 2);
+        let control = merged.iter().find(|i| i.intent_type == "device.control").unwrap();
+        assert_eq!(control.confidence, 0.8);
+        assert_eq!(control.parameters["device"], "lamp");
+        assert_eq!(control.parameters["action"], "on");
+        assert!(control.requires_permission);
+        assert!(merged.iter().all(|i| i.intent_type != "light.on"));
+    }
+
+    #[test]
+    fn test_merge_equivalent_keeps_conflicting_parameters_apart() {
+        let generator = IntentGenerator::new();
+        let mut on = HashMap::new();
+        on.insert("action".to_string(), Value::String("on".to_string()));
+        let mut off = HashMap::new();
+        off.insert("action".to_string(), Value::String("off".to_string()));
+
+        let intents = vec![
+            generator.generate("switch.toggle".to_string(), 0.7, on, "a".to_string()).unwrap(),
+            generator.generate("device.control".to_string(), 0.8, off, "b".to_string()).unwrap(),
+            generator.generate("light.set".to_string(), 0.9, HashMap::new(), "c".to_string()).unwrap(),
+        ];
+
+        // Transitive: switch.toggle ~ light.set ~ device.control
+        let merged = generator.merge_equivalent(
+            intents,
+            &[("switch.toggle", "light.set"), ("light.set", "device.control")],
+        );
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].intent_type, "light.set");
+        assert_eq!(merged[0].parameters["action"], "on");
+        assert_eq!(merged[1].intent_type, "device.control");
+    }
 }
@@ -0,0 +1,129 @@
+//! MQTT transport for intents
+//!
+//! Publishes intents to `{prefix}/{target_module}/{intent_type}` so device
+//! agents on a home-automation bus can subscribe per module.
+//!
+//! The sink does not make policy decisions. Only publish intents that have
+//! already been approved by `PolicyEngine::check_intent`, or use
+//! `publish_checked` to run the check as part of publishing.
+
+use crate::error::{AgentError, Result};
+use crate::policy::PolicyEngine;
+use crate::types::Intent;
+
+/// Minimal publishing interface, so the sink can be tested without a broker
+pub trait MqttPublisher: Send + Sync {
+    fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<()>;
+}
+
+/// `rumqttc` blocking client (requires the `mqtt` feature)
+/// The caller is responsible for driving the client's event loop.
+#[cfg(feature = "mqtt")]
+impl MqttPublisher for rumqttc::Client {
+    fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<()> {
+        rumqttc::Client::publish(self, topic, rumqttc::QoS::AtLeastOnce, false, payload)
+            .map_err(|e| AgentError::Other(format!("MQTT publish failed: {}", e)))
+    }
+}
+
+/// Publishes approved intents as JSON over MQTT
+pub struct MqttIntentSink<C: MqttPublisher> {
+    client: C,
+    topic_prefix: String,
+}
+
+impl<C: MqttPublisher> MqttIntentSink<C> {
+    pub fn new(client: C, topic_prefix: String) -> Self {
+        Self {
+            client,
+            topic_prefix: topic_prefix.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Topic an intent is published to
+    /// Falls back to the first segment of `intent_type` when no target module is set.
+    pub fn topic_for(&self, intent: &Intent) -> Result<String> {
+        let module = intent
+            .target_module
+            .as_deref()
+            .or_else(|| intent.intent_type.split('.').next())
+            .filter(|m| !m.is_empty())
+            .ok_or_else(|| AgentError::InvalidIntent("Intent has no target module".to_string()))?;
+
+        Ok(format!("{}/{}/{}", self.topic_prefix, module, intent.intent_type))
+    }
+
+    /// Publish an intent that has already been cleared by the policy engine
+    pub fn publish(&self, intent: &Intent) -> Result<()> {
+        let topic = self.topic_for(intent)?;
+        let payload = serde_json::to_vec(intent)?;
+        self.client.publish(&topic, payload)
+    }
+
+    /// Check the intent against `policy`, then publish it
+    pub fn publish_checked(&self, intent: &Intent, policy: &PolicyEngine) -> Result<()> {
+        policy.check_intent(intent)?;
+        self.publish(intent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingPublisher {
+        published: Mutex<Vec<(String, Vec<u8>)>>,
+    }
+
+    impl MqttPublisher for &RecordingPublisher {
+        fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<()> {
+            self.published.lock().unwrap().push((topic.to_string(), payload));
+            Ok(())
+        }
+    }
+
+    fn weather_intent() -> Intent {
+        let mut params = HashMap::new();
+        params.insert("location".to_string(), serde_json::json!("Lisbon"));
+        let mut intent = Intent::new("weather.query".to_string(), 0.9, params, "Weather".to_string());
+        intent.target_module = Some("weather".to_string());
+        intent
+    }
+
+    #[test]
+    fn test_publish_topic_and_payload() {
+        let recorder = RecordingPublisher::default();
+        let sink = MqttIntentSink::new(&recorder, "home/agent/".to_string());
+        let intent = weather_intent();
+
+        sink.publish(&intent).unwrap();
+
+        let published = recorder.published.lock().unwrap();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].0, "home/agent/weather/weather.query");
+
+        let payload: Intent = serde_json::from_slice(&published[0].1).unwrap();
+        assert_eq!(payload.id, intent.id);
+        assert_eq!(payload.parameters["location"], "Lisbon");
+    }
+
+    #[test]
+    fn test_publish_checked_blocks_unapproved() {
+        let recorder = RecordingPublisher::default();
+        let sink = MqttIntentSink::new(&recorder, "home".to_string());
+        let policy = PolicyEngine::new(vec!["device".to_string()]);
+
+        let mut intent = Intent::new("device.control".to_string(), 0.9, HashMap::new(), "Lamp".to_string())
+            .with_permission(true);
+        intent.target_module = Some("device".to_string());
+
+        assert!(sink.publish_checked(&intent, &policy).is_err());
+        assert!(recorder.published.lock().unwrap().is_empty());
+
+        sink.publish_checked(&weather_intent(), &policy).unwrap();
+        assert_eq!(recorder.published.lock().unwrap()[0].0, "home/weather/weather.query");
+    }
+}
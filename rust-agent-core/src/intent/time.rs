@@ -0,0 +1,309 @@
+//! Lightweight time phrase parsing
+//!
+//! Understands the handful of phrases people use in reminders: "at 5pm",
+//! "tomorrow morning", "tonight at 9:30", "in 20 minutes", "at noon".
+//! Clock times are interpreted in the local timezone, or the one given to the
+//! `_in` variants, and returned as UTC. Anything else is rejected rather than
+//! guessed.
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+
+/// Part of the day and the time used when no clock time is given
+const DAY_PARTS: &[(&str, u32)] = &[
+    ("morning", 9),
+    ("afternoon", 14),
+    ("evening", 18),
+    ("night", 20),
+];
+
+/// Hour, minute and whether it is pm, when am/pm was given
+type Clock = (u32, u32, Option<bool>);
+
+/// Resolve a time phrase relative to `now`, reading clock times as local time
+/// Times of day without an explicit day refer to their next occurrence
+pub fn parse_time_phrase(phrase: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    parse_time_phrase_in(phrase, now, &Local)
+}
+
+/// Resolve a time phrase relative to `now`, reading clock times in `tz`
+pub fn parse_time_phrase_in<Tz: TimeZone>(
+    phrase: &str,
+    now: DateTime<Utc>,
+    tz: &Tz,
+) -> Option<DateTime<Utc>> {
+    let phrase = phrase.to_lowercase().replace("a.m.", "am").replace("p.m.", "pm");
+    let tokens: Vec<&str> = phrase
+        .trim()
+        .trim_end_matches(['.', '!', '?', ','])
+        .split_whitespace()
+        .collect();
+
+    match tokens.as_slice() {
+        [] => None,
+        ["in", amount, unit] if *amount != "the" => parse_offset(amount, unit).map(|d| now + d),
+        _ => parse_moment(&tokens, now, tz),
+    }
+}
+
+/// Split a trailing time phrase off `text`, e.g.
+/// "call mom at 5pm" -> ("call mom", Some(17:00))
+/// The longest trailing phrase that parses wins; `text` is returned whole
+/// when none does. Clock times are read as local time.
+pub fn split_time_phrase(text: &str, now: DateTime<Utc>) -> (&str, Option<DateTime<Utc>>) {
+    split_time_phrase_in(text, now, &Local)
+}
+
+/// `split_time_phrase`, reading clock times in `tz`
+pub fn split_time_phrase_in<'a, Tz: TimeZone>(
+    text: &'a str,
+    now: DateTime<Utc>,
+    tz: &Tz,
+) -> (&'a str, Option<DateTime<Utc>>) {
+    let starts = text
+        .char_indices()
+        .filter(|(i, c)| !c.is_whitespace() && (*i == 0 || text[..*i].ends_with(char::is_whitespace)))
+        .map(|(i, _)| i)
+        .skip(1);
+
+    for start in starts {
+        if let Some(time) = parse_time_phrase_in(&text[start..], now, tz) {
+            return (text[..start].trim_end(), Some(time));
+        }
+    }
+
+    (text, None)
+}
+
+/// "in 20 minutes", "in an hour", "in 2 days"
+fn parse_offset(amount: &str, unit: &str) -> Option<Duration> {
+    let amount: i64 = match amount {
+        "a" | "an" | "one" => 1,
+        _ => amount.parse().ok().filter(|n| *n > 0)?,
+    };
+
+    match unit.trim_end_matches('s') {
+        "minute" | "min" => Some(Duration::minutes(amount)),
+        "hour" | "hr" => Some(Duration::hours(amount)),
+        "day" => Some(Duration::days(amount)),
+        "week" => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// Day, part of day and clock time in any order: "tomorrow morning",
+/// "at 5pm", "5:30 pm tomorrow", "tonight at 9"
+fn parse_moment<Tz: TimeZone>(tokens: &[&str], now: DateTime<Utc>, tz: &Tz) -> Option<DateTime<Utc>> {
+    let mut day_offset: Option<i64> = None;
+    let mut part: Option<(&str, u32)> = None;
+    let mut clock: Option<Clock> = None;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = tokens[i];
+        match token {
+            "today" if day_offset.is_none() => day_offset = Some(0),
+            "tomorrow" if day_offset.is_none() => day_offset = Some(1),
+            "tonight" if day_offset.is_none() && part.is_none() => {
+                day_offset = Some(0);
+                part = Some(("night", 20));
+            }
+            "this" | "in" => {
+                // "this evening", "in the morning"
+                let next = match (token, tokens.get(i + 1)) {
+                    ("in", Some(&"the")) => i + 2,
+                    ("this", _) => i + 1,
+                    _ => return None,
+                };
+                part = Some(day_part(tokens.get(next)?).filter(|_| part.is_none())?);
+                i = next;
+            }
+            "at" | "by" if clock.is_none() => {
+                // Must be followed by a clock time, where a bare hour is allowed
+                let (parsed, consumed) = parse_clock(&tokens[i + 1..], true)?;
+                clock = Some(parsed);
+                i += consumed;
+            }
+            _ => {
+                if let Some(found) = day_part(token).filter(|_| part.is_none()) {
+                    part = Some(found);
+                } else if clock.is_none() {
+                    let (parsed, consumed) = parse_clock(&tokens[i..], false)?;
+                    clock = Some(parsed);
+                    i += consumed - 1;
+                } else {
+                    return None;
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let (hour, minute) = match (clock, part) {
+        (Some((hour, minute, Some(pm))), _) => (to_24h(hour, pm), minute),
+        // "tonight at 9" means 21:00
+        (Some((hour, minute, None)), Some((name, _))) if hour < 12 && name != "morning" => (hour + 12, minute),
+        (Some((hour, minute, None)), _) => (hour, minute),
+        (None, Some((_, hour))) => (hour, 0),
+        (None, None) if day_offset.is_some() => (9, 0),
+        (None, None) => return None,
+    };
+
+    let time = NaiveTime::from_hms_opt(hour, minute, 0)?;
+    let date = now.with_timezone(tz).date_naive() + Duration::days(day_offset.unwrap_or(0));
+    let resolved = resolve_local(tz, date, time)?;
+
+    if day_offset.is_none() && resolved <= now {
+        resolve_local(tz, date + Duration::days(1), time)
+    } else {
+        Some(resolved)
+    }
+}
+
+/// `date` at `time` in `tz`, as UTC
+/// A time skipped by a DST jump resolves to the same wall time an hour later.
+fn resolve_local<Tz: TimeZone>(tz: &Tz, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+    let naive = date.and_time(time);
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(naive + Duration::hours(1))).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+fn day_part(token: &str) -> Option<(&'static str, u32)> {
+    DAY_PARTS.iter().copied().find(|(name, _)| *name == token)
+}
+
+/// Parse a clock time from the start of `tokens`, returning it with the
+/// number of tokens consumed.
+/// Bare hours ("at 5") are only accepted when `allow_bare_hour` is set.
+fn parse_clock(tokens: &[&str], allow_bare_hour: bool) -> Option<(Clock, usize)> {
+    let first = *tokens.first()?;
+    match first {
+        "noon" | "midday" => return Some(((12, 0, None), 1)),
+        "midnight" => return Some(((0, 0, None), 1)),
+        _ => {}
+    }
+
+    // Meridiem may be attached ("5pm") or the next token ("5 pm")
+    let (digits, meridiem, consumed) = match (first.strip_suffix("am"), first.strip_suffix("pm")) {
+        (Some(digits), _) => (digits, Some(false), 1),
+        (_, Some(digits)) => (digits, Some(true), 1),
+        _ => match tokens.get(1) {
+            Some(&"am") => (first, Some(false), 2),
+            Some(&"pm") => (first, Some(true), 2),
+            _ => (first, None, 1),
+        },
+    };
+
+    let (hour, minute) = match digits.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse().ok()?, minute.parse().ok()?),
+        Some(_) => return None,
+        None if meridiem.is_some() || allow_bare_hour => (digits.parse().ok()?, 0),
+        None => return None,
+    };
+
+    let valid_hour = match meridiem {
+        Some(_) => (1..=12).contains(&hour),
+        None => hour <= 23,
+    };
+    if !valid_hour || minute > 59 {
+        return None;
+    }
+
+    Some(((hour, minute, meridiem), consumed))
+}
+
+fn to_24h(hour: u32, pm: bool) -> u32 {
+    match (hour, pm) {
+        (12, false) => 0,
+        (12, true) => 12,
+        (hour, true) => hour + 12,
+        (hour, false) => hour,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn parse_utc(phrase: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        parse_time_phrase_in(phrase, now, &Utc)
+    }
+
+    fn split_utc(text: &str, now: DateTime<Utc>) -> (&str, Option<DateTime<Utc>>) {
+        split_time_phrase_in(text, now, &Utc)
+    }
+
+    #[test]
+    fn test_clock_times() {
+        let now = at("2024-03-10T08:00:00Z");
+
+        assert_eq!(parse_utc("at 5pm", now), Some(at("2024-03-10T17:00:00Z")));
+        assert_eq!(parse_utc("5:30 p.m.", now), Some(at("2024-03-10T17:30:00Z")));
+        assert_eq!(parse_utc("at 17:00", now), Some(at("2024-03-10T17:00:00Z")));
+        assert_eq!(parse_utc("at noon", now), Some(at("2024-03-10T12:00:00Z")));
+        // Already passed today
+        assert_eq!(parse_utc("at 7am", now), Some(at("2024-03-11T07:00:00Z")));
+    }
+
+    #[test]
+    fn test_days_and_parts_of_day() {
+        let now = at("2024-03-10T08:00:00Z");
+
+        assert_eq!(parse_utc("tomorrow morning", now), Some(at("2024-03-11T09:00:00Z")));
+        assert_eq!(parse_utc("tonight at 9", now), Some(at("2024-03-10T21:00:00Z")));
+        assert_eq!(parse_utc("this evening", now), Some(at("2024-03-10T18:00:00Z")));
+        assert_eq!(parse_utc("at 5pm tomorrow", now), Some(at("2024-03-11T17:00:00Z")));
+        assert_eq!(parse_utc("in the afternoon", now), Some(at("2024-03-10T14:00:00Z")));
+        assert_eq!(parse_utc("in 20 minutes", now), Some(at("2024-03-10T08:20:00Z")));
+        assert_eq!(parse_utc("in an hour", now), Some(at("2024-03-10T09:00:00Z")));
+    }
+
+    #[test]
+    fn test_rejects_unknown_phrases() {
+        let now = at("2024-03-10T08:00:00Z");
+
+        for phrase in ["", "soon", "at the office", "5", "at 25:00", "13pm", "in a while", "tomorrow tomorrow"] {
+            assert_eq!(parse_utc(phrase, now), None, "{}", phrase);
+        }
+    }
+
+    #[test]
+    fn test_split_utc() {
+        let now = at("2024-03-10T08:00:00Z");
+
+        assert_eq!(
+            split_utc("call mom at 5pm", now),
+            ("call mom", Some(at("2024-03-10T17:00:00Z")))
+        );
+        assert_eq!(
+            split_utc("look at the report tomorrow morning", now),
+            ("look at the report", Some(at("2024-03-11T09:00:00Z")))
+        );
+        assert_eq!(split_utc("call 5 people", now), ("call 5 people", None));
+        assert_eq!(split_utc("tomorrow", now), ("tomorrow", None));
+    }
+
+    #[test]
+    fn test_clock_times_in_local_zone() {
+        // UTC-5: 03:00Z on the 10th is still 22:00 on the 9th locally
+        let tz = chrono::FixedOffset::west_opt(5 * 3600).unwrap();
+        let now = at("2024-03-10T03:00:00Z");
+
+        assert_eq!(parse_time_phrase_in("at 5pm", now, &tz), Some(at("2024-03-10T22:00:00Z")));
+        assert_eq!(
+            parse_time_phrase_in("tomorrow morning", now, &tz),
+            Some(at("2024-03-10T14:00:00Z"))
+        );
+        // 9pm local has passed, so it rolls over to the next local day
+        assert_eq!(parse_time_phrase_in("at 9pm", now, &tz), Some(at("2024-03-11T02:00:00Z")));
+        assert_eq!(
+            split_time_phrase_in("call mom at 11pm", now, &tz),
+            ("call mom", Some(at("2024-03-10T04:00:00Z")))
+        );
+    }
+}
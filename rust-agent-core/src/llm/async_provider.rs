@@ -0,0 +1,183 @@
+//! Async LLM providers
+//!
+//! Non-blocking counterparts of `LlmProvider` for callers running on tokio.
+
+use super::{check_response_size, LlmProvider, OllamaProvider};
+use crate::error::{AgentError, Result};
+use crate::types::{LlmOptions, LlmResponse};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Async trait for LLM providers
+pub trait AsyncLlmProvider: Send + Sync {
+    /// Complete a prompt
+    fn complete(
+        &self,
+        prompt: &str,
+        options: &LlmOptions,
+    ) -> impl Future<Output = Result<LlmResponse>> + Send;
+
+    /// Get provider name
+    fn name(&self) -> &str;
+}
+
+impl OllamaProvider {
+    fn async_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        builder
+            .build()
+            .map_err(|e| AgentError::Llm(format!("Failed to build HTTP client: {}", e)))
+    }
+
+    /// Async counterpart of `read_body`
+    async fn read_body_async(mut response: reqwest::Response) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AgentError::Llm(format!("Failed to read Ollama response: {}", e)))?
+        {
+            body.extend_from_slice(&chunk);
+            check_response_size(&body, "Ollama")?;
+        }
+        Ok(body)
+    }
+
+    /// Async counterpart of `send_with_retry`
+    async fn send_with_retry_async(
+        &self,
+        path: &str,
+        request: &(impl Serialize + Sync),
+    ) -> Result<reqwest::Response> {
+        let url = format!("{}{}", self.base_url, path);
+        let client = self.async_client()?;
+        let mut attempt = 0;
+
+        loop {
+            let error = match client.post(&url).json(request).send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let message = format!("Ollama API returned error status: {}", response.status());
+                    if !response.status().is_server_error() {
+                        return Err(AgentError::Llm(message));
+                    }
+                    AgentError::LlmUnavailable(message)
+                }
+                Err(e) => {
+                    let message = format!("Failed to send request to Ollama: {}", e);
+                    if !(e.is_connect() || e.is_timeout()) {
+                        return Err(AgentError::Llm(message));
+                    }
+                    AgentError::LlmUnavailable(message)
+                }
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+
+            let delay = self.retry_backoff * 2u32.saturating_pow(attempt);
+            log::warn!("Ollama request failed (attempt {}), retrying in {:?}", attempt + 1, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl AsyncLlmProvider for OllamaProvider {
+    async fn complete(&self, prompt: &str, options: &LlmOptions) -> Result<LlmResponse> {
+        let request = self.build_request(prompt, options, false);
+        let response = self.send_with_retry_async("/api/generate", &request).await?;
+
+        let ollama_response = Self::parse_body(&Self::read_body_async(response).await?)?;
+
+        let text = ollama_response.response.clone();
+        Ok(Self::to_llm_response(text, &ollama_response))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Adapter running a blocking `LlmProvider` on tokio's blocking thread pool
+pub struct BlockingAdapter<P> {
+    inner: Arc<P>,
+}
+
+/// Wrap a synchronous provider so it can be awaited without stalling the runtime
+pub fn blocking_to_async<P: LlmProvider + 'static>(provider: P) -> BlockingAdapter<P> {
+    BlockingAdapter {
+        inner: Arc::new(provider),
+    }
+}
+
+impl<P: LlmProvider + 'static> AsyncLlmProvider for BlockingAdapter<P> {
+    async fn complete(&self, prompt: &str, options: &LlmOptions) -> Result<LlmResponse> {
+        let inner = Arc::clone(&self.inner);
+        let prompt = prompt.to_string();
+        let options = options.clone();
+
+        tokio::task::spawn_blocking(move || inner.complete(&prompt, &options))
+            .await
+            .map_err(|e| AgentError::Llm(format!("Blocking LLM task failed: {}", e)))?
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::tests::spawn_http_server;
+    use crate::llm::MockLlmProvider;
+
+    #[tokio::test]
+    async fn test_async_ollama_complete() {
+        let body = r#"{"response":"Hello async","done":true,"prompt_eval_count":3,"eval_count":2}"#;
+        let base_url = spawn_http_server(vec![(200, body.to_string())]);
+        let provider = OllamaProvider::with_endpoint("llama2".to_string(), base_url);
+
+        let response = AsyncLlmProvider::complete(&provider, "Hi", &LlmOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "Hello async");
+        assert_eq!(response.finish_reason, "stop");
+        assert_eq!(response.usage.total_tokens, 5);
+    }
+
+    #[tokio::test]
+    async fn test_async_ollama_retries_server_error() {
+        let body = r#"{"response":"Recovered","done":true}"#;
+        let base_url = spawn_http_server(vec![
+            (503, "{}".to_string()),
+            (200, body.to_string()),
+        ]);
+        let mut provider =
+            OllamaProvider::with_endpoint("llama2".to_string(), base_url).with_retries(1);
+        provider.retry_backoff = std::time::Duration::from_millis(10);
+
+        let response = AsyncLlmProvider::complete(&provider, "Hi", &LlmOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.text, "Recovered");
+    }
+
+    #[tokio::test]
+    async fn test_blocking_to_async() {
+        let provider = blocking_to_async(MockLlmProvider::new());
+
+        let response = provider.complete("Hello", &LlmOptions::default()).await.unwrap();
+
+        assert_eq!(provider.name(), MockLlmProvider::new().name());
+        assert!(!response.text.is_empty());
+    }
+}
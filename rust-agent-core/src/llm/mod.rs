@@ -3,15 +3,94 @@
 //! Abstract interface for language models - works with any provider.
 //! Supports local models (llama.cpp family) or cloud providers.
 
+#[cfg(feature = "async")]
+pub mod async_provider;
+
+use crate::config::LlmConfig;
 use crate::error::{AgentError, Result};
-use crate::types::{LlmOptions, LlmResponse, LlmUsage};
+use crate::types::{ChatMessage, LlmOptions, LlmResponse, LlmUsage, Role};
 use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+use std::time::Duration;
+
+/// Timeout used by `is_available` health checks
+const AVAILABILITY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Largest response body read from an LLM server
+const MAX_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+
+/// How much of an unparseable body to quote in error messages
+const BODY_SNIPPET_CHARS: usize = 200;
+
+/// Blocking HTTP client with an optional overall request timeout
+fn blocking_client(timeout: Option<Duration>) -> Result<reqwest::blocking::Client> {
+    let mut builder = reqwest::blocking::Client::builder();
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+
+    builder
+        .build()
+        .map_err(|e| AgentError::Llm(format!("Failed to build HTTP client: {}", e)))
+}
+
+/// Read a response body from `server`, refusing anything over `MAX_RESPONSE_BYTES`
+fn read_limited_body(response: reqwest::blocking::Response, server: &str) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut body = Vec::new();
+    response
+        .take(MAX_RESPONSE_BYTES as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| AgentError::Llm(format!("Failed to read {} response: {}", server, e)))?;
+    check_response_size(&body, server)?;
+    Ok(body)
+}
+
+fn check_response_size(body: &[u8], server: &str) -> Result<()> {
+    if body.len() > MAX_RESPONSE_BYTES {
+        return Err(AgentError::Llm(format!(
+            "{} response exceeded {} bytes",
+            server, MAX_RESPONSE_BYTES
+        )));
+    }
+    Ok(())
+}
+
+/// Lossy, truncated view of a response body for error messages
+fn body_snippet(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    let mut snippet: String = text.chars().take(BODY_SNIPPET_CHARS).collect();
+    if text.chars().count() > BODY_SNIPPET_CHARS {
+        snippet.push_str("...");
+    }
+    snippet
+}
 
 /// Trait for LLM providers
 pub trait LlmProvider: Send + Sync {
     /// Complete a prompt
     fn complete(&self, prompt: &str, options: &LlmOptions) -> Result<LlmResponse>;
 
+    /// Complete a prompt, invoking `on_token` with each fragment as it arrives
+    /// The default implementation calls `complete` and fires the callback once
+    fn complete_stream(
+        &self,
+        prompt: &str,
+        options: &LlmOptions,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<LlmResponse> {
+        let response = self.complete(prompt, options)?;
+        on_token(&response.text);
+        Ok(response)
+    }
+
+    /// Complete a conversation made of system, user and assistant messages
+    /// The default implementation flattens the messages into a single prompt
+    fn complete_chat(&self, messages: &[ChatMessage], options: &LlmOptions) -> Result<LlmResponse> {
+        self.complete(&flatten_messages(messages), options)
+    }
+
     /// Get provider name
     fn name(&self) -> &str;
 
@@ -21,6 +100,50 @@ pub trait LlmProvider: Send + Sync {
     }
 }
 
+/// Estimate how many tokens `text` will consume in a model's context window
+/// Uses the cl100k tokenizer with the `tokenizer` feature, otherwise a
+/// character-based heuristic
+pub fn estimate_tokens(text: &str) -> u32 {
+    if text.trim().is_empty() {
+        return 0;
+    }
+
+    #[cfg(feature = "tokenizer")]
+    {
+        use std::sync::OnceLock;
+        static BPE: OnceLock<Option<tiktoken_rs::CoreBPE>> = OnceLock::new();
+
+        if let Some(bpe) = BPE.get_or_init(|| tiktoken_rs::cl100k_base().ok()) {
+            return bpe.encode_with_special_tokens(text).len() as u32;
+        }
+    }
+
+    heuristic_token_count(text)
+}
+
+/// Roughly 4 characters per token, ignoring whitespace, but never fewer
+/// tokens than words
+fn heuristic_token_count(text: &str) -> u32 {
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count() as u32;
+    let words = text.split_whitespace().count() as u32;
+    chars.div_ceil(4).max(words)
+}
+
+/// Render chat messages as a plain prompt for providers without chat support
+pub fn flatten_messages(messages: &[ChatMessage]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        let label = match message.role {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+        };
+        prompt.push_str(&format!("{}: {}\n\n", label, message.content));
+    }
+    prompt.push_str("Assistant:");
+    prompt
+}
+
 /// Mock LLM provider for testing
 pub struct MockLlmProvider {
     name: String,
@@ -113,6 +236,22 @@ struct OllamaRequest {
     options: Option<OllamaOptions>,
 }
 
+/// Ollama chat message
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaChatMessage {
+    role: String,
+    content: String,
+}
+
+/// Ollama `/api/chat` request structure
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaChatMessage>,
+    stream: bool,
+    options: Option<OllamaOptions>,
+}
+
 /// Ollama API options
 #[derive(Debug, Serialize)]
 struct OllamaOptions {
@@ -127,6 +266,9 @@ struct OllamaOptions {
 struct OllamaResponse {
     #[serde(default)]
     response: String,
+    /// Set by `/api/chat` instead of `response`
+    #[serde(default)]
+    message: Option<OllamaChatMessage>,
     #[serde(default)]
     done: bool,
     #[serde(default)]
@@ -141,6 +283,9 @@ pub struct OllamaProvider {
     name: String,
     base_url: String,
     model: String,
+    timeout: Option<Duration>,
+    retries: u32,
+    retry_backoff: Duration,
 }
 
 impl OllamaProvider {
@@ -151,6 +296,9 @@ impl OllamaProvider {
             name: format!("ollama-{}", model),
             base_url: "http://localhost:11434".to_string(),
             model,
+            timeout: None,
+            retries: 0,
+            retry_backoff: Duration::from_millis(250),
         }
     }
 
@@ -160,57 +308,255 @@ impl OllamaProvider {
             name: format!("ollama-{}", model),
             base_url,
             model,
+            timeout: None,
+            retries: 0,
+            retry_backoff: Duration::from_millis(250),
         }
     }
-}
 
-impl LlmProvider for OllamaProvider {
-    fn complete(&self, prompt: &str, options: &LlmOptions) -> Result<LlmResponse> {
-        let url = format!("{}/api/generate", self.base_url);
-        
-        let request = OllamaRequest {
+    /// Create a provider for the model, endpoint, timeout and retries in a config
+    pub fn from_config(config: &LlmConfig) -> Self {
+        let provider = Self::with_endpoint(config.model.clone(), config.endpoint.clone())
+            .with_retries(config.retries);
+        match config.timeout_secs {
+            Some(secs) => provider.with_timeout(Duration::from_secs(secs)),
+            None => provider,
+        }
+    }
+
+    /// Set the HTTP timeout for completion requests
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry connection failures and 5xx responses up to `retries` extra times
+    /// Waits with exponential backoff between attempts
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Base URL requests are sent to
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// HTTP timeout for completion requests, if any
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Extra attempts made after a transient failure
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    fn client(&self, timeout: Option<Duration>) -> Result<reqwest::blocking::Client> {
+        blocking_client(timeout)
+    }
+
+    /// POST `request` to `path`, retrying transient failures
+    /// Returns the last error if every attempt fails
+    fn send_with_retry(
+        &self,
+        path: &str,
+        request: &impl Serialize,
+    ) -> Result<reqwest::blocking::Response> {
+        let url = format!("{}{}", self.base_url, path);
+        let client = self.client(self.timeout)?;
+        let mut attempt = 0;
+
+        loop {
+            let error = match client.post(&url).json(request).send() {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let message = format!("Ollama API returned error status: {}", response.status());
+                    if !response.status().is_server_error() {
+                        return Err(AgentError::Llm(message));
+                    }
+                    AgentError::LlmUnavailable(message)
+                }
+                Err(e) => {
+                    let message = format!("Failed to send request to Ollama: {}", e);
+                    if !(e.is_connect() || e.is_timeout()) {
+                        return Err(AgentError::Llm(message));
+                    }
+                    AgentError::LlmUnavailable(message)
+                }
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+
+            let delay = self.retry_backoff * 2u32.saturating_pow(attempt);
+            log::warn!("Ollama request failed (attempt {}), retrying in {:?}", attempt + 1, delay);
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn build_request(&self, prompt: &str, options: &LlmOptions, stream: bool) -> OllamaRequest {
+        let options = options.clamped();
+        OllamaRequest {
             model: self.model.clone(),
             prompt: prompt.to_string(),
-            stream: false,
+            stream,
             options: Some(OllamaOptions {
                 temperature: options.temperature,
                 max_tokens: options.max_tokens as i32,
                 top_p: options.top_p,
             }),
-        };
-
-        let client = reqwest::blocking::Client::new();
-        let response = client
-            .post(&url)
-            .json(&request)
-            .send()
-            .map_err(|e| AgentError::Llm(format!("Failed to send request to Ollama: {}", e)))?;
-
-        if !response.status().is_success() {
-            return Err(AgentError::Llm(format!(
-                "Ollama API returned error status: {}",
-                response.status()
-            )));
         }
+    }
 
-        let ollama_response: OllamaResponse = response
-            .json()
-            .map_err(|e| AgentError::Llm(format!("Failed to parse Ollama response: {}", e)))?;
+    fn build_chat_request(&self, messages: &[ChatMessage], options: &LlmOptions) -> OllamaChatRequest {
+        let options = options.clamped();
+        OllamaChatRequest {
+            model: self.model.clone(),
+            messages: messages
+                .iter()
+                .map(|m| OllamaChatMessage {
+                    role: m.role.as_str().to_string(),
+                    content: m.content.clone(),
+                })
+                .collect(),
+            stream: false,
+            options: Some(OllamaOptions {
+                temperature: options.temperature,
+                max_tokens: options.max_tokens as i32,
+                top_p: options.top_p,
+            }),
+        }
+    }
 
-        Ok(LlmResponse {
-            text: ollama_response.response,
-            finish_reason: if ollama_response.done {
+    /// Convert a final (or accumulated) Ollama response into an `LlmResponse`
+    fn to_llm_response(text: String, last: &OllamaResponse) -> LlmResponse {
+        LlmResponse {
+            text,
+            finish_reason: if last.done {
                 "stop".to_string()
             } else {
                 "length".to_string()
             },
             usage: LlmUsage {
-                prompt_tokens: ollama_response.prompt_eval_count.unwrap_or(0),
-                completion_tokens: ollama_response.eval_count.unwrap_or(0),
-                total_tokens: ollama_response.prompt_eval_count.unwrap_or(0)
-                    + ollama_response.eval_count.unwrap_or(0),
+                prompt_tokens: last.prompt_eval_count.unwrap_or(0),
+                completion_tokens: last.eval_count.unwrap_or(0),
+                total_tokens: last.prompt_eval_count.unwrap_or(0) + last.eval_count.unwrap_or(0),
             },
-        })
+        }
+    }
+
+    /// Read a response body, refusing anything over `MAX_RESPONSE_BYTES`
+    fn read_body(response: reqwest::blocking::Response) -> Result<Vec<u8>> {
+        read_limited_body(response, "Ollama")
+    }
+
+    /// Parse a complete response body, quoting the start of it on failure
+    /// so that e.g. an HTML proxy page is recognizable
+    /// Some Ollama builds stream even with `stream: false`; a body of several
+    /// JSON objects is merged, concatenating the text and keeping the final
+    /// object's `done` flag and token counts.
+    fn parse_body(body: &[u8]) -> Result<OllamaResponse> {
+        let parse_error = |e: &dyn std::fmt::Display| {
+            AgentError::Llm(format!(
+                "Failed to parse Ollama response: {} (body: {:?})",
+                e,
+                body_snippet(body)
+            ))
+        };
+
+        let mut merged: Option<OllamaResponse> = None;
+        for chunk in serde_json::Deserializer::from_slice(body).into_iter::<OllamaResponse>() {
+            let chunk = chunk.map_err(|e| parse_error(&e))?;
+            merged = Some(match merged {
+                None => chunk,
+                Some(mut acc) => {
+                    acc.response.push_str(&chunk.response);
+                    match (&mut acc.message, chunk.message) {
+                        (Some(message), Some(next)) => message.content.push_str(&next.content),
+                        (message, next) => *message = message.take().or(next),
+                    }
+                    acc.done = chunk.done;
+                    acc.prompt_eval_count = chunk.prompt_eval_count;
+                    acc.eval_count = chunk.eval_count;
+                    acc
+                }
+            });
+        }
+
+        merged.ok_or_else(|| parse_error(&"empty body"))
+    }
+
+    /// Parse a newline-delimited JSON stream from `/api/generate`
+    /// Calls `on_token` for every non-empty `response` fragment
+    fn parse_stream<R: BufRead>(reader: R, on_token: &mut dyn FnMut(&str)) -> Result<LlmResponse> {
+        let mut text = String::new();
+        let mut last: Option<OllamaResponse> = None;
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let chunk: OllamaResponse = serde_json::from_str(&line).map_err(|e| {
+                AgentError::Llm(format!("Failed to parse Ollama stream chunk: {}", e))
+            })?;
+
+            if !chunk.response.is_empty() {
+                on_token(&chunk.response);
+                text.push_str(&chunk.response);
+            }
+
+            let done = chunk.done;
+            last = Some(chunk);
+            if done {
+                break;
+            }
+        }
+
+        let last = last.ok_or_else(|| AgentError::Llm("Empty Ollama stream".to_string()))?;
+        Ok(Self::to_llm_response(text, &last))
+    }
+}
+
+impl LlmProvider for OllamaProvider {
+    fn complete(&self, prompt: &str, options: &LlmOptions) -> Result<LlmResponse> {
+        let request = self.build_request(prompt, options, false);
+        let response = self.send_with_retry("/api/generate", &request)?;
+
+        let ollama_response = Self::parse_body(&Self::read_body(response)?)?;
+
+        let text = ollama_response.response.clone();
+        Ok(Self::to_llm_response(text, &ollama_response))
+    }
+
+    fn complete_stream(
+        &self,
+        prompt: &str,
+        options: &LlmOptions,
+        on_token: &mut dyn FnMut(&str),
+    ) -> Result<LlmResponse> {
+        let request = self.build_request(prompt, options, true);
+        let response = self.send_with_retry("/api/generate", &request)?;
+
+        Self::parse_stream(std::io::BufReader::new(response), on_token)
+    }
+
+    fn complete_chat(&self, messages: &[ChatMessage], options: &LlmOptions) -> Result<LlmResponse> {
+        let request = self.build_chat_request(messages, options);
+        let response = self.send_with_retry("/api/chat", &request)?;
+
+        let ollama_response = Self::parse_body(&Self::read_body(response)?)?;
+
+        let text = ollama_response
+            .message
+            .as_ref()
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+        Ok(Self::to_llm_response(text, &ollama_response))
     }
 
     fn name(&self) -> &str {
@@ -219,8 +565,12 @@ impl LlmProvider for OllamaProvider {
 
     fn is_available(&self) -> bool {
         // Try to connect to Ollama server
+        // Use a short timeout so startup checks don't block
         let url = format!("{}/api/tags", self.base_url);
-        reqwest::blocking::Client::new()
+        let Ok(client) = self.client(Some(AVAILABILITY_TIMEOUT)) else {
+            return false;
+        };
+        client
             .get(&url)
             .send()
             .map(|r| r.status().is_success())
@@ -228,6 +578,202 @@ impl LlmProvider for OllamaProvider {
     }
 }
 
+/// OpenAI chat message
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiMessage {
+    role: String,
+    content: String,
+}
+
+/// OpenAI chat completions request
+#[derive(Debug, Serialize)]
+struct OpenAiRequest {
+    model: String,
+    messages: Vec<OpenAiMessage>,
+    temperature: f32,
+    max_tokens: u32,
+    top_p: f32,
+    frequency_penalty: f32,
+    presence_penalty: f32,
+    stream: bool,
+}
+
+/// OpenAI chat completions response
+#[derive(Debug, Deserialize)]
+struct OpenAiResponse {
+    choices: Vec<OpenAiChoice>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiChoice {
+    message: OpenAiMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OpenAiUsage {
+    #[serde(default)]
+    prompt_tokens: u32,
+    #[serde(default)]
+    completion_tokens: u32,
+    #[serde(default)]
+    total_tokens: u32,
+}
+
+/// OpenAI-compatible chat provider
+/// Works with any server exposing `/v1/chat/completions`
+/// (LM Studio, llama.cpp server, vLLM, ...)
+pub struct OpenAiProvider {
+    name: String,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl OpenAiProvider {
+    /// Create a new provider for a server such as `http://localhost:1234`
+    pub fn new(base_url: String, model: String) -> Self {
+        Self {
+            name: format!("openai-{}", model),
+            base_url,
+            model,
+            api_key: None,
+            timeout: None,
+        }
+    }
+
+    /// Send `Authorization: Bearer <key>` with every request
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Set the HTTP timeout for completion requests
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn build_request(&self, messages: &[ChatMessage], options: &LlmOptions) -> OpenAiRequest {
+        let options = options.clamped();
+        OpenAiRequest {
+            model: self.model.clone(),
+            messages: messages
+                .iter()
+                .map(|m| OpenAiMessage {
+                    role: m.role.as_str().to_string(),
+                    content: m.content.clone(),
+                })
+                .collect(),
+            temperature: options.temperature,
+            max_tokens: options.max_tokens,
+            top_p: options.top_p,
+            frequency_penalty: options.frequency_penalty,
+            presence_penalty: options.presence_penalty,
+            stream: false,
+        }
+    }
+
+    fn build_http_request(
+        &self,
+        client: &reqwest::blocking::Client,
+        request: &OpenAiRequest,
+    ) -> Result<reqwest::blocking::Request> {
+        let url = format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'));
+        let mut builder = client.post(&url).json(request);
+
+        if let Some(api_key) = &self.api_key {
+            builder = builder.bearer_auth(api_key);
+        }
+
+        builder
+            .build()
+            .map_err(|e| AgentError::Llm(format!("Failed to build OpenAI request: {}", e)))
+    }
+
+    fn parse_response(body: &str) -> Result<LlmResponse> {
+        let response: OpenAiResponse = serde_json::from_str(body)
+            .map_err(|e| AgentError::Llm(format!("Failed to parse OpenAI response: {}", e)))?;
+
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| AgentError::Llm("OpenAI response contained no choices".to_string()))?;
+        let usage = response.usage.unwrap_or_default();
+
+        Ok(LlmResponse {
+            text: choice.message.content,
+            finish_reason: choice.finish_reason.unwrap_or_else(|| "stop".to_string()),
+            usage: LlmUsage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                total_tokens: usage.total_tokens,
+            },
+        })
+    }
+}
+
+impl LlmProvider for OpenAiProvider {
+    fn complete(&self, prompt: &str, options: &LlmOptions) -> Result<LlmResponse> {
+        self.complete_chat(&[ChatMessage::user(prompt)], options)
+    }
+
+    fn complete_chat(&self, messages: &[ChatMessage], options: &LlmOptions) -> Result<LlmResponse> {
+        let request = self.build_request(messages, options);
+
+        let client = blocking_client(self.timeout)?;
+        let http_request = self.build_http_request(&client, &request)?;
+        let response = client.execute(http_request).map_err(|e| {
+            let message = format!("Failed to send request to OpenAI server: {}", e);
+            if e.is_connect() || e.is_timeout() {
+                AgentError::LlmUnavailable(message)
+            } else {
+                AgentError::Llm(message)
+            }
+        })?;
+
+        if !response.status().is_success() {
+            let message = format!("OpenAI API returned error status: {}", response.status());
+            return Err(if response.status().is_server_error() {
+                AgentError::LlmUnavailable(message)
+            } else {
+                AgentError::Llm(message)
+            });
+        }
+
+        let body = read_limited_body(response, "OpenAI")?;
+        let body = std::str::from_utf8(&body)
+            .map_err(|e| AgentError::Llm(format!("OpenAI response is not UTF-8: {}", e)))?;
+        Self::parse_response(body)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_available(&self) -> bool {
+        // Use a short timeout so startup checks don't block
+        let url = format!("{}/v1/models", self.base_url.trim_end_matches('/'));
+        let Ok(client) = blocking_client(Some(AVAILABILITY_TIMEOUT)) else {
+            return false;
+        };
+        let mut request = client.get(&url);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        request
+            .send()
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,4 +830,406 @@ mod tests {
         let response = response.unwrap();
         assert!(!response.text.is_empty());
     }
+
+    #[test]
+    fn test_default_complete_stream() {
+        let provider = MockLlmProvider::new();
+        let mut tokens = Vec::new();
+
+        let response = provider
+            .complete_stream("What's the weather?", &LlmOptions::default(), &mut |t| {
+                tokens.push(t.to_string())
+            })
+            .unwrap();
+
+        assert_eq!(tokens, vec![response.text]);
+    }
+
+    #[test]
+    fn test_ollama_parse_stream() {
+        let body = concat!(
+            "{\"model\":\"llama2\",\"response\":\"Hel\",\"done\":false}\n",
+            "{\"model\":\"llama2\",\"response\":\"lo\",\"done\":false}\n",
+            "\n",
+            "{\"model\":\"llama2\",\"response\":\" there\",\"done\":false}\n",
+            "{\"model\":\"llama2\",\"response\":\"\",\"done\":true,",
+            "\"prompt_eval_count\":5,\"eval_count\":3}\n",
+        );
+
+        let mut tokens = Vec::new();
+        let response = OllamaProvider::parse_stream(std::io::Cursor::new(body), &mut |t| {
+            tokens.push(t.to_string())
+        })
+        .unwrap();
+
+        assert_eq!(tokens, vec!["Hel", "lo", " there"]);
+        assert_eq!(response.text, tokens.concat());
+        assert_eq!(response.finish_reason, "stop");
+        assert_eq!(response.usage.total_tokens, 8);
+    }
+
+    #[test]
+    fn test_ollama_parse_stream_invalid_chunk() {
+        let body = "{\"response\":\"ok\",\"done\":false}\nnot json\n";
+        let result = OllamaProvider::parse_stream(std::io::Cursor::new(body), &mut |_| {});
+        assert!(matches!(result, Err(AgentError::Llm(_))));
+    }
+
+    #[test]
+    fn test_openai_parse_response() {
+        let body = r#"{
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hello there!"},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15}
+        }"#;
+
+        let response = OpenAiProvider::parse_response(body).unwrap();
+        assert_eq!(response.text, "Hello there!");
+        assert_eq!(response.finish_reason, "stop");
+        assert_eq!(response.usage.prompt_tokens, 12);
+        assert_eq!(response.usage.completion_tokens, 3);
+        assert_eq!(response.usage.total_tokens, 15);
+    }
+
+    #[test]
+    fn test_openai_parse_response_without_choices() {
+        let result = OpenAiProvider::parse_response(r#"{"choices": []}"#);
+        assert!(matches!(result, Err(AgentError::Llm(_))));
+    }
+
+    #[test]
+    fn test_openai_request_body_and_auth() {
+        let client = reqwest::blocking::Client::new();
+        let options = LlmOptions {
+            temperature: 0.2,
+            max_tokens: 64,
+            ..LlmOptions::default()
+        };
+        let messages = vec![ChatMessage::user("Hi")];
+
+        let local = OpenAiProvider::new("http://localhost:1234/".to_string(), "phi".to_string());
+        let body = local.build_request(&messages, &options);
+        let request = local.build_http_request(&client, &body).unwrap();
+        assert_eq!(request.url().as_str(), "http://localhost:1234/v1/chat/completions");
+        assert!(request.headers().get("authorization").is_none());
+
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json["model"], "phi");
+        assert_eq!(json["max_tokens"], 64);
+        assert_eq!(json["messages"][0]["content"], "Hi");
+
+        let hosted = OpenAiProvider::new("https://api.example.com".to_string(), "phi".to_string())
+            .with_api_key("secret".to_string());
+        let request = hosted.build_http_request(&client, &body).unwrap();
+        assert_eq!(request.headers()["authorization"], "Bearer secret");
+        assert_eq!(hosted.name(), "openai-phi");
+    }
+
+    /// Serve one canned HTTP response per entry, then stop
+    pub(crate) fn spawn_http_server(responses: Vec<(u16, String)>) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+
+                // Drain the request headers and body before replying
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let content_length = text[..end]
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + content_length {
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+
+                let reply = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_ollama_retries_server_errors() {
+        let ok_body = r#"{"response":"Recovered","done":true,"prompt_eval_count":4,"eval_count":1}"#;
+        let base_url = spawn_http_server(vec![
+            (500, "{}".to_string()),
+            (503, "{}".to_string()),
+            (200, ok_body.to_string()),
+        ]);
+
+        let mut provider = OllamaProvider::with_endpoint("llama2".to_string(), base_url)
+            .with_timeout(Duration::from_secs(5))
+            .with_retries(2);
+        provider.retry_backoff = Duration::from_millis(10);
+
+        let response = provider.complete("Hello", &LlmOptions::default()).unwrap();
+        assert_eq!(response.text, "Recovered");
+        assert_eq!(response.usage.total_tokens, 5);
+    }
+
+    #[test]
+    fn test_ollama_returns_last_error_when_retries_exhausted() {
+        let base_url = spawn_http_server(vec![
+            (500, "{}".to_string()),
+            (502, "{}".to_string()),
+        ]);
+
+        let mut provider =
+            OllamaProvider::with_endpoint("llama2".to_string(), base_url).with_retries(1);
+        provider.retry_backoff = Duration::from_millis(10);
+
+        match provider.complete("Hello", &LlmOptions::default()) {
+            Err(AgentError::LlmUnavailable(msg)) => assert!(msg.contains("502")),
+            other => panic!("expected LlmUnavailable error, got {:?}", other.map(|r| r.text)),
+        }
+    }
+
+    fn system_and_user() -> Vec<ChatMessage> {
+        vec![
+            ChatMessage::system("You are a helpful home assistant."),
+            ChatMessage::user("Turn on the lights"),
+        ]
+    }
+
+    #[test]
+    fn test_openai_serializes_chat_messages() {
+        let provider = OpenAiProvider::new("http://localhost:1234".to_string(), "phi".to_string());
+        let request = provider.build_request(&system_and_user(), &LlmOptions::default());
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            json["messages"],
+            serde_json::json!([
+                {"role": "system", "content": "You are a helpful home assistant."},
+                {"role": "user", "content": "Turn on the lights"}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_ollama_serializes_chat_messages() {
+        let provider = OllamaProvider::new("llama2".to_string());
+        let request = provider.build_chat_request(&system_and_user(), &LlmOptions::default());
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["model"], "llama2");
+        assert_eq!(json["stream"], false);
+        assert_eq!(
+            json["messages"],
+            serde_json::json!([
+                {"role": "system", "content": "You are a helpful home assistant."},
+                {"role": "user", "content": "Turn on the lights"}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_ollama_complete_chat_reads_message() {
+        let body = r#"{"message":{"role":"assistant","content":"Lights on"},"done":true,"prompt_eval_count":9,"eval_count":2}"#;
+        let base_url = spawn_http_server(vec![(200, body.to_string())]);
+        let provider = OllamaProvider::with_endpoint("llama2".to_string(), base_url);
+
+        let response = provider
+            .complete_chat(&system_and_user(), &LlmOptions::default())
+            .unwrap();
+        assert_eq!(response.text, "Lights on");
+        assert_eq!(response.usage.total_tokens, 11);
+    }
+
+    #[test]
+    fn test_default_complete_chat_flattens_messages() {
+        let prompt = flatten_messages(&system_and_user());
+        assert_eq!(
+            prompt,
+            "System: You are a helpful home assistant.\n\nUser: Turn on the lights\n\nAssistant:"
+        );
+
+        let provider = MockLlmProvider::new();
+        let response = provider
+            .complete_chat(&system_and_user(), &LlmOptions::default())
+            .unwrap();
+        assert!(!response.text.is_empty());
+    }
+
+    #[test]
+    fn test_estimate_tokens_empty() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("   \n\t"), 0);
+    }
+
+    #[test]
+    fn test_heuristic_token_count_range() {
+        // Reference counts from the cl100k tokenizer
+        let cases = [
+            ("Hello world", 2),
+            ("The quick brown fox jumps over the lazy dog", 9),
+            ("Turn on the living room lights at 7pm, please.", 12),
+        ];
+
+        for (text, expected) in cases {
+            let estimate = heuristic_token_count(text) as f32;
+            let expected = expected as f32;
+            assert!(
+                estimate >= expected * 0.5 && estimate <= expected * 1.5,
+                "{:?}: estimated {} vs {}",
+                text,
+                estimate,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_ollama_non_json_body_quoted_in_error() {
+        let page = format!("<html><body>Proxy login required{}</body></html>", " ".repeat(500));
+        let base_url = spawn_http_server(vec![(200, page)]);
+        let provider = OllamaProvider::with_endpoint("llama2".to_string(), base_url)
+            .with_timeout(Duration::from_secs(5));
+
+        match provider.complete("Hello", &LlmOptions::default()) {
+            Err(AgentError::Llm(message)) => {
+                assert!(message.contains("<html><body>Proxy login required"));
+                assert!(message.contains("..."));
+                assert!(message.len() < 400);
+            }
+            other => panic!("expected Llm error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_body_snippet() {
+        assert_eq!(body_snippet(b"short"), "short");
+        assert_eq!(body_snippet(&[b'a', 0xff, b'b']), "a\u{fffd}b");
+
+        let long = "x".repeat(BODY_SNIPPET_CHARS + 10);
+        assert_eq!(body_snippet(long.as_bytes()).len(), BODY_SNIPPET_CHARS + 3);
+        assert!(check_response_size(&vec![0; MAX_RESPONSE_BYTES + 1], "Ollama").is_err());
+    }
+
+    #[test]
+    fn test_ollama_complete_merges_streamed_body() {
+        // Returned by some Ollama builds despite `stream: false`
+        let body = concat!(
+            "{\"response\":\"Good\",\"done\":false}\n",
+            "{\"response\":\" morning\",\"done\":false}\n",
+            "{\"response\":\"!\",\"done\":true,\"prompt_eval_count\":6,\"eval_count\":3}\n",
+        );
+        let base_url = spawn_http_server(vec![(200, body.to_string())]);
+        let provider = OllamaProvider::with_endpoint("llama2".to_string(), base_url)
+            .with_timeout(Duration::from_secs(5));
+
+        let response = provider.complete("Hello", &LlmOptions::default()).unwrap();
+        assert_eq!(response.text, "Good morning!");
+        assert_eq!(response.finish_reason, "stop");
+        assert_eq!(response.usage.prompt_tokens, 6);
+        assert_eq!(response.usage.completion_tokens, 3);
+        assert_eq!(response.usage.total_tokens, 9);
+    }
+
+    #[test]
+    fn test_ollama_parse_body_chat_chunks_and_empty() {
+        let body = concat!(
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"Lights \"},\"done\":false}",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"on\"},\"done\":true,\"eval_count\":2}",
+        );
+        let parsed = OllamaProvider::parse_body(body.as_bytes()).unwrap();
+        assert_eq!(parsed.message.unwrap().content, "Lights on");
+        assert!(parsed.done);
+
+        assert!(matches!(OllamaProvider::parse_body(b"  \n"), Err(AgentError::Llm(_))));
+    }
+
+    #[test]
+    fn test_requests_use_clamped_options() {
+        let options = LlmOptions {
+            temperature: 3.5,
+            top_p: -1.0,
+            max_tokens: 0,
+            ..LlmOptions::default()
+        };
+
+        let provider = OpenAiProvider::new("http://localhost:1234".to_string(), "phi".to_string());
+        let json = serde_json::to_value(provider.build_request(&system_and_user(), &options)).unwrap();
+        assert_eq!(json["temperature"], 2.0);
+        assert_eq!(json["top_p"], 0.0);
+        assert_eq!(json["max_tokens"], 1);
+
+        let ollama = OllamaProvider::new("llama2".to_string());
+        let json = serde_json::to_value(ollama.build_request("Hi", &options, false)).unwrap();
+        assert_eq!(json["options"]["temperature"], 2.0);
+        assert_eq!(json["options"]["num_predict"], 1);
+    }
+
+    #[test]
+    fn test_ollama_client_errors_are_not_retryable() {
+        let base_url = spawn_http_server(vec![(404, "{}".to_string())]);
+        let provider = OllamaProvider::with_endpoint("llama2".to_string(), base_url)
+            .with_timeout(Duration::from_secs(5))
+            .with_retries(3);
+
+        let error = provider.complete("Hello", &LlmOptions::default()).unwrap_err();
+        assert!(matches!(error, AgentError::Llm(ref msg) if msg.contains("404")));
+        assert!(!error.is_retryable());
+
+        let parse_error = OllamaProvider::parse_body(b"not json").unwrap_err();
+        assert!(!parse_error.is_retryable());
+    }
+
+    #[test]
+    fn test_openai_complete_over_http() {
+        let ok_body = r#"{"choices":[{"message":{"role":"assistant","content":"Hi"},"finish_reason":"stop"}]}"#;
+        let oversized = format!("\"{}\"", "x".repeat(MAX_RESPONSE_BYTES));
+        let base_url = spawn_http_server(vec![(200, ok_body.to_string()), (200, oversized)]);
+        let provider = OpenAiProvider::new(base_url, "phi".to_string())
+            .with_timeout(Duration::from_secs(5));
+
+        assert_eq!(provider.complete("Hello", &LlmOptions::default()).unwrap().text, "Hi");
+        match provider.complete("Hello", &LlmOptions::default()) {
+            Err(AgentError::Llm(message)) => assert!(message.contains("exceeded")),
+            other => panic!("expected Llm error, got {:?}", other.map(|r| r.text)),
+        }
+    }
+
+    #[test]
+    fn test_openai_availability_times_out() {
+        // Accepts connections but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let provider = OpenAiProvider::new(base_url, "phi".to_string());
+
+        let started = std::time::Instant::now();
+        assert!(!provider.is_available());
+        assert!(started.elapsed() < AVAILABILITY_TIMEOUT * 3);
+    }
 }
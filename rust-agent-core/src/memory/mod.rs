@@ -7,96 +7,384 @@
 //! - SQL-first: auditable and deterministic
 
 use crate::error::{AgentError, Result};
-use crate::types::{Event, Habit, HabitFrequency};
-use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection};
+use crate::habit::HabitAnalyzer;
+use crate::policy::Permission;
+use crate::types::{parse_timestamp, Event, Habit, HabitFrequency};
+use chrono::{DateTime, NaiveTime, Utc};
+use rusqlite::{params, types::Type, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 use uuid::Uuid;
 
+/// Schema migrations, applied in order
+/// `PRAGMA user_version` records how many have run. Append new migrations;
+/// never edit or reorder existing ones.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS events (
+        id TEXT PRIMARY KEY,
+        event_type TEXT NOT NULL,
+        description TEXT NOT NULL,
+        importance REAL NOT NULL,
+        metadata TEXT NOT NULL,
+        timestamp TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS habits (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        frequency TEXT NOT NULL,
+        schedule TEXT,
+        completion_count INTEGER NOT NULL,
+        last_completed TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS habit_completions (
+        id TEXT PRIMARY KEY,
+        habit_id TEXT NOT NULL,
+        completed_at TEXT NOT NULL,
+        FOREIGN KEY(habit_id) REFERENCES habits(id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events(timestamp);
+    CREATE INDEX IF NOT EXISTS idx_events_type ON events(event_type);",
+    // 2: statistical variance in habit timing
+    "ALTER TABLE habits ADD COLUMN variance REAL;",
+    // 3: importance filtering
+    "CREATE INDEX IF NOT EXISTS idx_events_importance ON events(importance);",
+    // 4: semantic tags as a JSON array
+    "ALTER TABLE events ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';",
+    // 5: persisted permission grants
+    "CREATE TABLE IF NOT EXISTS permissions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        module TEXT NOT NULL,
+        actions TEXT NOT NULL,
+        scope TEXT NOT NULL,
+        granted_at TEXT NOT NULL,
+        expires_at TEXT
+    );",
+    // 6: time-of-day windows on permissions
+    "ALTER TABLE permissions ADD COLUMN active_start TEXT;
+    ALTER TABLE permissions ADD COLUMN active_end TEXT;",
+    // 7: per-user partitioning; existing rows belong to the default user
+    "ALTER TABLE events ADD COLUMN user_id TEXT NOT NULL DEFAULT 'default';
+    ALTER TABLE habits ADD COLUMN user_id TEXT NOT NULL DEFAULT 'default';
+    CREATE INDEX IF NOT EXISTS idx_events_user ON events(user_id, timestamp);",
+    // 8: use quotas on permissions
+    "ALTER TABLE permissions ADD COLUMN max_uses INTEGER;
+    ALTER TABLE permissions ADD COLUMN uses INTEGER NOT NULL DEFAULT 0;",
+    // 9: integration that produced each event
+    "ALTER TABLE events ADD COLUMN source TEXT;
+    CREATE INDEX IF NOT EXISTS idx_events_source ON events(source);",
+    // 10: soft-delete tombstones
+    "ALTER TABLE events ADD COLUMN deleted_at TEXT;",
+];
+
+/// A habit with its `(id, completed_at)` completion rows
+type HabitRows = (Habit, Vec<(String, String)>);
+
+/// SQLite-backed memory store
+///
+/// The connection is guarded by a mutex, so the store is `Send + Sync` and
+/// can be shared across threads behind an `Arc`.
 pub struct MemoryStore {
-    conn: Connection,
+    conn: Mutex<Connection>,
+}
+
+/// Connection tuning for file-backed stores
+#[derive(Debug, Clone)]
+pub struct MemoryStoreOptions {
+    /// Use write-ahead logging so readers can proceed while a writer is active
+    pub wal: bool,
+    /// How long to wait for a lock held by another connection before failing
+    pub busy_timeout: Duration,
+}
+
+/// Retention limits applied by `MemoryStore::apply_retention`
+/// Expired events are soft-deleted; `MemoryStore::purge_soft_deleted` removes them.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Soft-delete events older than this
+    pub max_age: Option<chrono::Duration>,
+    /// Keep at most this many live events, soft-deleting the oldest first
+    pub max_events: Option<usize>,
+}
+
+impl Default for MemoryStoreOptions {
+    fn default() -> Self {
+        Self {
+            wal: true,
+            busy_timeout: Duration::from_millis(5000),
+        }
+    }
+}
+
+/// A record present in both stores with diverging content.
+/// The local copy is kept; the conflict is reported for the user to resolve.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeConflict {
+    Event(Uuid),
+    Habit(Uuid),
+}
+
+/// Outcome of merging another store into this one
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    pub events_added: usize,
+    pub events_skipped: usize,
+    pub habits_added: usize,
+    pub completions_added: usize,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Aggregate statistics for one event type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventTypeStats {
+    pub event_type: String,
+    pub count: u64,
+    pub avg_importance: f32,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Version of the portable JSON document written by `export_json`
+const EXPORT_VERSION: u32 = 1;
+
+/// A single recorded habit completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HabitCompletion {
+    pub id: Uuid,
+    pub habit_id: Uuid,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Portable snapshot of the whole store
+#[derive(Debug, Serialize, Deserialize)]
+struct MemoryExport {
+    version: u32,
+    events: Vec<Event>,
+    habits: Vec<Habit>,
+    completions: Vec<HabitCompletion>,
+}
+
+/// How `import_json` treats existing data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Keep existing records and skip imported ones with duplicate ids
+    Merge,
+    /// Delete all existing records before importing
+    Replace,
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Default)]
+pub struct ImportStats {
+    pub events_imported: usize,
+    pub habits_imported: usize,
+    pub completions_imported: usize,
+    pub skipped: usize,
 }
 
 impl MemoryStore {
     /// Create a new memory store with the given database path
+    /// Uses WAL mode and a 5 second busy timeout (see `MemoryStoreOptions`)
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        Self::with_options(db_path, MemoryStoreOptions::default())
+    }
+
+    /// Create a new memory store with custom connection options
+    pub fn with_options<P: AsRef<Path>>(db_path: P, options: MemoryStoreOptions) -> Result<Self> {
         let conn = Connection::open(db_path)?;
-        let store = Self { conn };
+
+        if options.wal {
+            conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))?;
+        }
+        conn.busy_timeout(options.busy_timeout)?;
+
+        let store = Self {
+            conn: Mutex::new(conn),
+        };
         store.initialize_schema()?;
         Ok(store)
     }
 
     /// Create an in-memory database for testing
+    /// In-memory databases are private to one connection and ignore WAL
     pub fn in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()?;
-        let store = Self { conn };
+        let store = Self {
+            conn: Mutex::new(conn),
+        };
         store.initialize_schema()?;
         Ok(store)
     }
 
-    /// Initialize database schema
+    /// Lock the underlying connection
+    fn conn(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| AgentError::Memory("Memory store lock poisoned".to_string()))
+    }
+
+    /// Initialize database schema, applying any pending migrations
     fn initialize_schema(&self) -> Result<()> {
-        // Events table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS events (
-                id TEXT PRIMARY KEY,
-                event_type TEXT NOT NULL,
-                description TEXT NOT NULL,
-                importance REAL NOT NULL,
-                metadata TEXT NOT NULL,
-                timestamp TEXT NOT NULL
-            )",
-            [],
-        )?;
-
-        // Habits table
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS habits (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                description TEXT NOT NULL,
-                frequency TEXT NOT NULL,
-                schedule TEXT,
-                completion_count INTEGER NOT NULL,
-                last_completed TEXT,
-                created_at TEXT NOT NULL,
-                variance REAL
-            )",
-            [],
-        )?;
-
-        // Habit completions table (for statistical tracking)
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS habit_completions (
-                id TEXT PRIMARY KEY,
-                habit_id TEXT NOT NULL,
-                completed_at TEXT NOT NULL,
-                FOREIGN KEY(habit_id) REFERENCES habits(id)
-            )",
-            [],
-        )?;
-
-        // Create indices for common queries
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events(timestamp)",
-            [],
-        )?;
-
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_events_type ON events(event_type)",
-            [],
-        )?;
+        let conn = self.conn()?;
+        Self::migrate(&conn)
+    }
+
+    /// Apply migrations newer than the database's `user_version`
+    /// Each migration runs in its own transaction together with the version bump
+    fn migrate(conn: &Connection) -> Result<()> {
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = conn.unchecked_transaction()?;
+
+            // Statements run one at a time so that a column which already exists
+            // doesn't skip the rest of the migration
+            for statement in migration.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                match tx.execute_batch(statement) {
+                    Ok(()) => {}
+                    // Databases created before versioning already have the column
+                    Err(e)
+                        if statement.starts_with("ALTER TABLE")
+                            && e.to_string().contains("duplicate column name") => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            tx.pragma_update(None, "user_version", (index + 1) as i64)?;
+            tx.commit()?;
+        }
 
         Ok(())
     }
 
+    /// Get the schema version of the underlying database
+    pub fn schema_version(&self) -> Result<usize> {
+        let version: usize = self
+            .conn()?
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        Ok(version)
+    }
+
+    /// Map a row of `SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at`
+    fn row_to_event(row: &rusqlite::Row) -> rusqlite::Result<Event> {
+        let id: String = row.get(0)?;
+        let metadata_json: String = row.get(4)?;
+        let timestamp_str: String = row.get(5)?;
+        let tags_json: String = row.get(6)?;
+
+        Ok(Event {
+            id: Self::parse_uuid(0, &id)?,
+            event_type: row.get(1)?,
+            description: row.get(2)?,
+            importance: row.get(3)?,
+            metadata: serde_json::from_str(&metadata_json).unwrap_or_default(),
+            timestamp: Self::parse_timestamp(5, &timestamp_str)?,
+            tags: serde_json::from_str(&tags_json).unwrap_or_default(),
+            user_id: row.get(7)?,
+            source: row.get(8)?,
+            deleted_at: row
+                .get::<_, Option<String>>(9)?
+                .map(|deleted_at| Self::parse_timestamp(9, &deleted_at))
+                .transpose()?,
+        })
+    }
+
+    /// Map a row of `SELECT id, name, description, frequency, schedule,
+    /// completion_count, last_completed, created_at, variance, user_id`
+    fn row_to_habit(row: &rusqlite::Row) -> rusqlite::Result<Habit> {
+        let id: String = row.get(0)?;
+        let frequency_str: String = row.get(3)?;
+        let last_completed_str: Option<String> = row.get(6)?;
+        let created_at_str: String = row.get(7)?;
+
+        Ok(Habit {
+            id: Self::parse_uuid(0, &id)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            frequency: Self::frequency_from_str(frequency_str),
+            schedule: row.get(4)?,
+            completion_count: row.get(5)?,
+            last_completed: last_completed_str
+                .map(|s| Self::parse_timestamp(6, &s))
+                .transpose()?,
+            created_at: Self::parse_timestamp(7, &created_at_str)?,
+            variance: row.get(8)?,
+            user_id: row.get(9)?,
+        })
+    }
+
+    /// Parse a UUID column, reporting corrupted values as a conversion error
+    fn parse_uuid(idx: usize, value: &str) -> rusqlite::Result<Uuid> {
+        Uuid::parse_str(value)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+    }
+
+    /// Parse a timestamp column with `types::parse_timestamp`, reporting
+    /// corrupted values as a conversion error
+    fn parse_timestamp(idx: usize, value: &str) -> rusqlite::Result<DateTime<Utc>> {
+        parse_timestamp(value)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+    }
+
+    /// Parse a `HH:MM:SS` time-of-day column
+    fn parse_time(idx: usize, value: &str) -> rusqlite::Result<NaiveTime> {
+        NaiveTime::parse_from_str(value, "%H:%M:%S%.f")
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+    }
+
+    fn frequency_to_str(frequency: &HabitFrequency) -> String {
+        match frequency {
+            HabitFrequency::Daily => "daily".to_string(),
+            HabitFrequency::Weekly => "weekly".to_string(),
+            HabitFrequency::Monthly => "monthly".to_string(),
+            HabitFrequency::Custom(s) => format!("custom:{}", s),
+        }
+    }
+
+    fn frequency_from_str(frequency_str: String) -> HabitFrequency {
+        if frequency_str == "daily" {
+            HabitFrequency::Daily
+        } else if frequency_str == "weekly" {
+            HabitFrequency::Weekly
+        } else if frequency_str == "monthly" {
+            HabitFrequency::Monthly
+        } else if let Some(custom) = frequency_str.strip_prefix("custom:") {
+            HabitFrequency::Custom(custom.to_string())
+        } else {
+            HabitFrequency::Custom(frequency_str)
+        }
+    }
+
     /// Store an event
     pub fn store_event(&self, event: &Event) -> Result<()> {
+        self.store_events(std::slice::from_ref(event))
+    }
+
+    /// Store a batch of events in a single transaction
+    /// If any insert fails, the whole batch is rolled back
+    pub fn store_events(&self, events: &[Event]) -> Result<()> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+
+        for event in events {
+            Self::insert_event(&tx, event)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn insert_event(conn: &Connection, event: &Event) -> Result<()> {
         let metadata_json = serde_json::to_string(&event.metadata)?;
-        
-        self.conn.execute(
-            "INSERT INTO events (id, event_type, description, importance, metadata, timestamp)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        let tags_json = serde_json::to_string(&event.tags)?;
+
+        conn.execute(
+            "INSERT INTO events (id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
             params![
                 event.id.to_string(),
                 event.event_type,
@@ -104,88 +392,360 @@ impl MemoryStore {
                 event.importance,
                 metadata_json,
                 event.timestamp.to_rfc3339(),
+                tags_json,
+                event.user_id,
+                event.source,
+                event.deleted_at.map(|dt| dt.to_rfc3339()),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Update the type, description, importance, metadata and tags of a stored event
+    /// The event's id and original timestamp are kept; errors if no event matches
+    pub fn update_event(&self, event: &Event) -> Result<()> {
+        let conn = self.conn()?;
+        let metadata_json = serde_json::to_string(&event.metadata)?;
+        let tags_json = serde_json::to_string(&event.tags)?;
+
+        let updated = conn.execute(
+            "UPDATE events
+             SET event_type = ?1, description = ?2, importance = ?3, metadata = ?4, tags = ?5
+             WHERE id = ?6",
+            params![
+                event.event_type,
+                event.description,
+                event.importance,
+                metadata_json,
+                tags_json,
+                event.id.to_string(),
             ],
         )?;
+        if updated == 0 {
+            return Err(AgentError::Memory(format!("Event {} not found", event.id)));
+        }
 
         Ok(())
     }
 
     /// Get recent events
     pub fn get_recent_events(&self, limit: u32) -> Result<Vec<Event>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, event_type, description, importance, metadata, timestamp
+        self.recent_events(None, false, limit)
+    }
+
+    /// Recent events, optionally restricted to one user
+    fn recent_events(&self, user_id: Option<&str>, include_deleted: bool, limit: u32) -> Result<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
              FROM events
+             WHERE (?1 IS NULL OR user_id = ?1) AND (?2 OR deleted_at IS NULL)
              ORDER BY timestamp DESC
-             LIMIT ?1",
+             LIMIT ?3",
         )?;
 
         let events = stmt
-            .query_map([limit], |row| {
-                let id: String = row.get(0)?;
-                let metadata_json: String = row.get(4)?;
-                let timestamp_str: String = row.get(5)?;
-
-                Ok(Event {
-                    id: Uuid::parse_str(&id).unwrap(),
-                    event_type: row.get(1)?,
-                    description: row.get(2)?,
-                    importance: row.get(3)?,
-                    metadata: serde_json::from_str(&metadata_json).unwrap_or_default(),
-                    timestamp: DateTime::parse_from_rfc3339(&timestamp_str)
-                        .unwrap()
-                        .with_timezone(&Utc),
-                })
-            })?
+            .query_map(params![user_id, include_deleted, limit], Self::row_to_event)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(events)
     }
 
+    /// Get a single event by id, or `None` if it does not exist or was soft-deleted
+    pub fn get_event(&self, id: Uuid) -> Result<Option<Event>> {
+        let conn = self.conn()?;
+        Ok(Self::query_event(&conn, id)?.filter(|event| event.deleted_at.is_none()))
+    }
+
+    fn query_event(conn: &Connection, id: Uuid) -> Result<Option<Event>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             WHERE id = ?1",
+        )?;
+        let mut events = stmt.query_map([id.to_string()], Self::row_to_event)?;
+
+        Ok(events.next().transpose()?)
+    }
+
     /// Get events by type
     pub fn get_events_by_type(&self, event_type: &str, limit: u32) -> Result<Vec<Event>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, event_type, description, importance, metadata, timestamp
+        self.events_by_type(None, false, event_type, limit)
+    }
+
+    /// Events of one type, optionally restricted to one user
+    fn events_by_type(
+        &self,
+        user_id: Option<&str>,
+        include_deleted: bool,
+        event_type: &str,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
              FROM events
-             WHERE event_type = ?1
+             WHERE event_type = ?1 AND (?2 IS NULL OR user_id = ?2) AND (?3 OR deleted_at IS NULL)
              ORDER BY timestamp DESC
-             LIMIT ?2",
+             LIMIT ?4",
         )?;
 
         let events = stmt
-            .query_map(params![event_type, limit], |row| {
-                let id: String = row.get(0)?;
-                let metadata_json: String = row.get(4)?;
-                let timestamp_str: String = row.get(5)?;
-
-                Ok(Event {
-                    id: Uuid::parse_str(&id).unwrap(),
-                    event_type: row.get(1)?,
-                    description: row.get(2)?,
-                    importance: row.get(3)?,
-                    metadata: serde_json::from_str(&metadata_json).unwrap_or_default(),
-                    timestamp: DateTime::parse_from_rfc3339(&timestamp_str)
-                        .unwrap()
-                        .with_timezone(&Utc),
-                })
-            })?
+            .query_map(params![event_type, user_id, include_deleted, limit], Self::row_to_event)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// Get events carrying the given tag, newest first
+    pub fn get_events_by_tag(&self, tag: &str, limit: u32) -> Result<Vec<Event>> {
+        self.events_by_tag(None, false, tag, limit)
+    }
+
+    /// Events carrying a tag, optionally restricted to one user
+    fn events_by_tag(
+        &self,
+        user_id: Option<&str>,
+        include_deleted: bool,
+        tag: &str,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             WHERE EXISTS (SELECT 1 FROM json_each(events.tags) WHERE value = ?1)
+                 AND (?2 IS NULL OR user_id = ?2) AND (?3 OR deleted_at IS NULL)
+             ORDER BY timestamp DESC
+             LIMIT ?4",
+        )?;
+
+        let events = stmt
+            .query_map(params![tag, user_id, include_deleted, limit], Self::row_to_event)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// Get events whose `metadata[key]` equals `value`, newest first
+    /// Scalars must match in JSON type as well as value (`"5"` is not `5`);
+    /// arrays and objects are compared by their serialized form.
+    pub fn get_events_by_metadata(
+        &self,
+        key: &str,
+        value: &serde_json::Value,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.events_by_metadata(None, false, key, value, limit)
+    }
+
+    /// Events with a metadata value, optionally restricted to one user
+    fn events_by_metadata(
+        &self,
+        user_id: Option<&str>,
+        include_deleted: bool,
+        key: &str,
+        value: &serde_json::Value,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        use rusqlite::types::Value as SqlValue;
+        use serde_json::Value;
+
+        let path = format!("$.\"{}\"", key.replace('"', "\\\""));
+        let (kind, operand) = match value {
+            Value::Null => ("null", SqlValue::Null),
+            Value::Bool(b) => ("bool", SqlValue::Text(b.to_string())),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => ("number", SqlValue::Integer(i)),
+                None => ("number", SqlValue::Real(n.as_f64().unwrap_or(f64::NAN))),
+            },
+            Value::String(s) => ("text", SqlValue::Text(s.clone())),
+            Value::Array(_) | Value::Object(_) => ("json", SqlValue::Text(value.to_string())),
+        };
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             WHERE (?5 IS NULL OR user_id = ?5) AND (?6 OR deleted_at IS NULL) AND CASE ?2
+                 WHEN 'null' THEN json_type(metadata, ?1) = 'null'
+                 WHEN 'bool' THEN json_type(metadata, ?1) = ?3
+                 WHEN 'number' THEN json_type(metadata, ?1) IN ('integer', 'real')
+                     AND json_extract(metadata, ?1) = ?3
+                 WHEN 'text' THEN json_type(metadata, ?1) = 'text'
+                     AND json_extract(metadata, ?1) = ?3
+                 ELSE json_type(metadata, ?1) IN ('array', 'object')
+                     AND json_extract(metadata, ?1) = json(?3)
+             END
+             ORDER BY timestamp DESC
+             LIMIT ?4",
+        )?;
+
+        let events = stmt
+            .query_map(
+                params![path, kind, operand, limit, user_id, include_deleted],
+                Self::row_to_event,
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// Get events with importance at or above `threshold`, newest first
+    pub fn get_events_above_importance(&self, threshold: f32, limit: u32) -> Result<Vec<Event>> {
+        self.events_above_importance(None, false, threshold, limit)
+    }
+
+    /// Events at or above an importance, optionally restricted to one user
+    fn events_above_importance(
+        &self,
+        user_id: Option<&str>,
+        include_deleted: bool,
+        threshold: f32,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             WHERE importance >= ?1 AND (?2 IS NULL OR user_id = ?2) AND (?3 OR deleted_at IS NULL)
+             ORDER BY timestamp DESC
+             LIMIT ?4",
+        )?;
+
+        let events = stmt
+            .query_map(params![threshold, user_id, include_deleted, limit], Self::row_to_event)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// Get events in the half-open range `[start, end)`, oldest first
+    pub fn get_events_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.events_between(None, false, start, end, limit)
+    }
+
+    /// Events in `[start, end)`, optionally restricted to one user
+    fn events_between(
+        &self,
+        user_id: Option<&str>,
+        include_deleted: bool,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        if start > end {
+            return Err(AgentError::Other(format!(
+                "Invalid date range: start {} is after end {}",
+                start, end
+            )));
+        }
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             WHERE timestamp >= ?1 AND timestamp < ?2
+                 AND (?3 IS NULL OR user_id = ?3) AND (?4 OR deleted_at IS NULL)
+             ORDER BY timestamp ASC
+             LIMIT ?5",
+        )?;
+
+        let events = stmt
+            .query_map(
+                params![start.to_rfc3339(), end.to_rfc3339(), user_id, include_deleted, limit],
+                Self::row_to_event,
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(events)
+    }
+
+    /// Get a page of events older than the `before` cursor, newest first
+    ///
+    /// The cursor is the `(timestamp, id)` of the last event on the previous
+    /// page, or `None` to start from the newest event. Events sharing a
+    /// timestamp are ordered by id, so a page boundary may fall inside a group
+    /// of equal timestamps without skipping or repeating events. Use
+    /// `has_events_before` to find out whether another page exists.
+    pub fn get_events_page(
+        &self,
+        before: Option<(DateTime<Utc>, Uuid)>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.events_page(None, false, before, limit)
+    }
+
+    /// A page of events before the cursor, optionally restricted to one user
+    fn events_page(
+        &self,
+        user_id: Option<&str>,
+        include_deleted: bool,
+        before: Option<(DateTime<Utc>, Uuid)>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             WHERE (?1 IS NULL OR timestamp < ?1 OR (timestamp = ?1 AND id < ?2))
+               AND (?3 IS NULL OR user_id = ?3) AND (?4 OR deleted_at IS NULL)
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?5",
+        )?;
+
+        let (timestamp, id) = match before {
+            Some((timestamp, id)) => (Some(timestamp.to_rfc3339()), Some(id.to_string())),
+            None => (None, None),
+        };
+        let events = stmt
+            .query_map(params![timestamp, id, user_id, include_deleted, limit], Self::row_to_event)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(events)
     }
 
+    /// Check whether any events exist older than the `(timestamp, id)` cursor
+    pub fn has_events_before(&self, before: (DateTime<Utc>, Uuid)) -> Result<bool> {
+        self.events_before_exist(None, false, before)
+    }
+
+    fn events_before_exist(
+        &self,
+        user_id: Option<&str>,
+        include_deleted: bool,
+        before: (DateTime<Utc>, Uuid),
+    ) -> Result<bool> {
+        let conn = self.conn()?;
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM events
+             WHERE (timestamp < ?1 OR (timestamp = ?1 AND id < ?2))
+                 AND (?3 IS NULL OR user_id = ?3) AND (?4 OR deleted_at IS NULL))",
+            params![before.0.to_rfc3339(), before.1.to_string(), user_id, include_deleted],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
     /// Store a habit
     pub fn store_habit(&self, habit: &Habit) -> Result<()> {
-        let frequency_str = match &habit.frequency {
-            HabitFrequency::Daily => "daily".to_string(),
-            HabitFrequency::Weekly => "weekly".to_string(),
-            HabitFrequency::Monthly => "monthly".to_string(),
-            HabitFrequency::Custom(s) => format!("custom:{}", s),
-        };
+        let conn = self.conn()?;
+        Self::insert_habit(&conn, habit)
+    }
+
+    fn insert_habit(conn: &Connection, habit: &Habit) -> Result<()> {
+        let frequency_str = Self::frequency_to_str(&habit.frequency);
 
-        self.conn.execute(
+        conn.execute(
             "INSERT OR REPLACE INTO habits 
-             (id, name, description, frequency, schedule, completion_count, last_completed, created_at, variance)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+             (id, name, description, frequency, schedule, completion_count, last_completed, created_at, variance, user_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
             params![
                 habit.id.to_string(),
                 habit.name,
@@ -196,173 +756,2418 @@ impl MemoryStore {
                 habit.last_completed.map(|dt| dt.to_rfc3339()),
                 habit.created_at.to_rfc3339(),
                 habit.variance,
+                habit.user_id,
             ],
         )?;
 
         Ok(())
     }
 
+    /// Store a habit with its completion history in a single transaction
+    /// `completion_count` and `last_completed` are derived from `completions`,
+    /// and any history already stored for the habit is replaced.
+    pub fn import_habit(&self, habit: &Habit, completions: &[DateTime<Utc>]) -> Result<()> {
+        let mut imported = habit.clone();
+        imported.completion_count = completions.len() as u32;
+        imported.last_completed = completions.iter().max().copied();
+
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+
+        Self::insert_habit(&tx, &imported)?;
+        tx.execute(
+            "DELETE FROM habit_completions WHERE habit_id = ?1",
+            [habit.id.to_string()],
+        )?;
+        for when in completions {
+            tx.execute(
+                "INSERT INTO habit_completions (id, habit_id, completed_at)
+                 VALUES (?1, ?2, ?3)",
+                params![Uuid::new_v4().to_string(), habit.id.to_string(), when.to_rfc3339()],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
     /// Get all active habits
     pub fn get_active_habits(&self) -> Result<Vec<Habit>> {
-        let mut stmt = self.conn.prepare(
+        self.active_habits(None)
+    }
+
+    /// Active habits, optionally restricted to one user
+    fn active_habits(&self, user_id: Option<&str>) -> Result<Vec<Habit>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
             "SELECT id, name, description, frequency, schedule, completion_count, 
-                    last_completed, created_at, variance
+                    last_completed, created_at, variance, user_id
              FROM habits
+             WHERE ?1 IS NULL OR user_id = ?1
              ORDER BY created_at DESC",
         )?;
 
         let habits = stmt
-            .query_map([], |row| {
-                let id: String = row.get(0)?;
-                let frequency_str: String = row.get(3)?;
-                let last_completed_str: Option<String> = row.get(6)?;
-                let created_at_str: String = row.get(7)?;
-
-                let frequency = if frequency_str == "daily" {
-                    HabitFrequency::Daily
-                } else if frequency_str == "weekly" {
-                    HabitFrequency::Weekly
-                } else if frequency_str == "monthly" {
-                    HabitFrequency::Monthly
-                } else if let Some(custom) = frequency_str.strip_prefix("custom:") {
-                    HabitFrequency::Custom(custom.to_string())
-                } else {
-                    HabitFrequency::Custom(frequency_str)
-                };
-
-                Ok(Habit {
-                    id: Uuid::parse_str(&id).unwrap(),
-                    name: row.get(1)?,
-                    description: row.get(2)?,
-                    frequency,
-                    schedule: row.get(4)?,
-                    completion_count: row.get(5)?,
-                    last_completed: last_completed_str.and_then(|s| {
-                        DateTime::parse_from_rfc3339(&s)
-                            .ok()
-                            .map(|dt| dt.with_timezone(&Utc))
-                    }),
-                    created_at: DateTime::parse_from_rfc3339(&created_at_str)
-                        .unwrap()
-                        .with_timezone(&Utc),
-                    variance: row.get(8)?,
-                })
-            })?
+            .query_map([user_id], Self::row_to_habit)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(habits)
     }
 
     /// Record habit completion
-    pub fn complete_habit(&self, habit_id: Uuid) -> Result<()> {
-        let completion_id = Uuid::new_v4();
-        let now = Utc::now();
+    /// Returns the habit with its updated count and last completion
+    pub fn complete_habit(&self, habit_id: Uuid) -> Result<Habit> {
+        self.complete_habit_at(habit_id, Utc::now())
+    }
+
+    /// Record a habit completion at a specific time, e.g. when backfilling history
+    /// `last_completed` only moves forward, so out-of-order inserts are safe
+    pub fn complete_habit_at(&self, habit_id: Uuid, when: DateTime<Utc>) -> Result<Habit> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+
+        // Update habit
+        let updated = tx.execute(
+            "UPDATE habits 
+             SET completion_count = completion_count + 1,
+                 last_completed = CASE
+                     WHEN last_completed IS NULL OR last_completed < ?1 THEN ?1
+                     ELSE last_completed
+                 END
+             WHERE id = ?2",
+            params![when.to_rfc3339(), habit_id.to_string()],
+        )?;
+
+        if updated == 0 {
+            return Err(AgentError::Memory(format!("Habit {} not found", habit_id)));
+        }
 
         // Insert completion record
-        self.conn.execute(
+        tx.execute(
             "INSERT INTO habit_completions (id, habit_id, completed_at)
              VALUES (?1, ?2, ?3)",
             params![
-                completion_id.to_string(),
+                Uuid::new_v4().to_string(),
                 habit_id.to_string(),
-                now.to_rfc3339(),
+                when.to_rfc3339(),
             ],
         )?;
 
-        // Update habit
-        self.conn.execute(
-            "UPDATE habits 
-             SET completion_count = completion_count + 1,
-                 last_completed = ?1
-             WHERE id = ?2",
-            params![now.to_rfc3339(), habit_id.to_string()],
+        let habit = tx.query_row(
+            "SELECT id, name, description, frequency, schedule, completion_count, 
+                    last_completed, created_at, variance, user_id
+             FROM habits
+             WHERE id = ?1",
+            [habit_id.to_string()],
+            Self::row_to_habit,
         )?;
+        tx.commit()?;
 
-        Ok(())
+        Ok(habit)
     }
 
-    /// Clear old events (privacy/retention policy)
-    pub fn clear_events_before(&self, before: DateTime<Utc>) -> Result<usize> {
-        let deleted = self.conn.execute(
-            "DELETE FROM events WHERE timestamp < ?1",
-            params![before.to_rfc3339()],
+    /// Get all completion timestamps for a habit, oldest first
+    /// The result can be passed straight into `HabitAnalyzer`
+    pub fn get_habit_completions(&self, habit_id: Uuid) -> Result<Vec<DateTime<Utc>>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT completed_at
+             FROM habit_completions
+             WHERE habit_id = ?1
+             ORDER BY completed_at ASC",
         )?;
 
-        Ok(deleted)
-    }
+        let completions = stmt
+            .query_map([habit_id.to_string()], |row| {
+                let completed_at: String = row.get(0)?;
+                Self::parse_timestamp(0, &completed_at)
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
 
-    /// Get event count
-    pub fn event_count(&self) -> Result<usize> {
-        let count: usize = self
-            .conn
-            .query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))?;
-        Ok(count)
+        Ok(completions)
     }
 
-    /// Get habit count
-    pub fn habit_count(&self) -> Result<usize> {
-        let count: usize = self
-            .conn
-            .query_row("SELECT COUNT(*) FROM habits", [], |row| row.get(0))?;
-        Ok(count)
-    }
-}
+    /// Recompute a habit's stored `variance` from its completions
+    /// Returns the new value, which is `None` with fewer than two completions.
+    /// `complete_habit` does not call this, so it stays a single insert;
+    /// run it from batch jobs or after a burst of completions.
+    pub fn recompute_habit_variance(&self, habit_id: Uuid) -> Result<Option<f32>> {
+        let completions = self.get_habit_completions(habit_id)?;
+        let variance = HabitAnalyzer::new().calculate_variance(&completions);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let updated = self.conn()?.execute(
+            "UPDATE habits SET variance = ?1 WHERE id = ?2",
+            params![variance, habit_id.to_string()],
+        )?;
+        if updated == 0 {
+            return Err(AgentError::Memory(format!("Habit {} not found", habit_id)));
+        }
 
-    #[test]
-    fn test_memory_store_creation() {
-        let store = MemoryStore::in_memory().unwrap();
-        assert_eq!(store.event_count().unwrap(), 0);
-        assert_eq!(store.habit_count().unwrap(), 0);
+        Ok(variance)
     }
 
-    #[test]
-    fn test_store_and_retrieve_event() {
-        let store = MemoryStore::in_memory().unwrap();
-        let event = Event::new("test".to_string(), "test event".to_string(), 0.5);
+    /// Delete a habit and all of its completions
+    /// Returns an error if the habit does not exist
+    pub fn delete_habit(&self, habit_id: Uuid) -> Result<()> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
 
-        store.store_event(&event).unwrap();
-        assert_eq!(store.event_count().unwrap(), 1);
+        // Foreign keys are not enforced, so remove completions explicitly
+        tx.execute(
+            "DELETE FROM habit_completions WHERE habit_id = ?1",
+            [habit_id.to_string()],
+        )?;
 
-        let events = store.get_recent_events(10).unwrap();
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_type, "test");
+        let deleted = tx.execute("DELETE FROM habits WHERE id = ?1", [habit_id.to_string()])?;
+        if deleted == 0 {
+            return Err(AgentError::Memory(format!("Habit {} not found", habit_id)));
+        }
+
+        tx.commit()?;
+        Ok(())
     }
 
-    #[test]
-    fn test_store_and_retrieve_habit() {
-        let store = MemoryStore::in_memory().unwrap();
-        let habit = Habit::new(
-            "Morning exercise".to_string(),
-            "Exercise every morning".to_string(),
-            HabitFrequency::Daily,
-        );
+    /// Merge events, habits and completions from another store (e.g. a second device)
+    ///
+    /// Records are deduplicated by id; events with a new id but identical type,
+    /// description and timestamp are treated as duplicates. Habit completion
+    /// counts are summed from completions not already present locally, so
+    /// merging the same store twice is a no-op. Records sharing an id but
+    /// differing in content are reported as conflicts and never overwritten.
+    pub fn merge_from(&self, other: &MemoryStore) -> Result<MergeReport> {
+        let mut report = MergeReport::default();
 
-        store.store_habit(&habit).unwrap();
-        assert_eq!(store.habit_count().unwrap(), 1);
+        // Merging a store into itself is a no-op
+        if std::ptr::eq(self, other) {
+            return Ok(report);
+        }
 
-        let habits = store.get_active_habits().unwrap();
+        // Read `other` and release its lock before locking `self`, so merges
+        // running in both directions at once cannot deadlock
+        let (events, habits) = other.merge_source()?;
+
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+
+        for event in events {
+            if let Some(existing) = Self::query_event(&tx, event.id)? {
+                if !Self::same_event(&existing, &event) {
+                    report.conflicts.push(MergeConflict::Event(event.id));
+                } else {
+                    report.events_skipped += 1;
+                }
+                continue;
+            }
+
+            let duplicates: usize = tx.query_row(
+                "SELECT COUNT(*) FROM events
+                 WHERE event_type = ?1 AND description = ?2 AND timestamp = ?3 AND user_id = ?4",
+                params![
+                    event.event_type,
+                    event.description,
+                    event.timestamp.to_rfc3339(),
+                    event.user_id
+                ],
+                |row| row.get(0),
+            )?;
+            if duplicates > 0 {
+                report.events_skipped += 1;
+                continue;
+            }
+
+            Self::insert_event(&tx, &event)?;
+            report.events_added += 1;
+        }
+
+        for (habit, completions) in habits {
+            let existing = Self::query_habit(&tx, habit.id)?;
+            match &existing {
+                None => {
+                    Self::insert_habit(&tx, &habit)?;
+                    report.habits_added += 1;
+                }
+                Some(local) if !Self::same_habit(local, &habit) => {
+                    report.conflicts.push(MergeConflict::Habit(habit.id));
+                }
+                Some(_) => {}
+            }
+
+            for (completion_id, completed_at) in completions {
+                let inserted = tx.execute(
+                    "INSERT OR IGNORE INTO habit_completions (id, habit_id, completed_at)
+                     VALUES (?1, ?2, ?3)",
+                    params![completion_id, habit.id.to_string(), completed_at],
+                )?;
+
+                if inserted == 0 {
+                    continue;
+                }
+                report.completions_added += 1;
+
+                // A newly imported habit already carries its own count
+                if existing.is_some() {
+                    tx.execute(
+                        "UPDATE habits
+                         SET completion_count = completion_count + 1,
+                             last_completed = MAX(COALESCE(last_completed, ''), ?1)
+                         WHERE id = ?2",
+                        params![completed_at, habit.id.to_string()],
+                    )?;
+                }
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(report)
+    }
+
+    /// Every event, and every habit with its `(id, completed_at)` completions,
+    /// as read by `merge_from`
+    fn merge_source(&self) -> Result<(Vec<Event>, Vec<HabitRows>)> {
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             ORDER BY timestamp ASC",
+        )?;
+        let events = stmt
+            .query_map([], Self::row_to_event)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, frequency, schedule, completion_count,
+                    last_completed, created_at, variance, user_id
+             FROM habits",
+        )?;
+        let habits = stmt
+            .query_map([], Self::row_to_habit)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn.prepare("SELECT id, completed_at FROM habit_completions WHERE habit_id = ?1")?;
+        let habits = habits
+            .into_iter()
+            .map(|habit| {
+                let completions = stmt
+                    .query_map([habit.id.to_string()], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok((habit, completions))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((events, habits))
+    }
+
+    fn query_habit(conn: &Connection, id: Uuid) -> Result<Option<Habit>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, frequency, schedule, completion_count,
+                    last_completed, created_at, variance, user_id
+             FROM habits
+             WHERE id = ?1",
+        )?;
+        let mut habits = stmt.query_map([id.to_string()], Self::row_to_habit)?;
+
+        Ok(habits.next().transpose()?)
+    }
+
+    fn same_event(a: &Event, b: &Event) -> bool {
+        a.event_type == b.event_type
+            && a.description == b.description
+            && a.importance == b.importance
+            && a.metadata == b.metadata
+            && a.timestamp == b.timestamp
+            && a.tags == b.tags
+            && a.user_id == b.user_id
+    }
+
+    /// Compare the identifying fields of two habits, ignoring completion stats
+    fn same_habit(a: &Habit, b: &Habit) -> bool {
+        a.name == b.name
+            && a.description == b.description
+            && Self::frequency_to_str(&a.frequency) == Self::frequency_to_str(&b.frequency)
+            && a.schedule == b.schedule
+            && a.created_at == b.created_at
+    }
+
+    /// Clear old events (privacy/retention policy)
+    /// Events are soft-deleted; `purge_soft_deleted` removes them for good.
+    /// Returns the number of events newly cleared.
+    pub fn clear_events_before(&self, before: DateTime<Utc>) -> Result<usize> {
+        let conn = self.conn()?;
+        let deleted = conn.execute(
+            "UPDATE events SET deleted_at = ?2 WHERE timestamp < ?1 AND deleted_at IS NULL",
+            params![before.to_rfc3339(), Utc::now().to_rfc3339()],
+        )?;
+
+        Ok(deleted)
+    }
+
+    /// Clear every event recorded from one source, e.g. an integration being removed
+    /// Events are soft-deleted, like `clear_events_before`, and events without
+    /// a source are never matched. Returns the number newly cleared.
+    pub fn clear_events_by_source(&self, source: &str) -> Result<usize> {
+        let conn = self.conn()?;
+        let deleted = conn.execute(
+            "UPDATE events SET deleted_at = ?2 WHERE source = ?1 AND deleted_at IS NULL",
+            params![source, Utc::now().to_rfc3339()],
+        )?;
+
+        Ok(deleted)
+    }
+
+    /// Hide an event from recall without deleting it yet
+    /// The row is kept, with `deleted_at` set, until `purge_soft_deleted`
+    /// removes it. Deleting an already soft-deleted event keeps its original
+    /// `deleted_at`. Returns an error if the event does not exist.
+    pub fn soft_delete_event(&self, id: Uuid) -> Result<()> {
+        let updated = self.conn()?.execute(
+            "UPDATE events SET deleted_at = COALESCE(deleted_at, ?1) WHERE id = ?2",
+            params![Utc::now().to_rfc3339(), id.to_string()],
+        )?;
+        if updated == 0 {
+            return Err(AgentError::Memory(format!("Event {} not found", id)));
+        }
+
+        Ok(())
+    }
+
+    /// Permanently delete events soft-deleted more than `older_than` ago
+    /// Returns the number of events removed.
+    pub fn purge_soft_deleted(&self, older_than: chrono::Duration) -> Result<usize> {
+        let cutoff = Utc::now() - older_than;
+        let deleted = self.conn()?.execute(
+            "DELETE FROM events WHERE deleted_at IS NOT NULL AND deleted_at <= ?1",
+            params![cutoff.to_rfc3339()],
+        )?;
+
+        Ok(deleted)
+    }
+
+    /// Recall that also returns soft-deleted events, e.g. to show what was forgotten
+    pub fn include_deleted(&self) -> DeletedEventsView<'_> {
+        DeletedEventsView { store: self }
+    }
+
+    /// Export all events, habits and completions as a versioned JSON document
+    pub fn export_json(&self) -> Result<String> {
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+             FROM events
+             ORDER BY timestamp ASC",
+        )?;
+        let events = stmt
+            .query_map([], Self::row_to_event)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, frequency, schedule, completion_count,
+                    last_completed, created_at, variance, user_id
+             FROM habits
+             ORDER BY created_at ASC",
+        )?;
+        let habits = stmt
+            .query_map([], Self::row_to_habit)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, habit_id, completed_at
+             FROM habit_completions
+             ORDER BY completed_at ASC",
+        )?;
+        let completions = stmt
+            .query_map([], |row| {
+                let id: String = row.get(0)?;
+                let habit_id: String = row.get(1)?;
+                let completed_at: String = row.get(2)?;
+
+                Ok(HabitCompletion {
+                    id: Self::parse_uuid(0, &id)?,
+                    habit_id: Self::parse_uuid(1, &habit_id)?,
+                    completed_at: Self::parse_timestamp(2, &completed_at)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let export = MemoryExport {
+            version: EXPORT_VERSION,
+            events,
+            habits,
+            completions,
+        };
+
+        Ok(serde_json::to_string_pretty(&export)?)
+    }
+
+    /// Import a document produced by `export_json`, preserving ids
+    /// Completions added to a habit that already existed locally update its
+    /// count and last completion, as `merge_from` does; completions for a
+    /// habit in neither the document nor the store are skipped.
+    pub fn import_json(&self, data: &str, mode: ImportMode) -> Result<ImportStats> {
+        let export: MemoryExport = serde_json::from_str(data)?;
+        if export.version > EXPORT_VERSION {
+            return Err(AgentError::Memory(format!(
+                "Unsupported export version {} (expected at most {})",
+                export.version, EXPORT_VERSION
+            )));
+        }
+
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let mut stats = ImportStats::default();
+
+        if mode == ImportMode::Replace {
+            tx.execute_batch(
+                "DELETE FROM habit_completions;
+                 DELETE FROM habits;
+                 DELETE FROM events;",
+            )?;
+        }
+
+        for event in &export.events {
+            if Self::query_event(&tx, event.id)?.is_some() {
+                stats.skipped += 1;
+                continue;
+            }
+            Self::insert_event(&tx, event)?;
+            stats.events_imported += 1;
+        }
+
+        // Newly imported habits already carry their own counts
+        let mut imported_habits = HashSet::new();
+        for habit in &export.habits {
+            if Self::query_habit(&tx, habit.id)?.is_some() {
+                stats.skipped += 1;
+                continue;
+            }
+            Self::insert_habit(&tx, habit)?;
+            imported_habits.insert(habit.id);
+            stats.habits_imported += 1;
+        }
+
+        for completion in &export.completions {
+            let imported = imported_habits.contains(&completion.habit_id);
+            if !imported && Self::query_habit(&tx, completion.habit_id)?.is_none() {
+                stats.skipped += 1;
+                continue;
+            }
+
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO habit_completions (id, habit_id, completed_at)
+                 VALUES (?1, ?2, ?3)",
+                params![
+                    completion.id.to_string(),
+                    completion.habit_id.to_string(),
+                    completion.completed_at.to_rfc3339(),
+                ],
+            )?;
+            if inserted == 0 {
+                stats.skipped += 1;
+                continue;
+            }
+            stats.completions_imported += 1;
+
+            if !imported {
+                tx.execute(
+                    "UPDATE habits
+                     SET completion_count = completion_count + 1,
+                         last_completed = MAX(COALESCE(last_completed, ''), ?1)
+                     WHERE id = ?2",
+                    params![completion.completed_at.to_rfc3339(), completion.habit_id.to_string()],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// Replace all persisted permission grants with the given set
+    pub fn store_permissions(&self, permissions: &[Permission]) -> Result<()> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+
+        tx.execute("DELETE FROM permissions", [])?;
+        for permission in permissions {
+            tx.execute(
+                "INSERT INTO permissions
+                 (module, actions, scope, granted_at, expires_at, active_start, active_end, max_uses, uses)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    permission.module,
+                    serde_json::to_string(&permission.actions)?,
+                    serde_json::to_string(&permission.scope)?,
+                    permission.granted_at.to_rfc3339(),
+                    permission.expires_at.map(|dt| dt.to_rfc3339()),
+                    permission.active_hours.map(|(start, _)| start.to_string()),
+                    permission.active_hours.map(|(_, end)| end.to_string()),
+                    permission.max_uses,
+                    permission.uses,
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Load all persisted permission grants, in the order they were stored
+    pub fn load_permissions(&self) -> Result<Vec<Permission>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT module, actions, scope, granted_at, expires_at, active_start, active_end,
+                    max_uses, uses
+             FROM permissions
+             ORDER BY id ASC",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let granted_at: String = row.get(3)?;
+                let expires_at: Option<String> = row.get(4)?;
+                let active_start: Option<String> = row.get(5)?;
+                let active_end: Option<String> = row.get(6)?;
+
+                let active_hours = match (active_start, active_end) {
+                    (Some(start), Some(end)) => {
+                        Some((Self::parse_time(5, &start)?, Self::parse_time(6, &end)?))
+                    }
+                    _ => None,
+                };
+
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    Self::parse_timestamp(3, &granted_at)?,
+                    expires_at
+                        .map(|s| Self::parse_timestamp(4, &s))
+                        .transpose()?,
+                    active_hours,
+                    row.get::<_, Option<u32>>(7)?,
+                    row.get::<_, u32>(8)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(module, actions, scope, granted_at, expires_at, active_hours, max_uses, uses)| {
+                Ok(Permission {
+                    module,
+                    actions: serde_json::from_str(&actions)?,
+                    scope: serde_json::from_str(&scope)?,
+                    granted_at,
+                    expires_at,
+                    active_hours,
+                    max_uses,
+                    uses,
+                })
+            })
+            .collect()
+    }
+
+    /// Enforce a retention policy, returning the total number of events soft-deleted
+    pub fn apply_retention(&self, policy: &RetentionPolicy) -> Result<usize> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let now = Utc::now();
+        let mut deleted = 0;
+
+        if let Some(max_age) = policy.max_age {
+            let cutoff = now - max_age;
+            deleted += tx.execute(
+                "UPDATE events SET deleted_at = ?2 WHERE timestamp < ?1 AND deleted_at IS NULL",
+                params![cutoff.to_rfc3339(), now.to_rfc3339()],
+            )?;
+        }
+
+        if let Some(max_events) = policy.max_events {
+            deleted += tx.execute(
+                "UPDATE events SET deleted_at = ?2
+                 WHERE deleted_at IS NULL AND id NOT IN (
+                    SELECT id FROM events
+                    WHERE deleted_at IS NULL
+                    ORDER BY timestamp DESC, id DESC
+                    LIMIT ?1
+                 )",
+                params![max_events as i64, now.to_rfc3339()],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Collapse runs of the same event logged repeatedly within `window`
+    ///
+    /// Events of one user with matching type and description that follow the
+    /// run's earliest event by at most `window` are soft-deleted and folded
+    /// into it, which keeps a `count` entry in its metadata. Returns how many
+    /// events were merged away.
+    pub fn dedup_events(&self, window: chrono::Duration) -> Result<usize> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()?;
+
+        let events = {
+            let mut stmt = tx.prepare(
+                "SELECT id, event_type, description, importance, metadata, timestamp, tags, user_id, source, deleted_at
+                 FROM events
+                 WHERE deleted_at IS NULL
+                 ORDER BY user_id, timestamp ASC, id ASC",
+            )?;
+            let events = stmt
+                .query_map([], Self::row_to_event)?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            events
+        };
+
+        let event_count = |event: &Event| {
+            event
+                .metadata
+                .get("count")
+                .and_then(|c| c.as_u64())
+                .unwrap_or(1)
+        };
+
+        let now = Utc::now().to_rfc3339();
+        let mut merged = 0;
+        // The run's earliest event, and whether anything was folded into it
+        let mut kept: Option<(Event, bool)> = None;
+        for event in events {
+            if let Some((run, changed)) = kept.as_mut() {
+                let duplicate = run.user_id == event.user_id
+                    && run.event_type == event.event_type
+                    && run.description == event.description
+                    && event.timestamp.signed_duration_since(run.timestamp) <= window;
+                if duplicate {
+                    let count = event_count(run) + event_count(&event);
+                    run.metadata.insert("count".to_string(), count.into());
+                    *changed = true;
+                    tx.execute(
+                        "UPDATE events SET deleted_at = ?1 WHERE id = ?2",
+                        params![now, event.id.to_string()],
+                    )?;
+                    merged += 1;
+                    continue;
+                }
+            }
+
+            if let Some((run, true)) = kept.replace((event, false)) {
+                Self::save_event_metadata(&tx, &run)?;
+            }
+        }
+        if let Some((run, true)) = kept {
+            Self::save_event_metadata(&tx, &run)?;
+        }
+
+        tx.commit()?;
+        Ok(merged)
+    }
+
+    fn save_event_metadata(conn: &Connection, event: &Event) -> Result<()> {
+        conn.execute(
+            "UPDATE events SET metadata = ?1 WHERE id = ?2",
+            params![serde_json::to_string(&event.metadata)?, event.id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Get event count
+    pub fn event_count(&self) -> Result<usize> {
+        self.count_events(None, false)
+    }
+
+    fn count_events(&self, user_id: Option<&str>, include_deleted: bool) -> Result<usize> {
+        let count: usize = self.conn()?.query_row(
+            "SELECT COUNT(*) FROM events
+             WHERE (?1 IS NULL OR user_id = ?1) AND (?2 OR deleted_at IS NULL)",
+            params![user_id, include_deleted],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    /// Scope reads and writes to one household member
+    pub fn for_user(&self, user_id: &str) -> UserMemory<'_> {
+        UserMemory {
+            store: self,
+            user_id: user_id.to_string(),
+        }
+    }
+
+    /// Get event counts, average importance and last occurrence per event type
+    /// Ordered by count, most frequent first
+    pub fn event_stats(&self) -> Result<Vec<EventTypeStats>> {
+        self.stats_by_type(None)
+    }
+
+    fn stats_by_type(&self, user_id: Option<&str>) -> Result<Vec<EventTypeStats>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT event_type, COUNT(*), AVG(importance), MAX(timestamp)
+             FROM events
+             WHERE (?1 IS NULL OR user_id = ?1) AND deleted_at IS NULL
+             GROUP BY event_type
+             ORDER BY COUNT(*) DESC, event_type ASC",
+        )?;
+
+        let stats = stmt
+            .query_map([user_id], |row| {
+                let count: i64 = row.get(1)?;
+                let avg_importance: f64 = row.get(2)?;
+                let last_seen: String = row.get(3)?;
+
+                Ok(EventTypeStats {
+                    event_type: row.get(0)?,
+                    count: count as u64,
+                    avg_importance: avg_importance as f32,
+                    last_seen: Self::parse_timestamp(3, &last_seen)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(stats)
+    }
+
+    /// Count events in `buckets` equal-width importance ranges over [0, 1]
+    /// Returns `(low, high, count)` for every bucket, lowest first, including
+    /// empty ones. Importance of exactly 1.0 falls in the top bucket.
+    pub fn importance_histogram(&self, buckets: usize) -> Result<Vec<(f32, f32, u64)>> {
+        self.histogram(None, buckets)
+    }
+
+    fn histogram(&self, user_id: Option<&str>, buckets: usize) -> Result<Vec<(f32, f32, u64)>> {
+        if buckets == 0 {
+            return Err(AgentError::Memory("Histogram needs at least one bucket".to_string()));
+        }
+
+        let conn = self.conn()?;
+        // Importance is an f32 widened on storage (0.7 reads back as 0.69999998),
+        // so round before flooring to keep boundary values in their own bucket
+        let mut stmt = conn.prepare(
+            "SELECT MAX(0, MIN(?1 - 1, CAST(ROUND(importance * ?1, 6) AS INTEGER))) AS bucket, COUNT(*)
+             FROM events
+             WHERE (?2 IS NULL OR user_id = ?2) AND deleted_at IS NULL
+             GROUP BY bucket",
+        )?;
+
+        let mut counts = vec![0u64; buckets];
+        let rows = stmt.query_map(params![buckets as i64, user_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (bucket, count) = row?;
+            counts[bucket as usize] = count as u64;
+        }
+
+        let width = 1.0 / buckets as f32;
+        Ok(counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| (i as f32 * width, (i + 1) as f32 * width, count))
+            .collect())
+    }
+
+    /// Get habit count
+    pub fn habit_count(&self) -> Result<usize> {
+        let count: usize = self
+            .conn()?
+            .query_row("SELECT COUNT(*) FROM habits", [], |row| row.get(0))?;
+        Ok(count)
+    }
+}
+
+/// View of a `MemoryStore` restricted to one user's events and habits
+/// Records stored through the view are assigned to that user.
+pub struct UserMemory<'a> {
+    store: &'a MemoryStore,
+    user_id: String,
+}
+
+impl UserMemory<'_> {
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// Store an event for this user
+    pub fn store_event(&self, event: &Event) -> Result<()> {
+        self.store.store_event(&event.clone().with_user(self.user_id.clone()))
+    }
+
+    /// Store a habit for this user
+    pub fn store_habit(&self, habit: &Habit) -> Result<()> {
+        self.store.store_habit(&habit.clone().with_user(self.user_id.clone()))
+    }
+
+    /// Get this user's recent events
+    pub fn get_recent_events(&self, limit: u32) -> Result<Vec<Event>> {
+        self.store.recent_events(Some(&self.user_id), false, limit)
+    }
+
+    /// Get this user's events of one type
+    pub fn get_events_by_type(&self, event_type: &str, limit: u32) -> Result<Vec<Event>> {
+        self.store.events_by_type(Some(&self.user_id), false, event_type, limit)
+    }
+
+    /// Get this user's active habits
+    pub fn get_active_habits(&self) -> Result<Vec<Habit>> {
+        self.store.active_habits(Some(&self.user_id))
+    }
+
+    /// Get this user's events carrying a tag
+    pub fn get_events_by_tag(&self, tag: &str, limit: u32) -> Result<Vec<Event>> {
+        self.store.events_by_tag(Some(&self.user_id), false, tag, limit)
+    }
+
+    /// Get this user's events whose `metadata[key]` equals `value`
+    pub fn get_events_by_metadata(
+        &self,
+        key: &str,
+        value: &serde_json::Value,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.store.events_by_metadata(Some(&self.user_id), false, key, value, limit)
+    }
+
+    /// Get this user's events with importance at or above `threshold`
+    pub fn get_events_above_importance(&self, threshold: f32, limit: u32) -> Result<Vec<Event>> {
+        self.store.events_above_importance(Some(&self.user_id), false, threshold, limit)
+    }
+
+    /// Get this user's events in `[start, end)`, oldest first
+    pub fn get_events_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.store.events_between(Some(&self.user_id), false, start, end, limit)
+    }
+
+    /// Get a page of this user's events, as `MemoryStore::get_events_page`
+    pub fn get_events_page(
+        &self,
+        before: Option<(DateTime<Utc>, Uuid)>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.store.events_page(Some(&self.user_id), false, before, limit)
+    }
+
+    /// Check whether this user has events older than the cursor
+    pub fn has_events_before(&self, before: (DateTime<Utc>, Uuid)) -> Result<bool> {
+        self.store.events_before_exist(Some(&self.user_id), false, before)
+    }
+
+    /// Per-type statistics over this user's events
+    pub fn event_stats(&self) -> Result<Vec<EventTypeStats>> {
+        self.store.stats_by_type(Some(&self.user_id))
+    }
+
+    /// Importance histogram over this user's events
+    pub fn importance_histogram(&self, buckets: usize) -> Result<Vec<(f32, f32, u64)>> {
+        self.store.histogram(Some(&self.user_id), buckets)
+    }
+
+    /// Count this user's events
+    pub fn event_count(&self) -> Result<usize> {
+        self.store.count_events(Some(&self.user_id), false)
+    }
+}
+
+/// View of a `MemoryStore` whose recall includes soft-deleted events
+/// Such events carry their `deleted_at` timestamp.
+pub struct DeletedEventsView<'a> {
+    store: &'a MemoryStore,
+}
+
+impl DeletedEventsView<'_> {
+    /// Get recent events, soft-deleted or not
+    pub fn get_recent_events(&self, limit: u32) -> Result<Vec<Event>> {
+        self.store.recent_events(None, true, limit)
+    }
+
+    /// Get events of one type, soft-deleted or not
+    pub fn get_events_by_type(&self, event_type: &str, limit: u32) -> Result<Vec<Event>> {
+        self.store.events_by_type(None, true, event_type, limit)
+    }
+
+    /// Get a single event by id, soft-deleted or not
+    pub fn get_event(&self, id: Uuid) -> Result<Option<Event>> {
+        let conn = self.store.conn()?;
+        MemoryStore::query_event(&conn, id)
+    }
+
+    /// Get events carrying a tag, soft-deleted or not
+    pub fn get_events_by_tag(&self, tag: &str, limit: u32) -> Result<Vec<Event>> {
+        self.store.events_by_tag(None, true, tag, limit)
+    }
+
+    /// Get events whose `metadata[key]` equals `value`, soft-deleted or not
+    pub fn get_events_by_metadata(
+        &self,
+        key: &str,
+        value: &serde_json::Value,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.store.events_by_metadata(None, true, key, value, limit)
+    }
+
+    /// Get events with importance at or above `threshold`, soft-deleted or not
+    pub fn get_events_above_importance(&self, threshold: f32, limit: u32) -> Result<Vec<Event>> {
+        self.store.events_above_importance(None, true, threshold, limit)
+    }
+
+    /// Get events in `[start, end)`, soft-deleted or not
+    pub fn get_events_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.store.events_between(None, true, start, end, limit)
+    }
+
+    /// Get a page of events, soft-deleted or not
+    pub fn get_events_page(
+        &self,
+        before: Option<(DateTime<Utc>, Uuid)>,
+        limit: u32,
+    ) -> Result<Vec<Event>> {
+        self.store.events_page(None, true, before, limit)
+    }
+
+    /// Check whether any events, soft-deleted or not, are older than the cursor
+    pub fn has_events_before(&self, before: (DateTime<Utc>, Uuid)) -> Result<bool> {
+        self.store.events_before_exist(None, true, before)
+    }
+
+    /// Count events, soft-deleted or not
+    pub fn event_count(&self) -> Result<usize> {
+        self.store.count_events(None, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DEFAULT_USER_ID;
+
+    #[test]
+    fn test_memory_store_creation() {
+        let store = MemoryStore::in_memory().unwrap();
+        assert_eq!(store.event_count().unwrap(), 0);
+        assert_eq!(store.habit_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_store_and_retrieve_event() {
+        let store = MemoryStore::in_memory().unwrap();
+        let event = Event::new("test".to_string(), "test event".to_string(), 0.5);
+
+        store.store_event(&event).unwrap();
+        assert_eq!(store.event_count().unwrap(), 1);
+
+        let events = store.get_recent_events(10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "test");
+    }
+
+    #[test]
+    fn test_store_and_retrieve_habit() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Morning exercise".to_string(),
+            "Exercise every morning".to_string(),
+            HabitFrequency::Daily,
+        );
+
+        store.store_habit(&habit).unwrap();
+        assert_eq!(store.habit_count().unwrap(), 1);
+
+        let habits = store.get_active_habits().unwrap();
+        assert_eq!(habits.len(), 1);
+        assert_eq!(habits[0].name, "Morning exercise");
+    }
+
+    #[test]
+    fn test_complete_habit() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Morning exercise".to_string(),
+            "Exercise every morning".to_string(),
+            HabitFrequency::Daily,
+        );
+
+        store.store_habit(&habit).unwrap();
+        store.complete_habit(habit.id).unwrap();
+
+        let habits = store.get_active_habits().unwrap();
+        assert_eq!(habits[0].completion_count, 1);
+        assert!(habits[0].last_completed.is_some());
+    }
+
+    #[test]
+    fn test_merge_from() {
+        let local = MemoryStore::in_memory().unwrap();
+        let remote = MemoryStore::in_memory().unwrap();
+
+        // Shared event present on both devices
+        let shared = Event::new("note".to_string(), "shared".to_string(), 0.5);
+        local.store_event(&shared).unwrap();
+        remote.store_event(&shared).unwrap();
+
+        // Same content recorded independently with a different id
+        let local_copy = Event::new("note".to_string(), "copied".to_string(), 0.5);
+        let mut remote_copy = local_copy.clone();
+        remote_copy.id = Uuid::new_v4();
+        local.store_event(&local_copy).unwrap();
+        remote.store_event(&remote_copy).unwrap();
+
+        // Same id, diverging content
+        let mut conflicting = Event::new("note".to_string(), "original".to_string(), 0.5);
+        local.store_event(&conflicting).unwrap();
+        conflicting.description = "edited".to_string();
+        remote.store_event(&conflicting).unwrap();
+
+        // Disjoint event only on the remote
+        let remote_only = Event::new("note".to_string(), "remote".to_string(), 0.5);
+        remote.store_event(&remote_only).unwrap();
+
+        // Habit completed on both devices with one shared completion
+        let habit = Habit::new(
+            "Exercise".to_string(),
+            "Daily exercise".to_string(),
+            HabitFrequency::Daily,
+        );
+        local.store_habit(&habit).unwrap();
+        local.complete_habit(habit.id).unwrap();
+        remote.merge_from(&local).unwrap();
+        remote.complete_habit(habit.id).unwrap();
+        remote.complete_habit(habit.id).unwrap();
+
+        let remote_habit = Habit::new(
+            "Reading".to_string(),
+            "Read a chapter".to_string(),
+            HabitFrequency::Daily,
+        );
+        remote.store_habit(&remote_habit).unwrap();
+        remote.complete_habit(remote_habit.id).unwrap();
+
+        let report = local.merge_from(&remote).unwrap();
+        assert_eq!(report.events_added, 1);
+        assert_eq!(report.habits_added, 1);
+        assert_eq!(report.completions_added, 3);
+        assert_eq!(report.conflicts, vec![MergeConflict::Event(conflicting.id)]);
+
+        assert_eq!(local.event_count().unwrap(), 4);
+        assert_eq!(local.habit_count().unwrap(), 2);
+
+        let habits = local.get_active_habits().unwrap();
+        let merged = habits.iter().find(|h| h.id == habit.id).unwrap();
+        assert_eq!(merged.completion_count, 3);
+        let imported = habits.iter().find(|h| h.id == remote_habit.id).unwrap();
+        assert_eq!(imported.completion_count, 1);
+
+        // Local copy wins on conflict
+        let kept = local.get_event(conflicting.id).unwrap().unwrap();
+        assert_eq!(kept.description, "original");
+
+        // Merging again changes nothing
+        let report = local.merge_from(&remote).unwrap();
+        assert_eq!(report.events_added, 0);
+        assert_eq!(report.completions_added, 0);
+        assert_eq!(local.event_count().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_get_events_page_empty() {
+        let store = MemoryStore::in_memory().unwrap();
+        assert!(store.get_events_page(None, 10).unwrap().is_empty());
+        assert!(!store.has_events_before((Utc::now(), Uuid::nil())).unwrap());
+    }
+
+    #[test]
+    fn test_get_events_page_exact_limit() {
+        let store = MemoryStore::in_memory().unwrap();
+        let base = Utc::now();
+        for i in 0..3 {
+            let mut event = Event::new("test".to_string(), format!("event {}", i), 0.5);
+            event.timestamp = base - chrono::Duration::minutes(i);
+            store.store_event(&event).unwrap();
+        }
+
+        let page = store.get_events_page(None, 3).unwrap();
+        assert_eq!(page.len(), 3);
+        assert_eq!(page[0].description, "event 0");
+        assert_eq!(page[2].description, "event 2");
+
+        let last = page.last().unwrap();
+        let cursor = (last.timestamp, last.id);
+        assert!(!store.has_events_before(cursor).unwrap());
+        assert!(store.get_events_page(Some(cursor), 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_events_page_cursor() {
+        let store = MemoryStore::in_memory().unwrap();
+        let base = Utc::now();
+        for i in 0..5 {
+            let mut event = Event::new("test".to_string(), format!("event {}", i), 0.5);
+            event.timestamp = base - chrono::Duration::minutes(i);
+            store.store_event(&event).unwrap();
+        }
+
+        let first = store.get_events_page(None, 2).unwrap();
+        let last = first.last().unwrap();
+        let cursor = (last.timestamp, last.id);
+        assert!(store.has_events_before(cursor).unwrap());
+
+        let second = store.get_events_page(Some(cursor), 2).unwrap();
+        assert_eq!(second.len(), 2);
+        assert_eq!(second[0].description, "event 2");
+        assert_eq!(second[1].description, "event 3");
+    }
+
+    #[test]
+    fn test_get_events_page_duplicate_timestamps() {
+        let store = MemoryStore::in_memory().unwrap();
+        let timestamp = Utc::now();
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let mut event = Event::new("test".to_string(), format!("event {}", i), 0.5);
+            event.timestamp = timestamp;
+            ids.push(event.id.to_string());
+            store.store_event(&event).unwrap();
+        }
+        ids.sort();
+        ids.reverse();
+
+        // Page through the tie group two at a time
+        let first = store.get_events_page(None, 2).unwrap();
+        let last = first.last().unwrap();
+        let cursor = (last.timestamp, last.id);
+        assert!(store.has_events_before(cursor).unwrap());
+        let second = store.get_events_page(Some(cursor), 2).unwrap();
+        assert_eq!(second.len(), 1);
+
+        let page_ids: Vec<String> = first
+            .iter()
+            .chain(second.iter())
+            .map(|e| e.id.to_string())
+            .collect();
+        assert_eq!(page_ids, ids);
+
+        let last = second.last().unwrap();
+        assert!(!store.has_events_before((last.timestamp, last.id)).unwrap());
+    }
+
+    #[test]
+    fn test_get_events_above_importance() {
+        let store = MemoryStore::in_memory().unwrap();
+        for importance in [0.1, 0.5, 0.9] {
+            let event = Event::new("test".to_string(), format!("{}", importance), importance);
+            store.store_event(&event).unwrap();
+        }
+
+        let events = store.get_events_above_importance(0.5, 10).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.importance >= 0.5));
+
+        // Exactly at the threshold is included
+        let events = store.get_events_above_importance(0.9, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].importance, 0.9);
+
+        assert!(store.get_events_above_importance(0.95, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_store_events_batch() {
+        let store = MemoryStore::in_memory().unwrap();
+        let events: Vec<Event> = (0..1000)
+            .map(|i| Event::new("sensor".to_string(), format!("reading {}", i), 0.2))
+            .collect();
+
+        store.store_events(&events).unwrap();
+        assert_eq!(store.event_count().unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_store_events_rolls_back_on_failure() {
+        let store = MemoryStore::in_memory().unwrap();
+        let first = Event::new("sensor".to_string(), "first".to_string(), 0.2);
+        let second = Event::new("sensor".to_string(), "second".to_string(), 0.2);
+
+        // Duplicate primary key fails mid-batch
+        let events = vec![first.clone(), second, first];
+        assert!(store.store_events(&events).is_err());
+        assert_eq!(store.event_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_get_events_between() {
+        let store = MemoryStore::in_memory().unwrap();
+        let midnight = DateTime::parse_from_rfc3339("2024-03-02T00:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        for (offset, name) in [(-60, "before"), (0, "midnight"), (60, "after")] {
+            let mut event = Event::new("test".to_string(), name.to_string(), 0.5);
+            event.timestamp = midnight + chrono::Duration::minutes(offset);
+            store.store_event(&event).unwrap();
+        }
+
+        // Previous day excludes the event exactly at midnight
+        let day = store
+            .get_events_between(midnight - chrono::Duration::days(1), midnight, 10)
+            .unwrap();
+        assert_eq!(day.len(), 1);
+        assert_eq!(day[0].description, "before");
+
+        // Next day starts inclusively at midnight, in chronological order
+        let day = store
+            .get_events_between(midnight, midnight + chrono::Duration::days(1), 10)
+            .unwrap();
+        assert_eq!(day.len(), 2);
+        assert_eq!(day[0].description, "midnight");
+        assert_eq!(day[1].description, "after");
+    }
+
+    #[test]
+    fn test_get_events_between_invalid_range() {
+        let store = MemoryStore::in_memory().unwrap();
+        let now = Utc::now();
+
+        let result = store.get_events_between(now, now - chrono::Duration::hours(1), 10);
+        assert!(matches!(result, Err(AgentError::Other(_))));
+    }
+
+    #[test]
+    fn test_get_habit_completions() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Morning exercise".to_string(),
+            "Exercise every morning".to_string(),
+            HabitFrequency::Daily,
+        );
+        store.store_habit(&habit).unwrap();
+        assert!(store.get_habit_completions(habit.id).unwrap().is_empty());
+
+        for _ in 0..3 {
+            store.complete_habit(habit.id).unwrap();
+        }
+
+        let completions = store.get_habit_completions(habit.id).unwrap();
+        assert_eq!(completions.len(), 3);
+        assert!(completions.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_delete_habit() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Morning exercise".to_string(),
+            "Exercise every morning".to_string(),
+            HabitFrequency::Daily,
+        );
+        store.store_habit(&habit).unwrap();
+        store.complete_habit(habit.id).unwrap();
+        store.complete_habit(habit.id).unwrap();
+
+        store.delete_habit(habit.id).unwrap();
+        assert_eq!(store.habit_count().unwrap(), 0);
+        assert!(store.get_habit_completions(habit.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_delete_missing_habit() {
+        let store = MemoryStore::in_memory().unwrap();
+        let result = store.delete_habit(Uuid::new_v4());
+        assert!(matches!(result, Err(AgentError::Memory(_))));
+    }
+
+    #[test]
+    fn test_get_event() {
+        let store = MemoryStore::in_memory().unwrap();
+        let event = Event::new("test".to_string(), "test event".to_string(), 0.5)
+            .with_metadata("source".to_string(), serde_json::json!("sensor"));
+        store.store_event(&event).unwrap();
+
+        let found = store.get_event(event.id).unwrap().unwrap();
+        assert_eq!(found.id, event.id);
+        assert_eq!(found.description, "test event");
+        assert_eq!(found.metadata, event.metadata);
+
+        assert!(store.get_event(Uuid::new_v4()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_malformed_rows_return_error() {
+        let store = MemoryStore::in_memory().unwrap();
+        store
+            .conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO events (id, event_type, description, importance, metadata, timestamp)
+                 VALUES (?1, 'test', 'corrupted', 0.5, '{}', 'not-a-timestamp')",
+                [Uuid::new_v4().to_string()],
+            )
+            .unwrap();
+        store
+            .conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO habits (id, name, description, frequency, completion_count, created_at)
+                 VALUES ('not-a-uuid', 'Broken', 'corrupted', 'daily', 0, ?1)",
+                [Utc::now().to_rfc3339()],
+            )
+            .unwrap();
+
+        assert!(matches!(
+            store.get_recent_events(10),
+            Err(AgentError::Database(_))
+        ));
+        assert!(matches!(
+            store.get_active_habits(),
+            Err(AgentError::Database(_))
+        ));
+    }
+
+    #[test]
+    fn test_file_store_uses_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path().join("memory.db")).unwrap();
+
+        let mode: String = store
+            .conn()
+            .unwrap()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+
+        let timeout: i64 = store
+            .conn()
+            .unwrap()
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(timeout, 5000);
+    }
+
+    #[test]
+    fn test_with_options_without_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = MemoryStoreOptions {
+            wal: false,
+            busy_timeout: Duration::from_millis(100),
+        };
+        let store = MemoryStore::with_options(dir.path().join("memory.db"), options).unwrap();
+
+        let mode: String = store
+            .conn()
+            .unwrap()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "delete");
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let store = std::sync::Arc::new(MemoryStore::in_memory().unwrap());
+
+        let handles: Vec<_> = (0..2)
+            .map(|t| {
+                let store = std::sync::Arc::clone(&store);
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        let event =
+                            Event::new("thread".to_string(), format!("{}-{}", t, i), 0.5);
+                        store.store_event(&event).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(store.event_count().unwrap(), 100);
+    }
+
+    #[test]
+    fn test_concurrent_merges_in_both_directions() {
+        let a = std::sync::Arc::new(MemoryStore::in_memory().unwrap());
+        let b = std::sync::Arc::new(MemoryStore::in_memory().unwrap());
+        for i in 0..20 {
+            a.store_event(&Event::new("a".to_string(), format!("a-{}", i), 0.5)).unwrap();
+            b.store_event(&Event::new("b".to_string(), format!("b-{}", i), 0.5)).unwrap();
+        }
+
+        let handles: Vec<_> = [(a.clone(), b.clone()), (b.clone(), a.clone())]
+            .into_iter()
+            .map(|(into, from)| {
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        into.merge_from(&from).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(a.event_count().unwrap(), 40);
+        assert_eq!(b.event_count().unwrap(), 40);
+    }
+
+    fn store_aged_events(store: &MemoryStore, ages_in_days: &[i64]) {
+        for days in ages_in_days {
+            let mut event = Event::new("test".to_string(), format!("{} days", days), 0.5);
+            event.timestamp = Utc::now() - chrono::Duration::days(*days);
+            store.store_event(&event).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_apply_retention_max_age() {
+        let store = MemoryStore::in_memory().unwrap();
+        store_aged_events(&store, &[1, 5, 40, 90]);
+
+        let policy = RetentionPolicy {
+            max_age: Some(chrono::Duration::days(30)),
+            max_events: None,
+        };
+        assert_eq!(store.apply_retention(&policy).unwrap(), 2);
+        assert_eq!(store.event_count().unwrap(), 2);
+
+        // Expired events are kept as tombstones until purged
+        assert_eq!(store.include_deleted().event_count().unwrap(), 4);
+        assert_eq!(store.purge_soft_deleted(chrono::Duration::zero()).unwrap(), 2);
+        assert_eq!(store.include_deleted().event_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_apply_retention_max_events() {
+        let store = MemoryStore::in_memory().unwrap();
+        store_aged_events(&store, &[1, 2, 3, 4, 5]);
+
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_events: Some(3),
+        };
+        assert_eq!(store.apply_retention(&policy).unwrap(), 2);
+
+        let remaining = store.get_recent_events(10).unwrap();
+        assert_eq!(remaining.len(), 3);
+        assert_eq!(remaining[2].description, "3 days");
+    }
+
+    #[test]
+    fn test_apply_retention_combined() {
+        let store = MemoryStore::in_memory().unwrap();
+        store_aged_events(&store, &[1, 2, 3, 40, 90]);
+
+        let policy = RetentionPolicy {
+            max_age: Some(chrono::Duration::days(30)),
+            max_events: Some(2),
+        };
+        assert_eq!(store.apply_retention(&policy).unwrap(), 3);
+        assert_eq!(store.event_count().unwrap(), 2);
+
+        // Nothing left to delete on a second pass
+        assert_eq!(store.apply_retention(&policy).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_apply_retention_max_events_counts_live_rows() {
+        let store = MemoryStore::in_memory().unwrap();
+        store_aged_events(&store, &[1, 2, 3, 4]);
+        let newest = store.get_recent_events(1).unwrap().remove(0);
+        store.soft_delete_event(newest.id).unwrap();
+
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_events: Some(2),
+        };
+        assert_eq!(store.apply_retention(&policy).unwrap(), 1);
+
+        let remaining = store.get_recent_events(10).unwrap();
+        let descriptions: Vec<&str> = remaining.iter().map(|e| e.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["2 days", "3 days"]);
+    }
+
+    #[test]
+    fn test_clear_events_before_soft_deletes() {
+        let store = MemoryStore::in_memory().unwrap();
+        store_aged_events(&store, &[1, 10, 20]);
+
+        let cutoff = Utc::now() - chrono::Duration::days(5);
+        assert_eq!(store.clear_events_before(cutoff).unwrap(), 2);
+        assert_eq!(store.event_count().unwrap(), 1);
+        assert_eq!(store.clear_events_before(cutoff).unwrap(), 0);
+
+        let deleted = store.include_deleted().get_recent_events(10).unwrap();
+        assert_eq!(deleted.len(), 3);
+        assert_eq!(deleted.iter().filter(|e| e.deleted_at.is_some()).count(), 2);
+    }
+
+    #[test]
+    fn test_migrate_from_unversioned_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+
+        // v0 database written before `variance` existed
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE habits (
+                    id TEXT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    description TEXT NOT NULL,
+                    frequency TEXT NOT NULL,
+                    schedule TEXT,
+                    completion_count INTEGER NOT NULL,
+                    last_completed TEXT,
+                    created_at TEXT NOT NULL
+                );",
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO habits (id, name, description, frequency, completion_count, created_at)
+                 VALUES (?1, 'Reading', 'Read a chapter', 'daily', 4, ?2)",
+                params![Uuid::new_v4().to_string(), Utc::now().to_rfc3339()],
+            )
+            .unwrap();
+        }
+
+        let store = MemoryStore::new(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+
+        let has_variance: bool = store
+            .conn()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('habits') WHERE name = 'variance'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(has_variance);
+
+        let habits = store.get_active_habits().unwrap();
         assert_eq!(habits.len(), 1);
-        assert_eq!(habits[0].name, "Morning exercise");
+        assert_eq!(habits[0].name, "Reading");
+        assert_eq!(habits[0].completion_count, 4);
+        assert!(habits[0].variance.is_none());
     }
 
     #[test]
-    fn test_complete_habit() {
+    fn test_migrate_legacy_schema_with_variance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+
+        // Unversioned database that already has every column
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.execute_batch(MIGRATIONS[1]).unwrap();
+        }
+
+        let store = MemoryStore::new(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+
+        // Reopening applies nothing new
+        drop(store);
+        let store = MemoryStore::new(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
         let store = MemoryStore::in_memory().unwrap();
-        let mut habit = Habit::new(
+        let event = Event::new("note".to_string(), "exported".to_string(), 0.8)
+            .with_metadata("source".to_string(), serde_json::json!("laptop"));
+        store.store_event(&event).unwrap();
+        store
+            .store_event(&Event::new("note".to_string(), "second".to_string(), 0.3))
+            .unwrap();
+
+        let habit = Habit::new(
             "Morning exercise".to_string(),
             "Exercise every morning".to_string(),
             HabitFrequency::Daily,
         );
-
         store.store_habit(&habit).unwrap();
         store.complete_habit(habit.id).unwrap();
+        store.complete_habit(habit.id).unwrap();
+
+        let json = store.export_json().unwrap();
+
+        // Replace wipes the existing data first
+        let other = MemoryStore::in_memory().unwrap();
+        other
+            .store_event(&Event::new("stale".to_string(), "stale".to_string(), 0.1))
+            .unwrap();
+        let stats = other.import_json(&json, ImportMode::Replace).unwrap();
+        assert_eq!(stats.events_imported, 2);
+        assert_eq!(stats.habits_imported, 1);
+        assert_eq!(stats.completions_imported, 2);
+        assert_eq!(other.event_count().unwrap(), 2);
+
+        let imported = other.get_event(event.id).unwrap().unwrap();
+        assert_eq!(imported.description, "exported");
+        assert_eq!(imported.timestamp, event.timestamp);
+        assert_eq!(imported.metadata, event.metadata);
+
+        let habits = other.get_active_habits().unwrap();
+        assert_eq!(habits[0].id, habit.id);
+        assert_eq!(habits[0].completion_count, 2);
+        assert_eq!(other.get_habit_completions(habit.id).unwrap().len(), 2);
+
+        // Merge skips everything already present
+        let stats = other.import_json(&json, ImportMode::Merge).unwrap();
+        assert_eq!(stats.events_imported, 0);
+        assert_eq!(stats.skipped, 5);
+        assert_eq!(other.event_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_import_rejects_newer_version() {
+        let store = MemoryStore::in_memory().unwrap();
+        let data = r#"{"version": 99, "events": [], "habits": [], "completions": []}"#;
+
+        let result = store.import_json(data, ImportMode::Merge);
+        assert!(matches!(result, Err(AgentError::Memory(_))));
+    }
+
+    #[test]
+    fn test_event_stats() {
+        let store = MemoryStore::in_memory().unwrap();
+        assert!(store.event_stats().unwrap().is_empty());
+
+        let latest = Utc::now();
+        for (i, importance) in [0.2, 0.4, 0.6].iter().enumerate() {
+            let mut event = Event::new("user_input".to_string(), "input".to_string(), *importance);
+            event.timestamp = latest - chrono::Duration::minutes(i as i64);
+            store.store_event(&event).unwrap();
+        }
+        store
+            .store_event(&Event::new("agent_response".to_string(), "reply".to_string(), 0.5))
+            .unwrap();
+
+        let stats = store.event_stats().unwrap();
+        assert_eq!(stats.len(), 2);
+
+        assert_eq!(stats[0].event_type, "user_input");
+        assert_eq!(stats[0].count, 3);
+        assert!((stats[0].avg_importance - 0.4).abs() < 1e-6);
+        assert_eq!(stats[0].last_seen, latest);
+
+        assert_eq!(stats[1].event_type, "agent_response");
+        assert_eq!(stats[1].count, 1);
+        assert!((stats[1].avg_importance - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_get_events_by_tag() {
+        let store = MemoryStore::in_memory().unwrap();
+        let checkup = Event::new("appointment".to_string(), "checkup".to_string(), 0.7)
+            .with_tags(vec!["health".to_string(), "finance".to_string()]);
+        let run = Event::new("activity".to_string(), "run".to_string(), 0.4)
+            .with_tags(vec!["health".to_string()]);
+        let untagged = Event::new("note".to_string(), "untagged".to_string(), 0.2);
+        store.store_events(&[checkup.clone(), run, untagged.clone()]).unwrap();
+
+        assert_eq!(store.get_events_by_tag("health", 10).unwrap().len(), 2);
+
+        let finance = store.get_events_by_tag("finance", 10).unwrap();
+        assert_eq!(finance.len(), 1);
+        assert_eq!(finance[0].id, checkup.id);
+        assert_eq!(finance[0].tags, vec!["health", "finance"]);
+
+        // Partial matches and unknown tags miss
+        assert!(store.get_events_by_tag("heal", 10).unwrap().is_empty());
+        assert!(store.get_events_by_tag("travel", 10).unwrap().is_empty());
+
+        let stored = store.get_event(untagged.id).unwrap().unwrap();
+        assert!(stored.tags.is_empty());
+    }
+
+    #[test]
+    fn test_complete_habit_at_backfill() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Running".to_string(),
+            "Evening run".to_string(),
+            HabitFrequency::Daily,
+        );
+        store.store_habit(&habit).unwrap();
+
+        let now = Utc::now();
+        let latest = now - chrono::Duration::days(1);
+        let earlier = now - chrono::Duration::days(3);
+
+        // Insert the newer completion first, then backfill an older one
+        store.complete_habit_at(habit.id, latest).unwrap();
+        store.complete_habit_at(habit.id, earlier).unwrap();
+
+        let stored = &store.get_active_habits().unwrap()[0];
+        assert_eq!(stored.completion_count, 2);
+        assert_eq!(stored.last_completed, Some(latest));
+
+        let completions = store.get_habit_completions(habit.id).unwrap();
+        assert_eq!(completions, vec![earlier, latest]);
+    }
+
+    #[test]
+    fn test_update_event() {
+        let store = MemoryStore::in_memory().unwrap();
+        let mut event = Event::new("note".to_string(), "Buy mlik".to_string(), 0.3);
+        store.store_event(&event).unwrap();
+
+        event.description = "Buy milk".to_string();
+        event.importance = 0.7;
+        store.update_event(&event).unwrap();
+
+        let stored = store.get_event(event.id).unwrap().unwrap();
+        assert_eq!(stored.id, event.id);
+        assert_eq!(stored.description, "Buy milk");
+        assert!((stored.importance - 0.7).abs() < f32::EPSILON);
+        assert_eq!(stored.timestamp, event.timestamp);
+        assert_eq!(store.event_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_update_missing_event() {
+        let store = MemoryStore::in_memory().unwrap();
+        let event = Event::new("note".to_string(), "Never stored".to_string(), 0.5);
+
+        let result = store.update_event(&event);
+        assert!(matches!(result, Err(AgentError::Memory(_))));
+    }
+
+    #[test]
+    fn test_for_user_isolates_queries() {
+        let store = MemoryStore::in_memory().unwrap();
+        let alice = store.for_user("alice");
+        let bob = store.for_user("bob");
+
+        alice
+            .store_event(&Event::new("note".to_string(), "Alice note".to_string(), 0.5))
+            .unwrap();
+        bob.store_event(&Event::new("note".to_string(), "Bob note".to_string(), 0.5))
+            .unwrap();
+        bob.store_event(&Event::new("meal".to_string(), "Bob lunch".to_string(), 0.5))
+            .unwrap();
+        bob.store_habit(&Habit::new(
+            "Gym".to_string(),
+            "Lift weights".to_string(),
+            HabitFrequency::Weekly,
+        ))
+        .unwrap();
+
+        let alice_events = alice.get_recent_events(10).unwrap();
+        assert_eq!(alice_events.len(), 1);
+        assert_eq!(alice_events[0].description, "Alice note");
+        assert_eq!(alice_events[0].user_id, "alice");
+
+        assert_eq!(bob.event_count().unwrap(), 2);
+        assert_eq!(bob.get_events_by_type("note", 10).unwrap()[0].description, "Bob note");
+        assert!(alice.get_events_by_type("meal", 10).unwrap().is_empty());
+
+        assert!(alice.get_active_habits().unwrap().is_empty());
+        assert_eq!(bob.get_active_habits().unwrap()[0].user_id, "bob");
+
+        // Unscoped queries still see everything
+        assert_eq!(store.event_count().unwrap(), 3);
+        assert!(store.for_user(DEFAULT_USER_ID).get_recent_events(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_for_user_isolates_filtered_queries() {
+        let store = MemoryStore::in_memory().unwrap();
+        let alice = store.for_user("alice");
+        let bob = store.for_user("bob");
+
+        let tagged = |description: &str, importance: f32| {
+            Event::new("note".to_string(), description.to_string(), importance)
+                .with_tags(vec!["home".to_string()])
+                .with_metadata("room".to_string(), serde_json::json!("kitchen"))
+        };
+        alice.store_event(&tagged("Alice note", 0.9)).unwrap();
+        bob.store_event(&tagged("Bob note", 0.9)).unwrap();
+        bob.store_event(&tagged("Bob reminder", 0.9)).unwrap();
+
+        let descriptions = |events: Vec<Event>| -> Vec<String> {
+            events.into_iter().map(|e| e.description).collect()
+        };
+        let room = serde_json::json!("kitchen");
+        let start = Utc::now() - chrono::Duration::hours(1);
+        let end = Utc::now() + chrono::Duration::hours(1);
+
+        assert_eq!(descriptions(alice.get_events_by_tag("home", 10).unwrap()), ["Alice note"]);
+        assert_eq!(
+            descriptions(alice.get_events_by_metadata("room", &room, 10).unwrap()),
+            ["Alice note"]
+        );
+        assert_eq!(
+            descriptions(alice.get_events_above_importance(0.8, 10).unwrap()),
+            ["Alice note"]
+        );
+        assert_eq!(
+            descriptions(alice.get_events_between(start, end, 10).unwrap()),
+            ["Alice note"]
+        );
+        assert_eq!(bob.get_events_by_tag("home", 10).unwrap().len(), 2);
+
+        let page = alice.get_events_page(None, 10).unwrap();
+        assert_eq!(descriptions(page.clone()), ["Alice note"]);
+        assert!(!alice.has_events_before((page[0].timestamp, page[0].id)).unwrap());
+        let first = bob.get_events_page(None, 1).unwrap();
+        assert!(bob.has_events_before((first[0].timestamp, first[0].id)).unwrap());
+
+        assert_eq!(alice.event_stats().unwrap()[0].count, 1);
+        assert_eq!(bob.event_stats().unwrap()[0].count, 2);
+        assert_eq!(alice.importance_histogram(2).unwrap()[1].2, 1);
+        assert_eq!(store.importance_histogram(2).unwrap()[1].2, 3);
+    }
+
+    #[test]
+    fn test_legacy_rows_belong_to_default_user() {
+        let store = MemoryStore::in_memory().unwrap();
+        store
+            .conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO events (id, event_type, description, importance, metadata, timestamp)
+                 VALUES (?1, 'note', 'legacy', 0.5, '{}', ?2)",
+                params![Uuid::new_v4().to_string(), Utc::now().to_rfc3339()],
+            )
+            .unwrap();
+
+        let events = store.for_user(DEFAULT_USER_ID).get_recent_events(10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].user_id, DEFAULT_USER_ID);
+    }
+
+    #[test]
+    fn test_dedup_events() {
+        let store = MemoryStore::in_memory().unwrap();
+        let base = Utc::now() - chrono::Duration::hours(1);
+
+        let mut first_id = None;
+        for i in 0..5 {
+            let mut event = Event::new("sensor".to_string(), "Door opened".to_string(), 0.3);
+            event.timestamp = base + chrono::Duration::seconds(i * 10);
+            first_id.get_or_insert(event.id);
+            store.store_event(&event).unwrap();
+        }
+
+        // Outside the window and a different description: both kept
+        let mut later = Event::new("sensor".to_string(), "Door opened".to_string(), 0.3);
+        later.timestamp = base + chrono::Duration::minutes(10);
+        store.store_event(&later).unwrap();
+        let mut other = Event::new("sensor".to_string(), "Door closed".to_string(), 0.3);
+        other.timestamp = base + chrono::Duration::minutes(11);
+        store.store_event(&other).unwrap();
+
+        let merged = store.dedup_events(chrono::Duration::minutes(1)).unwrap();
+        assert_eq!(merged, 4);
+        assert_eq!(store.event_count().unwrap(), 3);
+
+        let kept = store.get_event(first_id.unwrap()).unwrap().unwrap();
+        assert_eq!(kept.metadata.get("count"), Some(&serde_json::json!(5)));
+        assert_eq!(kept.timestamp, base);
+
+        // Running again is a no-op
+        assert_eq!(store.dedup_events(chrono::Duration::minutes(1)).unwrap(), 0);
+
+        // Folded duplicates are tombstoned, not removed
+        assert_eq!(store.include_deleted().event_count().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_include_deleted_filtered_recall() {
+        let store = MemoryStore::in_memory().unwrap();
+        let forgotten = Event::new("note".to_string(), "Forgotten".to_string(), 0.9)
+            .with_tags(vec!["home".to_string()])
+            .with_metadata("room".to_string(), serde_json::json!("kitchen"));
+        store.store_event(&forgotten).unwrap();
+        store.soft_delete_event(forgotten.id).unwrap();
+
+        let room = serde_json::json!("kitchen");
+        let start = Utc::now() - chrono::Duration::hours(1);
+        let end = Utc::now() + chrono::Duration::hours(1);
+        assert!(store.get_events_by_tag("home", 10).unwrap().is_empty());
+        assert!(store.get_events_by_metadata("room", &room, 10).unwrap().is_empty());
+        assert!(store.get_events_above_importance(0.5, 10).unwrap().is_empty());
+        assert!(store.get_events_between(start, end, 10).unwrap().is_empty());
+        assert!(store.get_events_page(None, 10).unwrap().is_empty());
+
+        let deleted = store.include_deleted();
+        assert_eq!(deleted.get_events_by_tag("home", 10).unwrap()[0].id, forgotten.id);
+        assert_eq!(deleted.get_events_by_metadata("room", &room, 10).unwrap().len(), 1);
+        assert_eq!(deleted.get_events_above_importance(0.5, 10).unwrap().len(), 1);
+        assert_eq!(deleted.get_events_between(start, end, 10).unwrap().len(), 1);
+        let page = deleted.get_events_page(None, 10).unwrap();
+        assert!(page[0].deleted_at.is_some());
+        let cursor = (page[0].timestamp, Uuid::max());
+        assert!(deleted.has_events_before(cursor).unwrap());
+        assert!(!store.has_events_before(cursor).unwrap());
+    }
+
+    #[test]
+    fn test_complete_habit_returns_updated_habit() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Stretch".to_string(),
+            "Stretch after waking up".to_string(),
+            HabitFrequency::Daily,
+        );
+        store.store_habit(&habit).unwrap();
+
+        let first = store.complete_habit(habit.id).unwrap();
+        let second = store.complete_habit(habit.id).unwrap();
+
+        assert_eq!(first.id, habit.id);
+        assert_eq!(first.completion_count, 1);
+        assert_eq!(second.completion_count, 2);
+        assert!(second.last_completed.unwrap() >= first.last_completed.unwrap());
+    }
+
+    #[test]
+    fn test_complete_missing_habit() {
+        let store = MemoryStore::in_memory().unwrap();
+
+        let missing = Uuid::new_v4();
+
+        assert!(matches!(store.complete_habit(missing), Err(AgentError::Memory(_))));
+        assert!(store.get_habit_completions(missing).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_events_by_metadata() {
+        let store = MemoryStore::in_memory().unwrap();
+        let meeting = Event::new("appointment".to_string(), "Team sync".to_string(), 0.6)
+            .with_metadata("source".to_string(), serde_json::json!("calendar"))
+            .with_metadata("attendees".to_string(), serde_json::json!(5))
+            .with_metadata("room".to_string(), serde_json::json!({"floor": 2, "name": "Oak"}));
+        let email = Event::new("message".to_string(), "Invoice".to_string(), 0.4)
+            .with_metadata("source".to_string(), serde_json::json!("email"))
+            .with_metadata("attendees".to_string(), serde_json::json!("5"));
+        let plain = Event::new("note".to_string(), "No metadata".to_string(), 0.2);
+        store.store_events(&[meeting.clone(), email.clone(), plain]).unwrap();
+
+        let calendar = store
+            .get_events_by_metadata("source", &serde_json::json!("calendar"), 10)
+            .unwrap();
+        assert_eq!(calendar.len(), 1);
+        assert_eq!(calendar[0].id, meeting.id);
+
+        // Type matters for scalars
+        let numeric = store.get_events_by_metadata("attendees", &serde_json::json!(5), 10).unwrap();
+        assert_eq!(numeric.iter().map(|e| e.id).collect::<Vec<_>>(), vec![meeting.id]);
+        let text = store.get_events_by_metadata("attendees", &serde_json::json!("5"), 10).unwrap();
+        assert_eq!(text.iter().map(|e| e.id).collect::<Vec<_>>(), vec![email.id]);
+
+        let room = store
+            .get_events_by_metadata("room", &serde_json::json!({"name": "Oak", "floor": 2}), 10)
+            .unwrap();
+        assert_eq!(room.len(), 1);
+
+        assert!(store
+            .get_events_by_metadata("source", &serde_json::json!("sms"), 10)
+            .unwrap()
+            .is_empty());
+        assert!(store
+            .get_events_by_metadata("missing", &serde_json::Value::Null, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_import_habit_with_history() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Journal".to_string(),
+            "Write a few lines".to_string(),
+            HabitFrequency::Daily,
+        );
+        let start = Utc::now() - chrono::Duration::days(40);
+        // Out of order, roughly daily with some jitter
+        let completions: Vec<DateTime<Utc>> = (0..30)
+            .rev()
+            .map(|day| start + chrono::Duration::days(day) + chrono::Duration::minutes(day * 7 % 45))
+            .collect();
+
+        store.import_habit(&habit, &completions).unwrap();
+
+        let stored = &store.get_active_habits().unwrap()[0];
+        assert_eq!(stored.completion_count, 30);
+        assert_eq!(stored.last_completed, completions.iter().max().copied());
+
+        let history = store.get_habit_completions(habit.id).unwrap();
+        assert_eq!(history.len(), 30);
+        assert!(history.windows(2).all(|w| w[0] <= w[1]));
+        assert!(crate::habit::HabitAnalyzer::new().calculate_variance(&history).is_some());
+
+        // Re-importing replaces rather than duplicates history
+        store.import_habit(&habit, &completions[..10]).unwrap();
+        assert_eq!(store.get_habit_completions(habit.id).unwrap().len(), 10);
+        assert_eq!(store.get_active_habits().unwrap()[0].completion_count, 10);
+    }
+
+    #[test]
+    fn test_recompute_habit_variance() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Reading".to_string(),
+            "Read before bed".to_string(),
+            HabitFrequency::Daily,
+        );
+        store.store_habit(&habit).unwrap();
+
+        let now = Utc::now();
+        store.complete_habit_at(habit.id, now - chrono::Duration::hours(60)).unwrap();
+        assert_eq!(store.recompute_habit_variance(habit.id).unwrap(), None);
+
+        store.complete_habit_at(habit.id, now - chrono::Duration::hours(48)).unwrap();
+        store.complete_habit_at(habit.id, now - chrono::Duration::hours(20)).unwrap();
+        // Not recomputed on completion
+        assert!(store.get_active_habits().unwrap()[0].variance.is_none());
+
+        let variance = store.recompute_habit_variance(habit.id).unwrap();
+        assert!(variance.is_some_and(|v| v > 0.0));
+        assert_eq!(store.get_active_habits().unwrap()[0].variance, variance);
+
+        assert!(matches!(
+            store.recompute_habit_variance(Uuid::new_v4()),
+            Err(AgentError::Memory(_))
+        ));
+    }
+
+    #[test]
+    fn test_clear_events_by_source() {
+        let store = MemoryStore::in_memory().unwrap();
+        store
+            .store_events(&[
+                Event::new("meeting".to_string(), "Standup".to_string(), 0.6)
+                    .with_source("calendar".to_string()),
+                Event::new("meeting".to_string(), "Review".to_string(), 0.6)
+                    .with_source("calendar".to_string()),
+                Event::new("email".to_string(), "Invoice".to_string(), 0.4)
+                    .with_source("email".to_string()),
+                Event::new("note".to_string(), "Typed by hand".to_string(), 0.3),
+            ])
+            .unwrap();
+
+        assert_eq!(store.clear_events_by_source("calendar").unwrap(), 2);
+        assert_eq!(store.clear_events_by_source("calendar").unwrap(), 0);
+
+        let remaining = store.get_recent_events(10).unwrap();
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining
+            .iter()
+            .any(|e| e.source.as_deref() == Some("email") && e.description == "Invoice"));
+        assert!(remaining.iter().any(|e| e.source.is_none()));
+
+        // Cleared events wait for a purge like any other soft delete
+        assert_eq!(store.include_deleted().event_count().unwrap(), 4);
+        assert_eq!(store.purge_soft_deleted(chrono::Duration::zero()).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_importance_histogram() {
+        let store = MemoryStore::in_memory().unwrap();
+        let events: Vec<Event> = [0.0, 0.1, 0.2, 0.26, 0.6, 0.74, 0.9, 1.0]
+            .into_iter()
+            .map(|importance| Event::new("note".to_string(), "Note".to_string(), importance))
+            .collect();
+        store.store_events(&events).unwrap();
+
+        let histogram = store.importance_histogram(4).unwrap();
+        let counts: Vec<u64> = histogram.iter().map(|(_, _, count)| *count).collect();
+        assert_eq!(counts, vec![3, 1, 2, 2]);
+        assert_eq!((histogram[1].0, histogram[1].1), (0.25, 0.5));
+        assert_eq!(histogram[3].1, 1.0);
+
+        assert_eq!(
+            MemoryStore::in_memory().unwrap().importance_histogram(2).unwrap(),
+            vec![(0.0, 0.5, 0), (0.5, 1.0, 0)]
+        );
+        assert!(store.importance_histogram(0).is_err());
+    }
+
+    #[test]
+    fn test_importance_histogram_exact_boundaries() {
+        let store = MemoryStore::in_memory().unwrap();
+        let events: Vec<Event> = [0.3, 0.7, 0.9]
+            .into_iter()
+            .map(|importance| Event::new("note".to_string(), "Note".to_string(), importance))
+            .collect();
+        store.store_events(&events).unwrap();
+
+        let histogram = store.importance_histogram(10).unwrap();
+        assert_eq!(histogram[3].2, 1);
+        assert_eq!(histogram[7].2, 1);
+        assert_eq!(histogram[9].2, 1);
+        assert_eq!(histogram[6].2 + histogram[8].2 + histogram[2].2, 0);
+    }
+
+    #[test]
+    fn test_soft_deleted_events_hidden() {
+        let store = MemoryStore::in_memory().unwrap();
+        let kept = Event::new("note".to_string(), "Kept".to_string(), 0.9);
+        let forgotten = Event::new("note".to_string(), "Forgotten".to_string(), 0.9);
+        store.store_events(&[kept.clone(), forgotten.clone()]).unwrap();
+
+        store.soft_delete_event(forgotten.id).unwrap();
+
+        let recent = store.get_recent_events(10).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, kept.id);
+        assert!(store.get_event(forgotten.id).unwrap().is_none());
+        assert_eq!(store.get_events_by_type("note", 10).unwrap().len(), 1);
+        assert_eq!(store.get_events_above_importance(0.5, 10).unwrap().len(), 1);
+        assert_eq!(store.event_count().unwrap(), 1);
+
+        let all = store.include_deleted();
+        assert_eq!(all.event_count().unwrap(), 2);
+        assert_eq!(all.get_recent_events(10).unwrap().len(), 2);
+        let tombstone = all.get_event(forgotten.id).unwrap().unwrap();
+        assert!(tombstone.deleted_at.is_some());
+
+        assert!(matches!(
+            store.soft_delete_event(Uuid::new_v4()),
+            Err(AgentError::Memory(_))
+        ));
+    }
+
+    #[test]
+    fn test_purge_soft_deleted() {
+        let store = MemoryStore::in_memory().unwrap();
+        let event = Event::new("note".to_string(), "Forgotten".to_string(), 0.5);
+        store.store_event(&event).unwrap();
+        store.store_event(&Event::new("note".to_string(), "Kept".to_string(), 0.5)).unwrap();
+        store.soft_delete_event(event.id).unwrap();
+
+        // Still inside the grace window
+        assert_eq!(store.purge_soft_deleted(chrono::Duration::hours(1)).unwrap(), 0);
+        assert!(store.include_deleted().get_event(event.id).unwrap().is_some());
+
+        assert_eq!(store.purge_soft_deleted(chrono::Duration::zero()).unwrap(), 1);
+        assert!(store.include_deleted().get_event(event.id).unwrap().is_none());
+        assert_eq!(store.include_deleted().event_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_reads_space_separated_timestamps() {
+        let store = MemoryStore::in_memory().unwrap();
+        {
+            let conn = store.conn().unwrap();
+            conn.execute(
+                "INSERT INTO events (id, event_type, description, importance, metadata, timestamp)
+                 VALUES (?1, 'note', 'from sql', 0.5, '{}', '2024-01-01 12:00:00')",
+                [Uuid::new_v4().to_string()],
+            )
+            .unwrap();
+        }
+
+        let events = store.get_recent_events(10).unwrap();
+        assert_eq!(events[0].timestamp.to_rfc3339(), "2024-01-01T12:00:00+00:00");
+    }
+
+    #[test]
+    fn test_complete_habit_rolls_back_on_failure() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Stretch".to_string(),
+            "Stretch after waking".to_string(),
+            HabitFrequency::Daily,
+        );
+        store.store_habit(&habit).unwrap();
+
+        // Break the completion insert so the update must be rolled back
+        store
+            .conn()
+            .unwrap()
+            .execute("DROP TABLE habit_completions", [])
+            .unwrap();
+        assert!(store.complete_habit(habit.id).is_err());
 
         let habits = store.get_active_habits().unwrap();
-        assert_eq!(habits[0].completion_count, 1);
-        assert!(habits[0].last_completed.is_some());
+        assert_eq!(habits[0].completion_count, 0);
+        assert!(habits[0].last_completed.is_none());
+    }
+
+    #[test]
+    fn test_migrate_partially_applied_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+
+        // Version 6 database whose events table already has `user_id`
+        {
+            let conn = Connection::open(&path).unwrap();
+            for migration in &MIGRATIONS[..6] {
+                conn.execute_batch(migration).unwrap();
+            }
+            conn.execute_batch("ALTER TABLE events ADD COLUMN user_id TEXT NOT NULL DEFAULT 'default';")
+                .unwrap();
+            conn.pragma_update(None, "user_version", 6).unwrap();
+        }
+
+        let store = MemoryStore::new(&path).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+
+        // The rest of migration 7 still ran
+        let conn = store.conn().unwrap();
+        let habit_user_column: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_table_info('habits') WHERE name = 'user_id')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let user_index: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'idx_events_user')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(habit_user_column);
+        assert!(user_index);
+    }
+
+    #[test]
+    fn test_merge_keeps_same_content_for_different_users() {
+        let local = MemoryStore::in_memory().unwrap();
+        let remote = MemoryStore::in_memory().unwrap();
+
+        let alice = Event::new("note".to_string(), "Took vitamins".to_string(), 0.5)
+            .with_user("alice".to_string());
+        let mut bob = alice.clone().with_user("bob".to_string());
+        bob.id = Uuid::new_v4();
+        local.store_event(&alice).unwrap();
+        remote.store_event(&bob).unwrap();
+
+        // Same id moved to another user is a conflict, not a duplicate
+        let mut shared = Event::new("note".to_string(), "shared".to_string(), 0.5);
+        local.store_event(&shared).unwrap();
+        shared.user_id = "bob".to_string();
+        remote.store_event(&shared).unwrap();
+
+        let report = local.merge_from(&remote).unwrap();
+        assert_eq!(report.events_added, 1);
+        assert_eq!(report.conflicts, vec![MergeConflict::Event(shared.id)]);
+        assert_eq!(local.for_user("bob").get_recent_events(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_dedup_events_leaves_unmerged_events_untouched() {
+        let store = MemoryStore::in_memory().unwrap();
+        let event = Event::new("note".to_string(), "single".to_string(), 0.5);
+        store.store_event(&event).unwrap();
+
+        // Metadata as written by another tool, not in serde_json's formatting
+        let raw = r#"{ "room" : "kitchen" }"#;
+        store
+            .conn()
+            .unwrap()
+            .execute(
+                "UPDATE events SET metadata = ?1 WHERE id = ?2",
+                params![raw, event.id.to_string()],
+            )
+            .unwrap();
+
+        assert_eq!(store.dedup_events(chrono::Duration::minutes(5)).unwrap(), 0);
+
+        let stored: String = store
+            .conn()
+            .unwrap()
+            .query_row(
+                "SELECT metadata FROM events WHERE id = ?1",
+                [event.id.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, raw);
+    }
+
+    #[test]
+    fn test_import_merge_updates_existing_habit() {
+        let source = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Meditate".to_string(),
+            "Ten minutes".to_string(),
+            HabitFrequency::Daily,
+        );
+        source.store_habit(&habit).unwrap();
+        let earlier = Utc::now() - chrono::Duration::days(2);
+        let later = Utc::now() - chrono::Duration::days(1);
+        source.complete_habit_at(habit.id, earlier).unwrap();
+        source.complete_habit_at(habit.id, later).unwrap();
+
+        // The local copy knows the habit but not its history
+        let local = MemoryStore::in_memory().unwrap();
+        local.store_habit(&habit).unwrap();
+
+        let mut export: serde_json::Value =
+            serde_json::from_str(&source.export_json().unwrap()).unwrap();
+        // A completion for a habit that exists nowhere
+        export["completions"].as_array_mut().unwrap().push(serde_json::json!({
+            "id": Uuid::new_v4(),
+            "habit_id": Uuid::new_v4(),
+            "completed_at": Utc::now(),
+        }));
+
+        let stats = local
+            .import_json(&export.to_string(), ImportMode::Merge)
+            .unwrap();
+        assert_eq!(stats.completions_imported, 2);
+        assert_eq!(stats.skipped, 2);
+
+        let merged = &local.get_active_habits().unwrap()[0];
+        assert_eq!(merged.completion_count, 2);
+        assert_eq!(merged.last_completed.unwrap().timestamp(), later.timestamp());
+        assert_eq!(local.get_habit_completions(habit.id).unwrap().len(), 2);
+
+        // No row was written for the unknown habit
+        let completion_rows: usize = local
+            .conn()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM habit_completions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(completion_rows, 2);
+    }
+
+    #[test]
+    fn test_corrupt_last_completed_is_an_error() {
+        let store = MemoryStore::in_memory().unwrap();
+        let habit = Habit::new(
+            "Journal".to_string(),
+            "Write a page".to_string(),
+            HabitFrequency::Daily,
+        );
+        store.store_habit(&habit).unwrap();
+        store
+            .conn()
+            .unwrap()
+            .execute(
+                "UPDATE habits SET last_completed = 'yesterday-ish' WHERE id = ?1",
+                [habit.id.to_string()],
+            )
+            .unwrap();
+
+        assert!(matches!(
+            store.get_active_habits(),
+            Err(AgentError::Database(rusqlite::Error::FromSqlConversionFailure(6, _, _)))
+        ));
     }
 }
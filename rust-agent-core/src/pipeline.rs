@@ -0,0 +1,131 @@
+//! Voice pipeline
+//!
+//! Wires wake word detection, speech-to-text and intent parsing together,
+//! optionally filtering the result through a policy engine. The pipeline
+//! only produces intents; executing them is still up to the caller.
+
+use crate::error::Result;
+use crate::intent::IntentGenerator;
+use crate::policy::PolicyEngine;
+use crate::types::Intent;
+use crate::voice::{encode_pcm16, SpeechToText, WakeWordDetector};
+use std::sync::Arc;
+
+/// Turns captured audio into intents
+pub struct VoicePipeline {
+    wake_word: Box<dyn WakeWordDetector>,
+    stt: Box<dyn SpeechToText>,
+    generator: IntentGenerator,
+    policy: Option<Arc<PolicyEngine>>,
+}
+
+impl VoicePipeline {
+    pub fn new(
+        wake_word: Box<dyn WakeWordDetector>,
+        stt: Box<dyn SpeechToText>,
+        generator: IntentGenerator,
+    ) -> Self {
+        Self {
+            wake_word,
+            stt,
+            generator,
+            policy: None,
+        }
+    }
+
+    /// Only return intents the policy engine permits
+    /// Permitted intents are charged against grants and rate limits as usual.
+    pub fn with_policy(mut self, policy: Arc<PolicyEngine>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Run one buffer of 16kHz mono audio through the pipeline
+    /// Returns no intents when the wake word is not detected. Otherwise the
+    /// whole buffer is transcribed and parsed; with a policy engine set,
+    /// intents it rejects are dropped.
+    pub fn process_audio(&self, audio: &[f32]) -> Result<Vec<Intent>> {
+        if !self.wake_word.detect(audio)? {
+            return Ok(Vec::new());
+        }
+
+        let transcription = self.stt.transcribe(&encode_pcm16(audio))?;
+        let intents = self.generator.parse_from_text(&transcription.text);
+
+        let Some(policy) = &self.policy else {
+            return Ok(intents);
+        };
+
+        Ok(intents
+            .into_iter()
+            .filter(|intent| match policy.check_intent(intent) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::debug!("Dropping intent '{}': {}", intent.intent_type, e);
+                    false
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intent::IntentPattern;
+    use crate::voice::{MockSpeechToText, MockWakeWordDetector, SimpleWakeWordDetector};
+
+    /// Generator that understands the mock transcription
+    fn generator() -> IntentGenerator {
+        let mut generator = IntentGenerator::new();
+        generator.add_pattern(
+            IntentPattern::new(r"(?i)mock transcription", "note.create", 0.8, "Heard a note").unwrap(),
+        );
+        generator.add_pattern(
+            IntentPattern::new(r"(?i)^this is", "device.control", 0.7, "Heard a command").unwrap(),
+        );
+        generator
+    }
+
+    fn pipeline() -> VoicePipeline {
+        VoicePipeline::new(
+            Box::new(SimpleWakeWordDetector::default()),
+            Box::new(MockSpeechToText::new()),
+            generator(),
+        )
+    }
+
+    fn loud() -> Vec<f32> {
+        (0..16_000).map(|i| 0.5 * (i as f32 * 0.3).sin()).collect()
+    }
+
+    #[test]
+    fn test_loud_audio_produces_intents() {
+        let intents = pipeline().process_audio(&loud()).unwrap();
+        let types: Vec<&str> = intents.iter().map(|i| i.intent_type.as_str()).collect();
+        assert!(types.contains(&"note.create"), "{:?}", types);
+        assert!(types.contains(&"device.control"), "{:?}", types);
+    }
+
+    #[test]
+    fn test_no_wake_word_no_intents() {
+        assert!(pipeline().process_audio(&vec![0.0; 16_000]).unwrap().is_empty());
+
+        let mock = VoicePipeline::new(
+            Box::new(MockWakeWordDetector::default()),
+            Box::new(MockSpeechToText::new()),
+            generator(),
+        );
+        assert!(mock.process_audio(&loud()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_policy_filters_intents() {
+        let pipeline = pipeline().with_policy(Arc::new(PolicyEngine::new(vec![])));
+
+        // Device control needs a grant that was never given
+        let intents = pipeline.process_audio(&loud()).unwrap();
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].intent_type, "note.create");
+    }
+}
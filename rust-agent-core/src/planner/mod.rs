@@ -3,74 +3,442 @@
 //! Reasoning and planning - prepares actions, doesn't execute them.
 //! Uses context and habits to make informed suggestions.
 
-use crate::error::Result;
-use crate::types::{Context, Event, Intent};
+use crate::error::{AgentError, Result};
+use crate::habit::HabitAnalyzer;
+use crate::intent::{extract_json_array, IntentGenerator};
+use crate::llm::{estimate_tokens, LlmProvider};
+use crate::types::{Context, Event, EvidenceRef, Habit, Intent, LlmOptions};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Token budget for the event lines in `summarize_events_llm` prompts
+const SUMMARY_EVENT_TOKENS: u32 = 1500;
+
+/// Minimum `HabitAnalyzer::is_habit_due` score that produces a reminder intent
+const HABIT_REMINDER_THRESHOLD: f32 = 0.9;
+
+/// A ranked suggestion for the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub text: String,
+    /// Higher is more pressing (0.0 - 1.0)
+    pub score: f32,
+    pub reason: String,
+    /// Identifies the suggestion for `Planner::suppress`: the habit name for
+    /// habit suggestions, otherwise the suggestion text
+    #[serde(default)]
+    pub key: String,
+}
+
+/// Why a suggestion was made
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestionExplanation {
+    pub suggestion: Suggestion,
+    /// Habit that triggered the suggestion, if any
+    pub habit_id: Option<Uuid>,
+    /// Current activity that triggered the suggestion, if any
+    pub activity: Option<String>,
+}
+
+/// Audit record of how the planner read a context
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanExplanation {
+    /// Events that made it into the planner's context window, newest first
+    pub events: Vec<Uuid>,
+    /// Suggestions, most pressing first, with what triggered each
+    pub suggestions: Vec<SuggestionExplanation>,
+}
+
+/// One step of a multi-step plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub intent: Intent,
+    /// Indices of steps that must run before this one
+    pub depends_on: Vec<usize>,
+}
+
+/// Ordered set of intents working towards a goal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+    pub goal: String,
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    /// Steps in an order that respects every dependency
+    /// Independent steps keep their original relative order.
+    /// Errors if a dependency is out of range or the steps form a cycle.
+    pub fn topological_order(&self) -> Result<Vec<&PlanStep>> {
+        let n = self.steps.len();
+        let mut remaining = vec![0usize; n];
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); n];
+
+        for (i, step) in self.steps.iter().enumerate() {
+            for &dep in &step.depends_on {
+                if dep >= n {
+                    return Err(AgentError::Other(format!(
+                        "Plan step {} depends on missing step {}",
+                        i, dep
+                    )));
+                }
+                remaining[i] += 1;
+                dependents[dep].push(i);
+            }
+        }
+
+        let mut ready: std::collections::BTreeSet<usize> =
+            (0..n).filter(|&i| remaining[i] == 0).collect();
+        let mut order = Vec::with_capacity(n);
+
+        while let Some(i) = ready.pop_first() {
+            order.push(&self.steps[i]);
+            for &next in &dependents[i] {
+                remaining[next] -= 1;
+                if remaining[next] == 0 {
+                    ready.insert(next);
+                }
+            }
+        }
+
+        if order.len() != n {
+            return Err(AgentError::Other(
+                "Plan contains a dependency cycle".to_string(),
+            ));
+        }
+
+        Ok(order)
+    }
+}
+
+/// Plan step as proposed by an LLM, before validation
+#[derive(Debug, Deserialize)]
+struct LlmPlanStep {
+    intent_type: String,
+    confidence: f32,
+    #[serde(default)]
+    parameters: HashMap<String, Value>,
+    reasoning: String,
+    #[serde(default)]
+    depends_on: Vec<usize>,
+}
+
+/// Layout of the context summary sent to the LLM
+///
+/// Placeholders:
+/// - `{user}`: user id, timestamp, location and activity lines
+/// - `{events}`: the "Recent events" section (empty when there are none)
+/// - `{habits}`: the "Active habits" section (empty when there are none)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    template: String,
+}
+
+impl PromptTemplate {
+    pub fn new(template: String) -> Self {
+        Self { template }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Substitute the rendered sections into the template
+    /// Placeholders are replaced in a single pass, so section text that
+    /// happens to contain `{events}` etc. is left alone.
+    pub fn render(&self, user: &str, events: &str, habits: &str) -> String {
+        let mut output = String::with_capacity(self.template.len() + user.len() + events.len() + habits.len());
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let replacement = [("{user}", user), ("{events}", events), ("{habits}", habits)]
+                .into_iter()
+                .find(|(placeholder, _)| rest.starts_with(placeholder));
+
+            match replacement {
+                Some((placeholder, value)) => {
+                    output.push_str(value);
+                    rest = &rest[placeholder.len()..];
+                }
+                None => {
+                    output.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        output.push_str(rest);
+
+        output
+    }
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self::new("{user}{events}{habits}".to_string())
+    }
+}
+
+/// Intents that are inappropriate while the user is doing something
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityRule {
+    pub activity: String,
+    /// Intent type prefixes blocked during the activity (e.g. `device.`)
+    pub blocked_prefixes: Vec<String>,
+    pub reason: String,
+}
+
+impl ActivityRule {
+    pub fn new(activity: &str, blocked_prefixes: &[&str], reason: &str) -> Self {
+        Self {
+            activity: activity.to_string(),
+            blocked_prefixes: blocked_prefixes.iter().map(|p| p.to_string()).collect(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Whether this rule blocks `intent` during `activity`
+    pub fn blocks(&self, activity: &str, intent: &Intent) -> bool {
+        self.activity == activity
+            && self
+                .blocked_prefixes
+                .iter()
+                .any(|prefix| intent.intent_type.starts_with(prefix.as_str()))
+    }
+}
+
+/// Action values that undo each other
+const OPPOSING_ACTIONS: &[(&str, &str)] = &[
+    ("on", "off"),
+    ("enable", "disable"),
+    ("open", "close"),
+    ("lock", "unlock"),
+    ("start", "stop"),
+];
+
+/// Parameters that identify what an action applies to, in lookup order
+const SCOPE_PARAMS: &[&str] = &["device", "scope", "target"];
 
 /// Planner for reasoning about actions and suggestions
 pub struct Planner {
     // Configuration
     max_context_events: usize,
+    analyzer: HabitAnalyzer,
+    template: PromptTemplate,
+    activity_rules: Vec<ActivityRule>,
+    /// When set, event importance decays with age when ranking events
+    importance_half_life: Option<chrono::Duration>,
+    /// Suggestion keys silenced until the given time
+    cooldowns: HashMap<String, DateTime<Utc>>,
+    /// Multiple of a habit's usual interval that must pass before suggesting it
+    overdue_grace: f32,
 }
 
 impl Planner {
     pub fn new() -> Self {
         Self {
             max_context_events: 10,
+            analyzer: HabitAnalyzer::new(),
+            template: PromptTemplate::default(),
+            activity_rules: Self::default_activity_rules(),
+            importance_half_life: None,
+            cooldowns: HashMap::new(),
+            overdue_grace: 1.0,
         }
     }
 
-    /// Build a context summary for LLM prompting
-    pub fn build_context_summary(&self, context: &Context) -> String {
-        let mut summary = String::new();
+    /// Rank events by importance decayed with this half-life, so older
+    /// events count for less in summaries
+    pub fn with_importance_half_life(mut self, half_life: chrono::Duration) -> Self {
+        self.importance_half_life = Some(half_life);
+        self
+    }
 
-        summary.push_str(&format!("User: {}\n", context.user_id));
-        summary.push_str(&format!("Timestamp: {}\n", context.timestamp));
+    /// Only suggest a habit once `grace` times its usual interval has passed
+    /// The default of 1.0 suggests it as soon as it is due; 1.25 waits until
+    /// a daily habit is six hours late.
+    pub fn with_overdue_grace(mut self, grace: f32) -> Self {
+        self.overdue_grace = grace.max(0.0);
+        self
+    }
 
-        if let Some(location) = &context.current_location {
-            summary.push_str(&format!("Location: {}\n", location));
+    /// Importance used for ranking, decayed if a half-life is configured
+    fn effective_importance(&self, event: &Event, now: chrono::DateTime<chrono::Utc>) -> f32 {
+        match self.importance_half_life {
+            Some(half_life) => event.decayed_importance(now, half_life),
+            None => event.importance,
         }
+    }
 
-        if let Some(activity) = &context.current_activity {
-            summary.push_str(&format!("Activity: {}\n", activity));
-        }
+    /// Built-in activity rules
+    pub fn default_activity_rules() -> Vec<ActivityRule> {
+        vec![ActivityRule::new(
+            "sleeping",
+            &["device."],
+            "User appears to be sleeping, device control may not be appropriate",
+        )]
+    }
+
+    /// Register an additional activity rule for `evaluate_intent`
+    pub fn add_activity_rule(&mut self, rule: ActivityRule) {
+        self.activity_rules.push(rule);
+    }
+
+    pub fn activity_rules(&self) -> &[ActivityRule] {
+        &self.activity_rules
+    }
+
+    /// Use a custom layout for context summaries
+    pub fn with_template(mut self, template: PromptTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    pub fn template(&self) -> &PromptTemplate {
+        &self.template
+    }
+
+    /// Build a context summary for LLM prompting
+    pub fn build_context_summary(&self, context: &Context) -> String {
+        let header = Self::context_header(context);
 
         // Recent events
+        let mut events = String::new();
         if !context.recent_events.is_empty() {
-            summary.push_str("\nRecent events:\n");
+            events.push_str("\nRecent events:\n");
             let events_to_show = context.recent_events
                 .iter()
                 .rev()
                 .take(self.max_context_events);
             
             for event in events_to_show {
-                summary.push_str(&format!(
-                    "  - {} ({}): {}\n",
-                    event.event_type,
-                    event.timestamp.format("%Y-%m-%d %H:%M"),
-                    event.description
-                ));
+                events.push_str(&Self::event_line(event));
             }
         }
 
         // Active habits
+        let mut habits = String::new();
         if !context.active_habits.is_empty() {
-            summary.push_str("\nActive habits:\n");
+            habits.push_str("\nActive habits:\n");
             for habit in &context.active_habits {
-                let last_completed = habit.last_completed
-                    .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
-                    .unwrap_or_else(|| "never".to_string());
-                
-                summary.push_str(&format!(
-                    "  - {} ({:?}): last completed {}\n",
-                    habit.name,
-                    habit.frequency,
-                    last_completed
-                ));
+                habits.push_str(&Self::habit_line(habit));
             }
         }
 
-        summary
+        self.template.render(&header, &events, &habits)
+    }
+
+    /// Build a context summary that fits within `max_tokens`
+    /// Habits are kept ahead of events; events are added from most to least
+    /// important (newest first on ties) until the budget is spent.
+    /// The token budget supersedes `max_context_events`.
+    pub fn build_context_summary_budgeted(&self, context: &Context, max_tokens: u32) -> String {
+        let header = Self::context_header(context);
+        let framing = self.template.render("", "", "");
+        let mut used = estimate_tokens(&header) + estimate_tokens(&framing);
+
+        // Habits
+        let habits_title = "\nActive habits:\n";
+        let mut habit_lines = Vec::new();
+        for habit in &context.active_habits {
+            let line = Self::habit_line(habit);
+            let mut cost = estimate_tokens(&line);
+            if habit_lines.is_empty() {
+                cost += estimate_tokens(habits_title);
+            }
+            if used + cost > max_tokens {
+                break;
+            }
+            used += cost;
+            habit_lines.push(line);
+        }
+
+        // Events, most important first
+        let events_title = "\nRecent events:\n";
+        let now = chrono::Utc::now();
+        let mut candidates: Vec<&Event> = context.recent_events.iter().collect();
+        candidates.sort_by(|a, b| {
+            self.effective_importance(b, now)
+                .partial_cmp(&self.effective_importance(a, now))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+
+        let mut selected: Vec<&Event> = Vec::new();
+        for event in candidates {
+            let mut cost = estimate_tokens(&Self::event_line(event));
+            if selected.is_empty() {
+                cost += estimate_tokens(events_title);
+            }
+            if used + cost > max_tokens {
+                continue;
+            }
+            used += cost;
+            selected.push(event);
+        }
+
+        // Render events newest first, like the unbudgeted summary
+        selected.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+
+        let mut events = String::new();
+        if !selected.is_empty() {
+            events.push_str(events_title);
+            for event in selected {
+                events.push_str(&Self::event_line(event));
+            }
+        }
+        let mut habits = String::new();
+        if !habit_lines.is_empty() {
+            habits.push_str(habits_title);
+            for line in habit_lines {
+                habits.push_str(&line);
+            }
+        }
+
+        self.template.render(&header, &events, &habits)
+    }
+
+    fn context_header(context: &Context) -> String {
+        let mut header = String::new();
+
+        header.push_str(&format!("User: {}\n", context.user_id));
+        header.push_str(&format!("Timestamp: {}\n", context.timestamp));
+
+        if let Some(location) = &context.current_location {
+            header.push_str(&format!("Location: {}\n", location));
+        }
+
+        if let Some(activity) = &context.current_activity {
+            header.push_str(&format!("Activity: {}\n", activity));
+        }
+
+        header
+    }
+
+    fn event_line(event: &Event) -> String {
+        format!(
+            "  - {} ({}): {}\n",
+            event.event_type,
+            event.timestamp.format("%Y-%m-%d %H:%M"),
+            event.description
+        )
+    }
+
+    fn habit_line(habit: &Habit) -> String {
+        let last_completed = habit.last_completed
+            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_string());
+
+        format!(
+            "  - {} ({:?}): last completed {}\n",
+            habit.name,
+            habit.frequency,
+            last_completed
+        )
     }
 
     /// Compress events into a summary
@@ -83,10 +451,11 @@ impl Planner {
         let mut by_type: HashMap<String, Vec<&Event>> = HashMap::new();
         for event in events {
             by_type.entry(event.event_type.clone())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(event);
         }
 
+        let now = chrono::Utc::now();
         let mut summary = String::new();
         summary.push_str("Event summary:\n");
 
@@ -99,7 +468,10 @@ impl Planner {
 
             // Show most important event of this type
             if let Some(most_important) = type_events.iter()
-                .max_by(|a, b| a.importance.partial_cmp(&b.importance).unwrap())
+                .max_by(|a, b| {
+                    self.effective_importance(a, now)
+                        .total_cmp(&self.effective_importance(b, now))
+                })
             {
                 summary.push_str(&format!("    Most important: {}\n", most_important.description));
             }
@@ -108,9 +480,181 @@ impl Planner {
         summary
     }
 
+    /// Summarize events in prose for a digest
+    /// The most important events that fit in `SUMMARY_EVENT_TOKENS` are sent
+    /// to the model in chronological order. Falls back to `compress_events`
+    /// when the provider is unavailable, fails or returns nothing.
+    pub fn summarize_events_llm(
+        &self,
+        events: &[Event],
+        llm: &dyn LlmProvider,
+        options: &LlmOptions,
+    ) -> Result<String> {
+        if events.is_empty() {
+            return Ok(self.compress_events(events));
+        }
+        if !llm.is_available() {
+            log::warn!("LLM provider '{}' unavailable, using mechanical summary", llm.name());
+            return Ok(self.compress_events(events));
+        }
+
+        let now = chrono::Utc::now();
+        let mut candidates: Vec<&Event> = events.iter().collect();
+        candidates.sort_by(|a, b| {
+            self.effective_importance(b, now)
+                .total_cmp(&self.effective_importance(a, now))
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+
+        let mut used = 0;
+        let mut selected: Vec<&Event> = Vec::new();
+        for event in candidates {
+            let cost = estimate_tokens(&Self::event_line(event));
+            if used + cost > SUMMARY_EVENT_TOKENS {
+                continue;
+            }
+            used += cost;
+            selected.push(event);
+        }
+        selected.sort_by_key(|e| e.timestamp);
+
+        let lines: String = selected.iter().map(|e| Self::event_line(e)).collect();
+        let prompt = format!(
+            "Summarize the following events in a few sentences for a daily digest. \
+             Mention anything that needs attention first.\n\nEvents:\n{}",
+            lines
+        );
+
+        match llm.complete(&prompt, options) {
+            Ok(response) if !response.text.trim().is_empty() => Ok(response.text.trim().to_string()),
+            Ok(_) => Ok(self.compress_events(events)),
+            Err(e) => {
+                log::warn!("LLM summary failed, using mechanical summary: {}", e);
+                Ok(self.compress_events(events))
+            }
+        }
+    }
+
+    /// Compress events into at most `max_lines` event lines
+    /// Lines are shared between event types in proportion to each type's
+    /// total importance, so a rare but critical type still surfaces.
+    /// Types are listed by total importance, highest first.
+    pub fn compress_events_weighted(&self, events: &[Event], max_lines: usize) -> String {
+        if events.is_empty() {
+            return "No recent events.".to_string();
+        }
+
+        // Group events by type, most important first within each type
+        let mut by_type: HashMap<&str, Vec<&Event>> = HashMap::new();
+        for event in events {
+            by_type.entry(event.event_type.as_str()).or_default().push(event);
+        }
+
+        let now = chrono::Utc::now();
+        let mut groups: Vec<(&str, f32, Vec<&Event>)> = by_type
+            .into_iter()
+            .map(|(event_type, mut type_events)| {
+                type_events.sort_by(|a, b| {
+                    self.effective_importance(b, now)
+                        .total_cmp(&self.effective_importance(a, now))
+                        .then(b.timestamp.cmp(&a.timestamp))
+                });
+                let total = type_events
+                    .iter()
+                    .map(|e| self.effective_importance(e, now).max(0.0))
+                    .sum();
+                (event_type, total, type_events)
+            })
+            .collect();
+        groups.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+
+        // Hand out lines one at a time to the type with the largest
+        // importance per line already allocated (D'Hondt method)
+        let mut slots = vec![0usize; groups.len()];
+        for _ in 0..max_lines {
+            let next = groups
+                .iter()
+                .enumerate()
+                .filter(|(i, (_, _, type_events))| slots[*i] < type_events.len())
+                .max_by(|(i, (_, a, _)), (j, (_, b, _))| {
+                    (a / (slots[*i] + 1) as f32)
+                        .total_cmp(&(b / (slots[*j] + 1) as f32))
+                        .then(j.cmp(i))
+                })
+                .map(|(i, _)| i);
+
+            match next {
+                Some(i) => slots[i] += 1,
+                None => break,
+            }
+        }
+
+        let mut summary = String::new();
+        summary.push_str("Event summary:\n");
+
+        for ((event_type, total, type_events), count) in groups.iter().zip(slots) {
+            if count == 0 {
+                continue;
+            }
+
+            summary.push_str(&format!(
+                "  - {}: {} occurrence(s), total importance {:.2}\n",
+                event_type,
+                type_events.len(),
+                total
+            ));
+            for event in type_events.iter().take(count) {
+                summary.push_str(&format!("    {}\n", event.description));
+            }
+        }
+
+        summary
+    }
+
     /// Suggest next actions based on context
     /// These are suggestions, not commands - user must authorize
     pub fn suggest_actions(&self, context: &Context) -> Vec<String> {
+        self.suggest_actions_ranked(context)
+            .into_iter()
+            .map(|s| s.text)
+            .collect()
+    }
+
+    /// Suggest next actions, most pressing first
+    /// Habit scores blend how overdue the habit is with how consistently
+    /// it has been kept
+    pub fn suggest_actions_ranked(&self, context: &Context) -> Vec<Suggestion> {
+        self.explained_suggestions(context)
+            .into_iter()
+            .map(|explained| explained.suggestion)
+            .collect()
+    }
+
+    /// Record which events and habits shaped the planner's view of `context`
+    pub fn explain(&self, context: &Context) -> PlanExplanation {
+        PlanExplanation {
+            events: context
+                .recent_events
+                .iter()
+                .rev()
+                .take(self.max_context_events)
+                .map(|event| event.id)
+                .collect(),
+            suggestions: self.explained_suggestions(context),
+        }
+    }
+
+    /// Stop suggesting `suggestion_key` until `until`, e.g. after the user dismisses it
+    pub fn suppress(&mut self, suggestion_key: &str, until: DateTime<Utc>) {
+        self.cooldowns.insert(suggestion_key.to_string(), until);
+    }
+
+    fn is_suppressed(&self, key: &str, now: DateTime<Utc>) -> bool {
+        self.cooldowns.get(key).is_some_and(|until| now < *until)
+    }
+
+    /// Ranked suggestions together with what triggered each one
+    fn explained_suggestions(&self, context: &Context) -> Vec<SuggestionExplanation> {
         let mut suggestions = Vec::new();
 
         // Check for habits that might be due
@@ -120,37 +664,123 @@ impl Planner {
                     .signed_duration_since(last)
                     .num_hours();
 
-                let expected_hours = match habit.frequency {
-                    crate::types::HabitFrequency::Daily => 24,
-                    crate::types::HabitFrequency::Weekly => 168,
-                    crate::types::HabitFrequency::Monthly => 720,
-                    crate::types::HabitFrequency::Custom(_) => 24,
-                };
+                let expected_hours = HabitAnalyzer::expected_interval_hours(&habit.frequency);
 
-                if hours_since >= expected_hours {
-                    suggestions.push(format!("Consider: {}", habit.name));
+                if hours_since as f32 >= expected_hours as f32 * self.overdue_grace {
+                    let due = self.analyzer.is_habit_due(habit, &[last]);
+                    let consistency = Self::consistency(habit);
+                    suggestions.push(SuggestionExplanation {
+                        suggestion: Suggestion {
+                            text: format!("Consider: {}", habit.name),
+                            score: 0.7 * due + 0.3 * consistency,
+                            reason: format!(
+                                "Last completed {} hours ago (usually every {} hours)",
+                                hours_since, expected_hours
+                            ),
+                            key: habit.name.clone(),
+                        },
+                        habit_id: Some(habit.id),
+                        activity: None,
+                    });
                 }
             } else {
-                suggestions.push(format!("Start habit: {}", habit.name));
+                suggestions.push(SuggestionExplanation {
+                    suggestion: Suggestion {
+                        text: format!("Start habit: {}", habit.name),
+                        score: 0.4,
+                        reason: "Habit has not been completed yet".to_string(),
+                        key: habit.name.clone(),
+                    },
+                    habit_id: Some(habit.id),
+                    activity: None,
+                });
             }
         }
 
         // Context-aware suggestions
         if let Some(activity) = &context.current_activity {
             if activity == "working" {
-                suggestions.push("Take a break?".to_string());
+                suggestions.push(SuggestionExplanation {
+                    suggestion: Suggestion {
+                        text: "Take a break?".to_string(),
+                        score: 0.5,
+                        reason: "Currently working".to_string(),
+                        key: "Take a break?".to_string(),
+                    },
+                    habit_id: None,
+                    activity: Some(activity.clone()),
+                });
             }
         }
 
+        let now = Utc::now();
+        suggestions.retain(|explained| !self.is_suppressed(&explained.suggestion.key, now));
+
+        suggestions.sort_by(|a, b| {
+            b.suggestion
+                .score
+                .partial_cmp(&a.suggestion.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
         suggestions
     }
 
+    /// 1.0 for a perfectly regular habit, falling towards 0.0 as the
+    /// interval std dev grows; 0.5 when nothing is known yet
+    fn consistency(habit: &Habit) -> f32 {
+        habit
+            .variance
+            .map(|v| {
+                let std_dev = v.max(0.0).sqrt();
+                1.0 / (1.0 + (std_dev / 10.0).powi(2))
+            })
+            .unwrap_or(0.5)
+    }
+
+    /// Propose a `reminder.create` intent for every due habit in `context`
+    /// Confidence is how due the habit is; intents the generator rejects
+    /// (e.g. below its minimum confidence) are left out.
+    pub fn habit_reminder_intents(
+        &self,
+        context: &Context,
+        generator: &IntentGenerator,
+    ) -> Result<Vec<Intent>> {
+        let mut intents = Vec::new();
+
+        for habit in &context.active_habits {
+            let Some(last) = habit.last_completed else {
+                continue;
+            };
+            let due = self.analyzer.is_habit_due(habit, &[last]);
+            if due < HABIT_REMINDER_THRESHOLD {
+                continue;
+            }
+
+            let mut parameters = HashMap::new();
+            parameters.insert("task".to_string(), Value::String(habit.name.clone()));
+            parameters.insert("habit_id".to_string(), Value::String(habit.id.to_string()));
+            let last_completed = last.format("%Y-%m-%d %H:%M");
+            let reasoning = format!("Habit '{}' is due (last completed {})", habit.name, last_completed);
+            let evidence = EvidenceRef::habit(habit.id, &format!("Last completed {}", last_completed));
+
+            match generator.generate("reminder.create".to_string(), due, parameters, reasoning) {
+                Ok(intent) => intents.push(intent.with_evidence(vec![evidence])),
+                Err(AgentError::InvalidIntent(reason)) => {
+                    log::debug!("Skipping reminder for habit '{}': {}", habit.name, reason);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(intents)
+    }
+
     /// Evaluate if an intent makes sense in current context
     pub fn evaluate_intent(&self, intent: &Intent, context: &Context) -> (bool, String) {
         // Check if intent aligns with current activity
         if let Some(activity) = &context.current_activity {
-            if activity == "sleeping" && intent.intent_type.starts_with("device.") {
-                return (false, "User appears to be sleeping, device control may not be appropriate".to_string());
+            if let Some(rule) = self.activity_rules.iter().find(|r| r.blocks(activity, intent)) {
+                return (false, rule.reason.clone());
             }
         }
 
@@ -161,6 +791,137 @@ impl Planner {
 
         (true, "Intent appears appropriate for current context".to_string())
     }
+
+    /// Ask the LLM to break `goal` into intents with dependencies
+    /// The plan is checked for a valid execution order before returning.
+    pub fn build_plan(&self, goal: &str, context: &Context, llm: &dyn LlmProvider) -> Result<Plan> {
+        let prompt = format!(
+            "{}\n\
+             Break the goal below into an ordered list of steps.\n\
+             Respond with a JSON array of objects with the fields \
+             \"intent_type\" (dotted, e.g. \"weather.query\"), \
+             \"confidence\" (0.0 - 1.0), \"parameters\" (object), \
+             \"reasoning\" (string) and \"depends_on\" (array of indices of earlier steps).\n\n\
+             Goal: {}",
+            self.build_context_summary(context),
+            goal
+        );
+        let options = LlmOptions {
+            temperature: 0.0,
+            ..LlmOptions::default()
+        };
+
+        let response = llm.complete(&prompt, &options)?;
+        let array = extract_json_array(&response.text).ok_or_else(|| {
+            AgentError::InvalidIntent("LLM response did not contain a JSON array".to_string())
+        })?;
+        let proposed: Vec<LlmPlanStep> = serde_json::from_value(array)?;
+
+        let generator = IntentGenerator::new();
+        let evidence = self.context_evidence(context);
+        let steps = proposed
+            .into_iter()
+            .map(|p| {
+                let intent = generator
+                    .generate(p.intent_type, p.confidence, p.parameters, p.reasoning)?
+                    .with_evidence(evidence.clone());
+                Ok(PlanStep {
+                    intent,
+                    depends_on: p.depends_on,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let plan = Plan {
+            goal: goal.to_string(),
+            steps,
+        };
+        plan.topological_order()?;
+
+        Ok(plan)
+    }
+
+    /// Events and habits the LLM was shown when planning from `context`
+    fn context_evidence(&self, context: &Context) -> Vec<EvidenceRef> {
+        let events = context
+            .recent_events
+            .iter()
+            .rev()
+            .take(self.max_context_events)
+            .map(|event| EvidenceRef::event(event.id, &format!("Recent {} event", event.event_type)));
+        let habits = context
+            .active_habits
+            .iter()
+            .map(|habit| EvidenceRef::habit(habit.id, &format!("Active habit '{}'", habit.name)));
+
+        events.chain(habits).collect()
+    }
+
+    /// Find pairs of intents that would issue contradictory commands
+    /// Two intents conflict when they target the same module and scope
+    /// parameter but carry opposing `action` values (e.g. `on` / `off`).
+    /// Returns `(first_index, second_index, reason)` for each pair.
+    pub fn detect_conflicts(&self, intents: &[Intent]) -> Vec<(usize, usize, String)> {
+        let mut conflicts = Vec::new();
+
+        for i in 0..intents.len() {
+            for j in (i + 1)..intents.len() {
+                let (a, b) = (&intents[i], &intents[j]);
+
+                let module = match (Self::module_of(a), Self::module_of(b)) {
+                    (Some(x), Some(y)) if x == y => x,
+                    _ => continue,
+                };
+                let scope = match (Self::scope_of(a), Self::scope_of(b)) {
+                    (Some(x), Some(y)) if x == y => x,
+                    _ => continue,
+                };
+                let (action_a, action_b) = match (Self::action_of(a), Self::action_of(b)) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => continue,
+                };
+
+                let opposing = OPPOSING_ACTIONS.iter().any(|(x, y)| {
+                    (action_a == *x && action_b == *y) || (action_a == *y && action_b == *x)
+                });
+                if opposing {
+                    let scope = scope
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| scope.to_string());
+                    conflicts.push((
+                        i,
+                        j,
+                        format!(
+                            "Conflicting actions '{}' and '{}' for {} {}",
+                            action_a, action_b, module, scope
+                        ),
+                    ));
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    fn module_of(intent: &Intent) -> Option<&str> {
+        intent
+            .target_module
+            .as_deref()
+            .or_else(|| intent.intent_type.split('.').next())
+    }
+
+    fn scope_of(intent: &Intent) -> Option<&serde_json::Value> {
+        SCOPE_PARAMS.iter().find_map(|key| intent.parameters.get(*key))
+    }
+
+    fn action_of(intent: &Intent) -> Option<String> {
+        intent
+            .parameters
+            .get("action")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_lowercase())
+    }
 }
 
 impl Default for Planner {
@@ -172,7 +933,7 @@ impl Default for Planner {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{Habit, HabitFrequency};
+    use crate::types::{EvidenceSource, Habit, HabitFrequency};
     use chrono::Utc;
 
     #[test]
@@ -239,4 +1000,511 @@ mod tests {
         assert!(!appropriate);
         assert!(reason.contains("sleeping"));
     }
+
+    #[test]
+    fn test_build_context_summary_budgeted() {
+        let planner = Planner::new();
+        let mut context = Context::new("test-user".to_string());
+
+        for i in 0..50 {
+            context.recent_events.push(Event::new(
+                "note".to_string(),
+                format!("Routine background note number {}", i),
+                0.1,
+            ));
+        }
+        context.recent_events.push(Event::new(
+            "alert".to_string(),
+            "Smoke detector battery low".to_string(),
+            0.95,
+        ));
+        context.recent_events.push(Event::new(
+            "reminder".to_string(),
+            "Dentist appointment tomorrow".to_string(),
+            0.9,
+        ));
+
+        let budget = 60;
+        let summary = planner.build_context_summary_budgeted(&context, budget);
+
+        assert!(estimate_tokens(&summary) <= budget);
+        assert!(summary.contains("Smoke detector battery low"));
+        assert!(summary.contains("Dentist appointment tomorrow"));
+        assert!(summary.matches("Routine background note").count() < 50);
+    }
+
+    #[test]
+    fn test_suggest_actions_ranked() {
+        let planner = Planner::new();
+        let mut context = Context::new("test-user".to_string());
+        context.current_activity = Some("working".to_string());
+
+        let mut variable = Habit::new(
+            "Journal".to_string(),
+            "Evening journaling".to_string(),
+            HabitFrequency::Daily,
+        );
+        variable.last_completed = Some(Utc::now() - chrono::Duration::hours(25));
+        variable.variance = Some(900.0);
+        context.active_habits.push(variable);
+
+        let mut consistent = Habit::new(
+            "Medication".to_string(),
+            "Morning medication".to_string(),
+            HabitFrequency::Daily,
+        );
+        consistent.last_completed = Some(Utc::now() - chrono::Duration::days(3));
+        consistent.variance = Some(1.0);
+        context.active_habits.push(consistent);
+
+        let ranked = planner.suggest_actions_ranked(&context);
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(ranked[0].text, "Consider: Medication");
+        assert!(ranked.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let journal = ranked.iter().position(|s| s.text == "Consider: Journal").unwrap();
+        assert!(ranked[0].score > ranked[journal].score);
+
+        // The string version keeps the same order
+        let plain = planner.suggest_actions(&context);
+        assert_eq!(plain[0], "Consider: Medication");
+    }
+
+    fn device_intent(device: &str, action: &str) -> Intent {
+        let mut params = HashMap::new();
+        params.insert("device".to_string(), serde_json::json!(device));
+        params.insert("action".to_string(), serde_json::json!(action));
+
+        let mut intent = Intent::new(
+            "device.control".to_string(),
+            0.9,
+            params,
+            format!("Turn {} the {}", action, device),
+        );
+        intent.target_module = Some("device".to_string());
+        intent
+    }
+
+    #[test]
+    fn test_detect_conflicts() {
+        let planner = Planner::new();
+        let intents = vec![
+            device_intent("heater", "on"),
+            device_intent("lamp", "on"),
+            device_intent("heater", "OFF"),
+        ];
+
+        let conflicts = planner.detect_conflicts(&intents);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].0, conflicts[0].1), (0, 2));
+        assert!(conflicts[0].2.contains("heater"));
+    }
+
+    #[test]
+    fn test_no_conflict_for_different_devices() {
+        let planner = Planner::new();
+        let intents = vec![device_intent("heater", "on"), device_intent("fan", "off")];
+
+        assert!(planner.detect_conflicts(&intents).is_empty());
+    }
+
+    struct FixedLlm(&'static str);
+
+    impl LlmProvider for FixedLlm {
+        fn complete(&self, _prompt: &str, _options: &LlmOptions) -> Result<crate::types::LlmResponse> {
+            Ok(crate::types::LlmResponse {
+                text: self.0.to_string(),
+                finish_reason: "stop".to_string(),
+                usage: crate::types::LlmUsage {
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    total_tokens: 0,
+                },
+            })
+        }
+
+        fn name(&self) -> &str {
+            "fixed"
+        }
+    }
+
+    #[test]
+    fn test_build_plan_linear() {
+        let planner = Planner::new();
+        let context = Context::new("test-user".to_string());
+        let llm = FixedLlm(
+            r#"Here is the plan:
+            [
+              {"intent_type": "notification.send", "confidence": 0.8,
+               "parameters": {"message": "Pack list"}, "reasoning": "Draft packing list",
+               "depends_on": [2]},
+              {"intent_type": "weather.query", "confidence": 0.9,
+               "parameters": {"location": "Lisbon"}, "reasoning": "Check weather"},
+              {"intent_type": "reminder.create", "confidence": 0.85,
+               "parameters": {"time": "6am"}, "reasoning": "Set alarm", "depends_on": [1]}
+            ]"#,
+        );
+
+        let plan = planner.build_plan("prepare for my trip", &context, &llm).unwrap();
+        assert_eq!(plan.steps.len(), 3);
+        assert!(plan.steps[0].intent.requires_permission);
+
+        let order: Vec<&str> = plan
+            .topological_order()
+            .unwrap()
+            .iter()
+            .map(|s| s.intent.intent_type.as_str())
+            .collect();
+        assert_eq!(order, vec!["weather.query", "reminder.create", "notification.send"]);
+    }
+
+    #[test]
+    fn test_plan_cycle_detection() {
+        let step = |depends_on: Vec<usize>| PlanStep {
+            intent: Intent::new("weather.query".to_string(), 0.9, HashMap::new(), String::new()),
+            depends_on,
+        };
+        let plan = Plan {
+            goal: "loop".to_string(),
+            steps: vec![step(vec![]), step(vec![0, 2]), step(vec![1])],
+        };
+
+        assert!(matches!(plan.topological_order(), Err(AgentError::Other(_))));
+
+        let planner = Planner::new();
+        let llm = FixedLlm(
+            r#"[{"intent_type": "a.x", "confidence": 0.9, "reasoning": "a", "depends_on": [1]},
+                {"intent_type": "b.y", "confidence": 0.9, "reasoning": "b", "depends_on": [0]}]"#,
+        );
+        let context = Context::new("test-user".to_string());
+        assert!(planner.build_plan("loop", &context, &llm).is_err());
+    }
+
+    #[test]
+    fn test_custom_prompt_template() {
+        let template = PromptTemplate::new(
+            "You are a calm home assistant.\n{user}\n## Memory{events}\n## Routines{habits}\nKeep answers short."
+                .to_string(),
+        );
+        let planner = Planner::new().with_template(template);
+
+        let mut context = Context::new("test-user".to_string());
+        context
+            .recent_events
+            .push(Event::new("user_input".to_string(), "Asked about {habits}".to_string(), 0.5));
+        context.active_habits.push(Habit::new(
+            "Exercise".to_string(),
+            "Daily exercise".to_string(),
+            HabitFrequency::Daily,
+        ));
+
+        let summary = planner.build_context_summary(&context);
+        assert!(summary.starts_with("You are a calm home assistant.\nUser: test-user\n"));
+        assert!(summary.contains("## Memory\nRecent events:\n"));
+        assert!(summary.contains("Asked about {habits}"));
+        assert!(summary.contains("## Routines\nActive habits:\n  - Exercise"));
+        assert!(summary.ends_with("Keep answers short."));
+    }
+
+    #[test]
+    fn test_default_template_matches_plain_layout() {
+        let template = PromptTemplate::default();
+        assert_eq!(template.render("U\n", "\nE\n", "\nH\n"), "U\n\nE\n\nH\n");
+        assert_eq!(template.render("U\n", "", ""), "U\n");
+    }
+
+    #[test]
+    fn test_compress_events_weighted() {
+        let planner = Planner::new();
+        let mut events: Vec<Event> = (0..20)
+            .map(|i| Event::new("heartbeat".to_string(), format!("Ping {}", i), 0.02))
+            .collect();
+        events.push(Event::new(
+            "alert".to_string(),
+            "Water leak detected in basement".to_string(),
+            1.0,
+        ));
+
+        let summary = planner.compress_events_weighted(&events, 3);
+        let alert = summary.find("Water leak detected").unwrap();
+        let heartbeat = summary.find("heartbeat").unwrap();
+        assert!(alert < heartbeat);
+        assert_eq!(summary.matches("Ping").count(), 2);
+
+        // A single line goes to the most important type
+        let summary = planner.compress_events_weighted(&events, 1);
+        assert!(summary.contains("Water leak detected"));
+        assert!(!summary.contains("heartbeat"));
+
+        // Deterministic output
+        assert_eq!(summary, planner.compress_events_weighted(&events, 1));
+    }
+
+    #[test]
+    fn test_custom_activity_rule() {
+        let mut planner = Planner::new();
+        planner.add_activity_rule(ActivityRule::new(
+            "driving",
+            &["message.", "email."],
+            "User is driving, messages can wait",
+        ));
+
+        let mut context = Context::new("test-user".to_string());
+        context.current_activity = Some("driving".to_string());
+
+        let message = Intent::new("message.send".to_string(), 0.9, HashMap::new(), "Text".to_string());
+        let (appropriate, reason) = planner.evaluate_intent(&message, &context);
+        assert!(!appropriate);
+        assert_eq!(reason, "User is driving, messages can wait");
+
+        // Other intents and other activities are unaffected
+        let weather = Intent::new("weather.query".to_string(), 0.9, HashMap::new(), "Weather".to_string());
+        assert!(planner.evaluate_intent(&weather, &context).0);
+
+        context.current_activity = Some("working".to_string());
+        assert!(planner.evaluate_intent(&message, &context).0);
+
+        // The default sleeping rule is still there
+        assert_eq!(planner.activity_rules().len(), 2);
+    }
+
+    #[test]
+    fn test_compress_events_with_importance_decay() {
+        let mut stale = Event::new("note".to_string(), "Stale but important".to_string(), 0.9);
+        stale.timestamp = Utc::now() - chrono::Duration::days(7);
+        let fresh = Event::new("note".to_string(), "Fresh and moderate".to_string(), 0.5);
+        let events = vec![stale, fresh];
+
+        let plain = Planner::new().compress_events(&events);
+        assert!(plain.contains("Most important: Stale but important"));
+
+        let decaying = Planner::new().with_importance_half_life(chrono::Duration::days(1));
+        let summary = decaying.compress_events(&events);
+        assert!(summary.contains("Most important: Fresh and moderate"));
+    }
+
+    #[test]
+    fn test_explain_records_due_habit() {
+        let planner = Planner::new();
+        let mut habit = Habit::new(
+            "Exercise".to_string(),
+            "Daily workout".to_string(),
+            HabitFrequency::Daily,
+        );
+        habit.last_completed = Some(chrono::Utc::now() - chrono::Duration::hours(30));
+        let event = Event::new("note".to_string(), "Skipped gym".to_string(), 0.5);
+
+        let context = Context::new("test-user".to_string())
+            .with_activity("working".to_string())
+            .with_events(vec![event.clone()])
+            .with_habits(vec![habit.clone()]);
+
+        let explanation = planner.explain(&context);
+
+        assert_eq!(explanation.events, vec![event.id]);
+        let habit_entry = explanation
+            .suggestions
+            .iter()
+            .find(|s| s.habit_id == Some(habit.id))
+            .expect("due habit should be explained");
+        assert!(habit_entry.suggestion.text.contains("Exercise"));
+
+        let break_entry = explanation
+            .suggestions
+            .iter()
+            .find(|s| s.activity.as_deref() == Some("working"))
+            .unwrap();
+        assert_eq!(break_entry.habit_id, None);
+
+        let ranked = planner.suggest_actions_ranked(&context);
+        let explained: Vec<_> = explanation.suggestions.iter().map(|s| &s.suggestion.text).collect();
+        assert_eq!(ranked.iter().map(|s| &s.text).collect::<Vec<_>>(), explained);
+    }
+
+    #[test]
+    fn test_suppressed_suggestion_omitted_until_cooldown_expires() {
+        let mut planner = Planner::new();
+        let context = Context::new("test-user".to_string()).with_activity("working".to_string());
+        assert!(planner.suggest_actions(&context).contains(&"Take a break?".to_string()));
+
+        planner.suppress("Take a break?", Utc::now() + chrono::Duration::minutes(30));
+        assert!(planner.suggest_actions(&context).is_empty());
+
+        // Cooldown over
+        planner.suppress("Take a break?", Utc::now() - chrono::Duration::seconds(1));
+        assert_eq!(planner.suggest_actions(&context), vec!["Take a break?"]);
+    }
+
+    #[test]
+    fn test_suppress_habit_by_name() {
+        let mut planner = Planner::new();
+        let habit = Habit::new(
+            "Exercise".to_string(),
+            "Daily workout".to_string(),
+            HabitFrequency::Daily,
+        );
+        let context = Context::new("test-user".to_string()).with_habits(vec![habit]);
+
+        planner.suppress("Exercise", Utc::now() + chrono::Duration::hours(1));
+
+        assert!(planner.suggest_actions_ranked(&context).is_empty());
+    }
+
+    struct FailingLlm;
+
+    impl LlmProvider for FailingLlm {
+        fn complete(&self, _prompt: &str, _options: &LlmOptions) -> Result<crate::types::LlmResponse> {
+            Err(AgentError::LlmUnavailable("connection refused".to_string()))
+        }
+
+        fn name(&self) -> &str {
+            "failing"
+        }
+    }
+
+    fn digest_events() -> Vec<Event> {
+        vec![
+            Event::new("door".to_string(), "Front door opened".to_string(), 0.4),
+            Event::new("alert".to_string(), "Smoke detector battery low".to_string(), 0.9),
+        ]
+    }
+
+    #[test]
+    fn test_summarize_events_llm() {
+        let planner = Planner::new();
+        let summary = planner
+            .summarize_events_llm(&digest_events(), &crate::llm::MockLlmProvider::new(), &LlmOptions::default())
+            .unwrap();
+
+        assert!(!summary.is_empty());
+        assert!(!summary.starts_with("Event summary:"));
+    }
+
+    #[test]
+    fn test_summarize_events_llm_falls_back() {
+        let planner = Planner::new();
+        let events = digest_events();
+        let options = LlmOptions::default();
+
+        let summary = planner.summarize_events_llm(&events, &FailingLlm, &options).unwrap();
+        assert!(summary.starts_with("Event summary:"));
+        assert!(summary.contains("Smoke detector battery low"));
+
+        let unavailable = crate::llm::LocalLlmProvider::new("/nonexistent/model.gguf".to_string());
+        let summary = planner.summarize_events_llm(&events, &unavailable, &options).unwrap();
+        assert!(summary.starts_with("Event summary:"));
+    }
+
+    #[test]
+    fn test_habit_reminder_intents() {
+        let planner = Planner::new();
+        let mut overdue = Habit::new("Exercise".to_string(), "Daily workout".to_string(), HabitFrequency::Daily);
+        overdue.last_completed = Some(Utc::now() - chrono::Duration::hours(30));
+        let mut fresh = Habit::new("Read".to_string(), "Read a chapter".to_string(), HabitFrequency::Daily);
+        fresh.last_completed = Some(Utc::now() - chrono::Duration::hours(1));
+        let context = Context::new("test-user".to_string()).with_habits(vec![overdue.clone(), fresh]);
+
+        let intents = planner
+            .habit_reminder_intents(&context, &IntentGenerator::new())
+            .unwrap();
+
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].intent_type, "reminder.create");
+        assert_eq!(intents[0].parameters["task"], "Exercise");
+        assert_eq!(intents[0].parameters["habit_id"], overdue.id.to_string());
+        assert!(intents[0].confidence >= HABIT_REMINDER_THRESHOLD);
+        assert!(intents[0].reasoning.contains("Exercise"));
+    }
+
+    fn every_three_days(hours_ago: i64) -> Context {
+        let mut habit = Habit::new(
+            "Water plants".to_string(),
+            "Water the balcony plants".to_string(),
+            HabitFrequency::Custom("every 3 days".to_string()),
+        );
+        habit.last_completed = Some(Utc::now() - chrono::Duration::hours(hours_ago));
+
+        let mut context = Context::new("test-user".to_string());
+        context.active_habits.push(habit);
+        context
+    }
+
+    #[test]
+    fn test_custom_frequency_due() {
+        let planner = Planner::new();
+
+        // Two days in is not yet due for an every-3-days habit
+        assert!(planner.suggest_actions(&every_three_days(48)).is_empty());
+
+        let suggestions = planner.suggest_actions_ranked(&every_three_days(73));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].text, "Consider: Water plants");
+        assert!(suggestions[0].reason.contains("usually every 72 hours"));
+    }
+
+    #[test]
+    fn test_overdue_grace() {
+        let planner = Planner::new().with_overdue_grace(1.25);
+
+        // Slightly overdue is left alone
+        assert!(planner.suggest_actions(&every_three_days(80)).is_empty());
+        assert_eq!(planner.suggest_actions(&every_three_days(91)).len(), 1);
+    }
+
+    #[test]
+    fn test_habit_reminder_evidence() {
+        let planner = Planner::new();
+        let mut habit = Habit::new("Stretch".to_string(), "Morning stretch".to_string(), HabitFrequency::Daily);
+        habit.last_completed = Some(Utc::now() - chrono::Duration::hours(40));
+        let context = Context::new("test-user".to_string()).with_habits(vec![habit.clone()]);
+
+        let intents = planner
+            .habit_reminder_intents(&context, &IntentGenerator::new())
+            .unwrap();
+
+        assert_eq!(intents[0].evidence.len(), 1);
+        assert_eq!(intents[0].evidence[0].source, EvidenceSource::Habit(habit.id));
+        assert!(intents[0].evidence[0].note.starts_with("Last completed"));
+    }
+
+    #[test]
+    fn test_build_plan_evidence() {
+        let planner = Planner::new();
+        let event = Event::new("travel".to_string(), "Flight booked".to_string(), 0.8);
+        let habit = Habit::new("Pack".to_string(), "Pack the night before".to_string(), HabitFrequency::Weekly);
+        let context = Context::new("test-user".to_string())
+            .with_events(vec![event.clone()])
+            .with_habits(vec![habit.clone()]);
+        let llm = FixedLlm(
+            r#"[{"intent_type": "weather.query", "confidence": 0.9, "parameters": {}, "reasoning": "Check weather"}]"#,
+        );
+
+        let plan = planner.build_plan("prepare for my trip", &context, &llm).unwrap();
+        let sources: Vec<EvidenceSource> = plan.steps[0].intent.evidence.iter().map(|e| e.source).collect();
+        assert_eq!(sources, vec![EvidenceSource::Event(event.id), EvidenceSource::Habit(habit.id)]);
+    }
+
+    #[test]
+    fn test_loaded_context_uses_newest_events() {
+        let store = crate::memory::MemoryStore::in_memory().unwrap();
+        let mut ids = Vec::new();
+        for i in 0..15 {
+            let mut event = Event::new("note".to_string(), format!("Note {:02}", i), 0.5);
+            event.timestamp = Utc::now() - chrono::Duration::minutes(60 - i);
+            store.store_event(&event).unwrap();
+            ids.push(event.id);
+        }
+
+        let context = Context::load_from(&store, crate::types::DEFAULT_USER_ID.to_string(), 15).unwrap();
+        let planner = Planner::new();
+
+        let explanation = planner.explain(&context);
+        assert_eq!(explanation.events.len(), 10);
+        assert_eq!(explanation.events[0], ids[14]);
+        assert_eq!(explanation.events[9], ids[5]);
+
+        let summary = planner.build_context_summary(&context);
+        assert!(summary.contains("Note 14"));
+        assert!(!summary.contains("Note 04"));
+    }
 }
@@ -3,124 +3,565 @@
 //! Enforces safety boundaries and permission checks.
 //! The agent prepares and suggests - humans authorize.
 
+use crate::config::PolicyConfig;
 use crate::error::{AgentError, Result};
+use crate::memory::MemoryStore;
 use crate::types::Intent;
-use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use chrono::{DateTime, Duration, Local, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// Permission grant from user
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Permission {
     pub module: String,
     pub actions: Vec<String>,
     pub scope: Vec<String>,
     pub granted_at: DateTime<Utc>,
     pub expires_at: Option<DateTime<Utc>>,
+    /// Local time window `(start, end)` in which the grant applies
+    /// Windows with `start > end` wrap past midnight
+    pub active_hours: Option<(NaiveTime, NaiveTime)>,
+    /// Total number of intents the grant may approve; `None` is unlimited
+    pub max_uses: Option<u32>,
+    /// Intents approved so far under this grant
+    pub uses: u32,
+}
+
+impl Permission {
+    /// Uses left before the grant is depleted, or `None` if unlimited
+    pub fn remaining_uses(&self) -> Option<u32> {
+        self.max_uses.map(|max| max.saturating_sub(self.uses))
+    }
+
+    /// Check if a use quota has been spent
+    pub fn is_depleted(&self) -> bool {
+        self.remaining_uses() == Some(0)
+    }
+
+    /// Check if the grant is unexpired, within its active hours and not depleted
+    fn is_usable(&self, now: DateTime<Utc>, local_time: NaiveTime) -> bool {
+        self.expires_at.map(|exp| exp >= now).unwrap_or(true)
+            && self.is_active_at(local_time)
+            && !self.is_depleted()
+    }
+
+    /// Check if the grant covers an action or one of its sub-actions
+    fn permits(&self, action: &str) -> bool {
+        self.actions
+            .iter()
+            .any(|a| a == action || action.starts_with(&format!("{}.", a)))
+    }
+
+    /// Check if the grant applies at the given local time of day
+    pub fn is_active_at(&self, time: NaiveTime) -> bool {
+        match self.active_hours {
+            None => true,
+            Some((start, end)) if start <= end => time >= start && time < end,
+            Some((start, end)) => time >= start || time < end,
+        }
+    }
+}
+
+/// Sliding window of recent approvals for a rate-limited action
+#[derive(Debug)]
+struct RateLimit {
+    max: u32,
+    per: Duration,
+    approvals: VecDeque<DateTime<Utc>>,
+}
+
+/// Rate limit settings, without the approvals already counted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Action pattern, as passed to `PolicyEngine::set_rate_limit`
+    pub action: String,
+    pub max: u32,
+    /// Window length in milliseconds
+    pub per_ms: i64,
+}
+
+/// Serializable copy of a `PolicyEngine`'s grants and rules
+/// The approval handler and recent approvals counted by rate limits are not
+/// included; restored rate limits start with empty windows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicySnapshot {
+    pub allowed_modules: Vec<String>,
+    /// Grants ordered by module, then in the order they were given
+    pub permissions: Vec<Permission>,
+    pub denies: BTreeMap<String, Vec<String>>,
+    /// Ordered by action
+    pub rate_limits: Vec<RateLimitConfig>,
+}
+
+/// Outcome of checking an intent against standing grants
+enum Grant {
+    /// Allowed by rules; carries the position of the grant that applies
+    /// among its module's grants, if one was needed
+    Standing(Option<usize>),
+    /// No grant applies; the approval handler may still allow it
+    NeedsApproval,
+}
+
+/// Outcome of `PolicyEngine::check_intent_detailed`
+#[derive(Debug, Clone)]
+pub struct PolicyDecision {
+    pub allowed: bool,
+    /// Every rule the intent broke, in the order they were checked
+    pub violations: Vec<String>,
+    /// Standing grant that approved the intent, as charged
+    pub matched_permission: Option<Permission>,
 }
 
+impl PolicyDecision {
+    fn denied(violations: Vec<String>) -> Self {
+        Self {
+            allowed: false,
+            violations,
+            matched_permission: None,
+        }
+    }
+}
+
+/// Callback asked to approve an intent that has no standing permission
+pub type ApprovalHandler = Box<dyn Fn(&Intent) -> bool + Send + Sync>;
+
 /// Policy engine for enforcing safety boundaries
+///
+/// Grants live behind a read-write lock, so one engine can be shared through
+/// an `Arc` and checked from many threads while grants are updated.
 pub struct PolicyEngine {
-    permissions: HashMap<String, Vec<Permission>>,
+    permissions: RwLock<HashMap<String, Vec<Permission>>>,
+    denies: HashMap<String, Vec<String>>,
+    rate_limits: Mutex<HashMap<String, RateLimit>>,
+    approval_handler: Option<ApprovalHandler>,
     allowed_modules: Vec<String>,
 }
 
 impl PolicyEngine {
     pub fn new(allowed_modules: Vec<String>) -> Self {
         Self {
-            permissions: HashMap::new(),
+            permissions: RwLock::new(HashMap::new()),
+            denies: HashMap::new(),
+            rate_limits: Mutex::new(HashMap::new()),
+            approval_handler: None,
             allowed_modules,
         }
     }
 
+    /// Create an engine with the allowed modules and deny rules from a config
+    pub fn from_config(config: &PolicyConfig) -> Self {
+        let mut engine = Self::new(config.allowed_modules.clone());
+        for (module, patterns) in &config.deny {
+            for pattern in patterns {
+                engine.deny(module, pattern);
+            }
+        }
+        engine
+    }
+
+    /// Set a handler consulted when an intent has no standing permission
+    /// Returning `true` approves that single intent without granting a permission.
+    /// Deny rules and allowed modules are still enforced first.
+    pub fn set_approval_handler(&mut self, handler: ApprovalHandler) {
+        self.approval_handler = Some(handler);
+    }
+
+    /// Allow at most `max` approvals of an action within any `per` window
+    /// The action accepts the same patterns as `deny`
+    pub fn set_rate_limit(&mut self, action: &str, max: u32, per: Duration) {
+        let limits = self
+            .rate_limits
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        limits.insert(
+            action.to_string(),
+            RateLimit {
+                max,
+                per,
+                approvals: VecDeque::new(),
+            },
+        );
+    }
+
+    /// Deny actions matching a pattern for a module, overriding any grants
+    /// Patterns are exact actions (`message.send`), prefixes (`message.*`) or `*`
+    pub fn deny(&mut self, module: &str, action_pattern: &str) {
+        self.denies
+            .entry(module.to_string())
+            .or_default()
+            .push(action_pattern.to_string());
+    }
+
+    /// Check if an action matches an explicit deny rule
+    pub fn is_denied(&self, module: &str, action: &str) -> bool {
+        self.denies
+            .get(module)
+            .map(|patterns| patterns.iter().any(|p| Self::matches_pattern(p, action)))
+            .unwrap_or(false)
+    }
+
+    fn matches_pattern(pattern: &str, action: &str) -> bool {
+        if pattern == "*" {
+            return true;
+        }
+
+        match pattern.strip_suffix(".*") {
+            Some(prefix) => action == prefix || action.starts_with(&format!("{}.", prefix)),
+            None => action == pattern || action.starts_with(&format!("{}.", pattern)),
+        }
+    }
+
+    /// Read access to grants; a panic while writing leaves them usable
+    fn permissions(&self) -> RwLockReadGuard<'_, HashMap<String, Vec<Permission>>> {
+        self.permissions.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn permissions_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, Vec<Permission>>> {
+        self.permissions.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
     /// Grant permission for a module and actions
-    pub fn grant_permission(&mut self, permission: Permission) {
+    pub fn grant_permission(&self, permission: Permission) {
         let module = permission.module.clone();
-        self.permissions
+        self.permissions_mut()
             .entry(module)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(permission);
     }
 
     /// Check if an intent is permitted
+    /// Approved intents count towards any matching rate limit and use up one
+    /// use of a quota-limited grant (unlimited grants are preferred)
     pub fn check_intent(&self, intent: &Intent) -> Result<()> {
-        // If no permission required, allow
-        if !intent.requires_permission {
-            return Ok(());
+        match self.check_intent_detailed(intent).violations.into_iter().next() {
+            Some(reason) => Err(AgentError::PolicyViolation(reason)),
+            None => Ok(()),
         }
+    }
 
-        // Check if target module is allowed
-        if let Some(module) = &intent.target_module {
-            if !self.allowed_modules.is_empty() && !self.allowed_modules.contains(module) {
-                return Err(AgentError::PolicyViolation(format!(
-                    "Module '{}' is not in allowed modules list",
-                    module
-                )));
+    /// Check an intent against every rule, listing all violations instead of
+    /// stopping at the first
+    /// Approval has the same effects as `check_intent`. The approval handler
+    /// is only asked when a missing grant would be the sole violation.
+    pub fn check_intent_detailed(&self, intent: &Intent) -> PolicyDecision {
+        let mut violations = Vec::new();
+
+        if let Some(expires_at) = intent.expires_at {
+            if intent.is_expired(Utc::now()) {
+                violations.push(format!(
+                    "Intent '{}' expired at {}",
+                    intent.intent_type,
+                    expires_at.to_rfc3339()
+                ));
             }
+        }
 
-            // Check permissions
-            if let Some(perms) = self.permissions.get(module) {
-                let now = Utc::now();
-                
-                // Check if any permission grants access
-                for perm in perms {
-                    // Check expiration
-                    if let Some(expires) = perm.expires_at {
-                        if expires < now {
-                            continue; // Permission expired
-                        }
-                    }
+        // Look for a grant under the read lock so concurrent checks don't
+        // serialize; only spending a quota needs the write lock
+        let permissions = self.permissions();
+        let mut grant_violations = Vec::new();
+        let grant = self.check_grants(intent, &permissions, &mut grant_violations);
+        let spends_quota = match grant {
+            Grant::Standing(Some(index)) => {
+                Self::grant_at(&permissions, intent, index).is_some_and(|p| p.max_uses.is_some())
+            }
+            _ => false,
+        };
 
-                    // Check if action is permitted
-                    if perm.actions.iter().any(|a| {
-                        a == &intent.intent_type || intent.intent_type.starts_with(&format!("{}.", a))
-                    }) {
-                        return Ok(());
+        if !spends_quota {
+            violations.extend(grant_violations);
+            if let Grant::Standing(index) = grant {
+                let matched = index.and_then(|index| Self::grant_at(&permissions, intent, index).cloned());
+                drop(permissions);
+                return self.approve_standing(intent, violations, || matched);
+            }
+            drop(permissions);
+        } else {
+            // Check again under the write lock, since the quota may have been
+            // used up or the grant revoked in between
+            drop(permissions);
+            let mut permissions = self.permissions_mut();
+            let mut grant_violations = Vec::new();
+            let grant = self.check_grants(intent, &permissions, &mut grant_violations);
+            violations.extend(grant_violations);
+            if let Grant::Standing(index) = grant {
+                return self.approve_standing(intent, violations, || {
+                    let module = intent.target_module.as_deref()?;
+                    let permission = permissions.get_mut(module)?.get_mut(index?)?;
+                    if permission.max_uses.is_some() {
+                        permission.uses += 1;
                     }
-                }
+                    Some(permission.clone())
+                });
             }
+        }
 
-            return Err(AgentError::PolicyViolation(format!(
+        // Ask the user in the moment, without holding the grants lock;
+        // approval is a one-time grant
+        let approved = violations.is_empty()
+            && self.approval_handler.as_ref().is_some_and(|handler| handler(intent));
+        if !approved {
+            violations.push(format!(
                 "No valid permission found for intent type '{}'",
                 intent.intent_type
-            )));
+            ));
+        }
+        violations.extend(self.check_rate_limit(intent, violations.is_empty()));
+
+        if violations.is_empty() {
+            PolicyDecision {
+                allowed: true,
+                violations,
+                matched_permission: None,
+            }
+        } else {
+            PolicyDecision::denied(violations)
+        }
+    }
+
+    /// Approve an intent allowed by standing rules unless a rate limit is
+    /// full; `charge` spends the matched grant, if any, and returns it
+    fn approve_standing(
+        &self,
+        intent: &Intent,
+        mut violations: Vec<String>,
+        charge: impl FnOnce() -> Option<Permission>,
+    ) -> PolicyDecision {
+        violations.extend(self.check_rate_limit(intent, violations.is_empty()));
+        if !violations.is_empty() {
+            return PolicyDecision::denied(violations);
+        }
+
+        PolicyDecision {
+            allowed: true,
+            violations,
+            matched_permission: charge(),
         }
+    }
 
-        Err(AgentError::PolicyViolation(
-            "Intent requires permission but has no target module".to_string(),
-        ))
+    /// The grant `check_grants` picked for `intent`
+    fn grant_at<'a>(
+        permissions: &'a HashMap<String, Vec<Permission>>,
+        intent: &Intent,
+        index: usize,
+    ) -> Option<&'a Permission> {
+        permissions.get(intent.target_module.as_deref()?)?.get(index)
+    }
+
+    /// Check matching rate limits, returning the first full window
+    /// With `record` set, an approval is recorded when every window has room.
+    fn check_rate_limit(&self, intent: &Intent, record: bool) -> Option<String> {
+        let mut limits = self.rate_limits.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Utc::now();
+
+        let mut matching: Vec<&mut RateLimit> = limits
+            .iter_mut()
+            .filter(|(pattern, _)| Self::matches_pattern(pattern, &intent.intent_type))
+            .map(|(_, limit)| limit)
+            .collect();
+
+        for limit in matching.iter_mut() {
+            while let Some(&oldest) = limit.approvals.front() {
+                if now.signed_duration_since(oldest) >= limit.per {
+                    limit.approvals.pop_front();
+                } else {
+                    break;
+                }
+            }
+
+            if limit.approvals.len() >= limit.max as usize {
+                return Some(format!(
+                    "Rate limit exceeded for '{}': at most {} per {} seconds",
+                    intent.intent_type,
+                    limit.max,
+                    limit.per.num_seconds()
+                ));
+            }
+        }
+
+        if record {
+            for limit in matching {
+                limit.approvals.push_back(now);
+            }
+        }
+
+        None
+    }
+
+    /// Check deny rules, allowed modules and grants for an intent,
+    /// adding any violations found
+    fn check_grants(
+        &self,
+        intent: &Intent,
+        permissions: &HashMap<String, Vec<Permission>>,
+        violations: &mut Vec<String>,
+    ) -> Grant {
+        // Explicit deny rules win over everything else
+        if let Some(module) = &intent.target_module {
+            if self.is_denied(module, &intent.intent_type) {
+                violations.push(format!(
+                    "Intent type '{}' is denied for module '{}'",
+                    intent.intent_type, module
+                ));
+            }
+        }
+
+        // If no permission required, allow
+        if !intent.requires_permission {
+            return Grant::Standing(None);
+        }
+
+        let Some(module) = &intent.target_module else {
+            violations.push("Intent requires permission but has no target module".to_string());
+            return Grant::NeedsApproval;
+        };
+
+        // Check if target module is allowed
+        if !self.allowed_modules.is_empty() && !self.allowed_modules.contains(module) {
+            violations.push(format!("Module '{}' is not in allowed modules list", module));
+        }
+
+        // Check permissions
+        if let Some(perms) = permissions.get(module) {
+            let now = Utc::now();
+            let local_time = Local::now().time();
+
+            // Any unexpired, active, non-depleted grant for the action will do,
+            // but spend a quota only if no unlimited grant applies
+            let usable = perms
+                .iter()
+                .enumerate()
+                .filter(|(_, perm)| perm.is_usable(now, local_time) && perm.permits(&intent.intent_type))
+                .min_by_key(|(_, perm)| perm.max_uses.is_some());
+
+            if let Some((index, _)) = usable {
+                return Grant::Standing(Some(index));
+            }
+        }
+
+        Grant::NeedsApproval
+    }
+
+    /// Persist all current permission grants to the memory store
+    pub fn save_to(&self, store: &MemoryStore) -> Result<()> {
+        let permissions: Vec<Permission> = self.permissions().values().flatten().cloned().collect();
+        store.store_permissions(&permissions)
+    }
+
+    /// Load persisted permission grants, skipping any that have expired
+    /// Returns the number of permissions loaded
+    pub fn load_from(&self, store: &MemoryStore) -> Result<usize> {
+        let now = Utc::now();
+        let mut loaded = 0;
+
+        for permission in store.load_permissions()? {
+            if permission.expires_at.map(|exp| exp > now).unwrap_or(true) {
+                self.grant_permission(permission);
+                loaded += 1;
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// Capture grants, deny rules, rate limits and allowed modules
+    pub fn snapshot(&self) -> PolicySnapshot {
+        let permissions = self.permissions();
+        let mut modules: Vec<&String> = permissions.keys().collect();
+        modules.sort();
+
+        let mut rate_limits: Vec<RateLimitConfig> = self
+            .rate_limits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(action, limit)| RateLimitConfig {
+                action: action.clone(),
+                max: limit.max,
+                per_ms: limit.per.num_milliseconds(),
+            })
+            .collect();
+        rate_limits.sort_by(|a, b| a.action.cmp(&b.action));
+
+        PolicySnapshot {
+            allowed_modules: self.allowed_modules.clone(),
+            permissions: modules
+                .into_iter()
+                .flat_map(|module| permissions[module].iter().cloned())
+                .collect(),
+            denies: self
+                .denies
+                .iter()
+                .map(|(module, patterns)| (module.clone(), patterns.clone()))
+                .collect(),
+            rate_limits,
+        }
+    }
+
+    /// Replace grants, deny rules, rate limits and allowed modules with a snapshot
+    /// The approval handler is kept.
+    pub fn restore(&mut self, snapshot: PolicySnapshot) {
+        let permissions = self
+            .permissions
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        permissions.clear();
+        for permission in snapshot.permissions {
+            permissions
+                .entry(permission.module.clone())
+                .or_default()
+                .push(permission);
+        }
+
+        self.denies = snapshot.denies.into_iter().collect();
+        self.allowed_modules = snapshot.allowed_modules;
+
+        self.rate_limits
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        for limit in snapshot.rate_limits {
+            self.set_rate_limit(&limit.action, limit.max, Duration::milliseconds(limit.per_ms));
+        }
     }
 
     /// Revoke all permissions for a module
-    pub fn revoke_module(&mut self, module: &str) {
-        self.permissions.remove(module);
+    pub fn revoke_module(&self, module: &str) {
+        self.permissions_mut().remove(module);
     }
 
     /// Clear expired permissions
-    pub fn clear_expired(&mut self) -> usize {
+    pub fn clear_expired(&self) -> usize {
         let now = Utc::now();
         let mut cleared = 0;
+        let mut permissions = self.permissions_mut();
 
-        for perms in self.permissions.values_mut() {
+        for perms in permissions.values_mut() {
             let original_len = perms.len();
             perms.retain(|p| p.expires_at.map(|exp| exp > now).unwrap_or(true));
             cleared += original_len - perms.len();
         }
 
-        self.permissions.retain(|_, perms| !perms.is_empty());
+        permissions.retain(|_, perms| !perms.is_empty());
 
         cleared
     }
 
     /// Get active permissions for a module
+    /// Depleted grants are left out; see `Permission::remaining_uses` for the rest
     pub fn get_permissions(&self, module: &str) -> Vec<Permission> {
         let now = Utc::now();
         
-        self.permissions
+        self.permissions()
             .get(module)
             .map(|perms| {
                 perms
                     .iter()
                     .filter(|p| p.expires_at.map(|exp| exp > now).unwrap_or(true))
+                    .filter(|p| !p.is_depleted())
                     .cloned()
                     .collect()
             })
@@ -129,23 +570,17 @@ impl PolicyEngine {
 
     /// Check if a specific action is permitted
     pub fn is_action_permitted(&self, module: &str, action: &str) -> bool {
-        let now = Utc::now();
-        
-        if let Some(perms) = self.permissions.get(module) {
-            return perms.iter().any(|p| {
-                // Check not expired
-                if let Some(expires) = p.expires_at {
-                    if expires < now {
-                        return false;
-                    }
-                }
-                
-                // Check action match
-                p.actions.iter().any(|a| a == action || action.starts_with(&format!("{}.", a)))
-            });
+        if self.is_denied(module, action) {
+            return false;
         }
-        
-        false
+
+        let now = Utc::now();
+        let local_time = Local::now().time();
+
+        self.permissions()
+            .get(module)
+            .map(|perms| perms.iter().any(|p| p.is_usable(now, local_time) && p.permits(action)))
+            .unwrap_or(false)
     }
 }
 
@@ -170,7 +605,7 @@ mod tests {
 
     #[test]
     fn test_check_intent_with_permission() {
-        let mut engine = PolicyEngine::new(vec!["device".to_string()]);
+        let engine = PolicyEngine::new(vec!["device".to_string()]);
         
         let permission = Permission {
             module: "device".to_string(),
@@ -178,6 +613,9 @@ mod tests {
             scope: vec![],
             granted_at: Utc::now(),
             expires_at: Some(Utc::now() + Duration::hours(1)),
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
         };
         engine.grant_permission(permission);
 
@@ -211,7 +649,7 @@ mod tests {
 
     #[test]
     fn test_expired_permission() {
-        let mut engine = PolicyEngine::new(vec!["device".to_string()]);
+        let engine = PolicyEngine::new(vec!["device".to_string()]);
         
         let permission = Permission {
             module: "device".to_string(),
@@ -219,6 +657,9 @@ mod tests {
             scope: vec![],
             granted_at: Utc::now() - Duration::hours(2),
             expires_at: Some(Utc::now() - Duration::hours(1)),
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
         };
         engine.grant_permission(permission);
 
@@ -236,7 +677,7 @@ mod tests {
 
     #[test]
     fn test_clear_expired() {
-        let mut engine = PolicyEngine::new(vec!["device".to_string()]);
+        let engine = PolicyEngine::new(vec!["device".to_string()]);
         
         // Add expired permission
         let expired = Permission {
@@ -245,6 +686,9 @@ mod tests {
             scope: vec![],
             granted_at: Utc::now() - Duration::hours(2),
             expires_at: Some(Utc::now() - Duration::hours(1)),
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
         };
         engine.grant_permission(expired);
 
@@ -255,6 +699,9 @@ mod tests {
             scope: vec![],
             granted_at: Utc::now(),
             expires_at: Some(Utc::now() + Duration::hours(1)),
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
         };
         engine.grant_permission(valid);
 
@@ -268,7 +715,7 @@ mod tests {
 
     #[test]
     fn test_revoke_module() {
-        let mut engine = PolicyEngine::new(vec!["device".to_string()]);
+        let engine = PolicyEngine::new(vec!["device".to_string()]);
         
         let permission = Permission {
             module: "device".to_string(),
@@ -276,6 +723,9 @@ mod tests {
             scope: vec![],
             granted_at: Utc::now(),
             expires_at: None,
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
         };
         engine.grant_permission(permission);
 
@@ -285,4 +735,523 @@ mod tests {
         
         assert!(!engine.is_action_permitted("device", "device.control"));
     }
+
+    #[test]
+    fn test_save_and_load_permissions() {
+        let store = MemoryStore::in_memory().unwrap();
+        let engine = PolicyEngine::new(vec!["device".to_string()]);
+
+        engine.grant_permission(Permission {
+            module: "device".to_string(),
+            actions: vec!["device.control".to_string()],
+            scope: vec!["living_room".to_string()],
+            granted_at: Utc::now(),
+            expires_at: Some(Utc::now() + Duration::hours(1)),
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
+        });
+        engine.grant_permission(Permission {
+            module: "device".to_string(),
+            actions: vec!["device.query".to_string()],
+            scope: vec![],
+            granted_at: Utc::now() - Duration::hours(2),
+            expires_at: Some(Utc::now() - Duration::hours(1)),
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
+        });
+        engine.save_to(&store).unwrap();
+
+        let restored = PolicyEngine::new(vec!["device".to_string()]);
+        assert_eq!(restored.load_from(&store).unwrap(), 1);
+
+        let perms = restored.get_permissions("device");
+        assert_eq!(perms.len(), 1);
+        assert_eq!(perms[0].scope, vec!["living_room"]);
+
+        let intent = Intent::new(
+            "device.control".to_string(),
+            0.8,
+            HashMap::new(),
+            "Control device".to_string(),
+        )
+        .with_permission(true)
+        .with_target_module("device".to_string());
+        assert!(restored.check_intent(&intent).is_ok());
+        assert!(!restored.is_action_permitted("device", "device.query"));
+    }
+
+    #[test]
+    fn test_deny_overrides_grant() {
+        let mut engine = PolicyEngine::new(vec!["message".to_string()]);
+
+        engine.grant_permission(Permission {
+            module: "message".to_string(),
+            actions: vec!["message.send".to_string()],
+            scope: vec![],
+            granted_at: Utc::now(),
+            expires_at: None,
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
+        });
+        engine.deny("message", "message.*");
+
+        let intent = Intent::new(
+            "message.send".to_string(),
+            0.9,
+            HashMap::new(),
+            "Send a message".to_string(),
+        )
+        .with_permission(true)
+        .with_target_module("message".to_string());
+
+        let result = engine.check_intent(&intent);
+        assert!(matches!(result, Err(AgentError::PolicyViolation(msg)) if msg.contains("denied")));
+        assert!(!engine.is_action_permitted("message", "message.send"));
+    }
+
+    #[test]
+    fn test_deny_pattern_matching() {
+        let mut engine = PolicyEngine::new(vec![]);
+        engine.deny("email", "email.send");
+
+        assert!(engine.is_denied("email", "email.send"));
+        assert!(!engine.is_denied("email", "email.read"));
+        assert!(!engine.is_denied("message", "email.send"));
+
+        engine.deny("device", "*");
+        assert!(engine.is_denied("device", "device.control"));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut engine = PolicyEngine::new(vec![]);
+        engine.set_rate_limit("notification.send", 2, Duration::milliseconds(200));
+
+        let intent = Intent::new(
+            "notification.send".to_string(),
+            0.9,
+            HashMap::new(),
+            "Notify user".to_string(),
+        );
+
+        assert!(engine.check_intent(&intent).is_ok());
+        assert!(engine.check_intent(&intent).is_ok());
+
+        let result = engine.check_intent(&intent);
+        assert!(matches!(result, Err(AgentError::PolicyViolation(msg)) if msg.contains("Rate limit exceeded")));
+
+        // Other actions are unaffected
+        let other = Intent::new(
+            "weather.query".to_string(),
+            0.9,
+            HashMap::new(),
+            "Weather".to_string(),
+        );
+        assert!(engine.check_intent(&other).is_ok());
+
+        // Window elapses
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        assert!(engine.check_intent(&intent).is_ok());
+    }
+
+    fn device_intent() -> Intent {
+        Intent::new(
+            "device.control".to_string(),
+            0.8,
+            HashMap::new(),
+            "Control device".to_string(),
+        )
+        .with_permission(true)
+        .with_target_module("device".to_string())
+    }
+
+    fn windowed_permission(start: NaiveTime, end: NaiveTime) -> Permission {
+        Permission {
+            module: "device".to_string(),
+            actions: vec!["device.control".to_string()],
+            scope: vec![],
+            granted_at: Utc::now(),
+            expires_at: None,
+            active_hours: Some((start, end)),
+            max_uses: None,
+            uses: 0,
+        }
+    }
+
+    #[test]
+    fn test_active_hours_in_window() {
+        let engine = PolicyEngine::new(vec!["device".to_string()]);
+        let now = Local::now().time();
+        engine.grant_permission(windowed_permission(
+            now - Duration::hours(1),
+            now + Duration::hours(1),
+        ));
+
+        assert!(engine.check_intent(&device_intent()).is_ok());
+        assert!(engine.is_action_permitted("device", "device.control"));
+    }
+
+    #[test]
+    fn test_active_hours_out_of_window() {
+        let engine = PolicyEngine::new(vec!["device".to_string()]);
+        let now = Local::now().time();
+        engine.grant_permission(windowed_permission(
+            now + Duration::hours(1),
+            now + Duration::hours(2),
+        ));
+
+        assert!(engine.check_intent(&device_intent()).is_err());
+        assert!(!engine.is_action_permitted("device", "device.control"));
+    }
+
+    #[test]
+    fn test_active_hours_wrap_midnight() {
+        let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let permission = windowed_permission(time(22), time(6));
+
+        assert!(permission.is_active_at(time(23)));
+        assert!(permission.is_active_at(time(2)));
+        assert!(!permission.is_active_at(time(6)));
+        assert!(!permission.is_active_at(time(12)));
+
+        let daytime = windowed_permission(time(6), time(22));
+        assert!(daytime.is_active_at(time(6)));
+        assert!(!daytime.is_active_at(time(23)));
+    }
+
+    #[test]
+    fn test_approval_handler_approves_device() {
+        let mut engine = PolicyEngine::new(vec![]);
+        engine.set_approval_handler(Box::new(|intent| intent.intent_type.starts_with("device.")));
+
+        assert!(engine.check_intent(&device_intent()).is_ok());
+
+        // One-time approval does not create a standing grant
+        assert!(engine.get_permissions("device").is_empty());
+
+        let message = Intent::new(
+            "message.send".to_string(),
+            0.8,
+            HashMap::new(),
+            "Send message".to_string(),
+        )
+        .with_permission(true)
+        .with_target_module("message".to_string());
+        assert!(engine.check_intent(&message).is_err());
+
+        // Deny rules still win
+        engine.deny("device", "device.*");
+        assert!(engine.check_intent(&device_intent()).is_err());
+    }
+
+    #[test]
+    fn test_approval_handler_denies_everything() {
+        let mut engine = PolicyEngine::new(vec![]);
+        engine.set_approval_handler(Box::new(|_| false));
+
+        assert!(engine.check_intent(&device_intent()).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_checks_and_grants() {
+        use std::sync::Arc;
+
+        let engine = Arc::new(PolicyEngine::new(vec!["device".to_string()]));
+        let grant = || Permission {
+            module: "device".to_string(),
+            actions: vec!["device.control".to_string()],
+            scope: vec![],
+            granted_at: Utc::now(),
+            expires_at: None,
+            active_hours: None,
+            max_uses: None,
+            uses: 0,
+        };
+        let intent = Intent::new(
+            "device.control".to_string(),
+            0.8,
+            HashMap::new(),
+            "Control device".to_string(),
+        )
+        .with_permission(true)
+        .with_target_module("device".to_string());
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let engine = Arc::clone(&engine);
+                let intent = intent.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..200 {
+                        match engine.check_intent(&intent) {
+                            Ok(()) | Err(AgentError::PolicyViolation(_)) => {}
+                            Err(e) => panic!("unexpected error: {}", e),
+                        }
+                        engine.get_permissions("device");
+                    }
+                })
+            })
+            .collect();
+
+        let writer = {
+            let engine = Arc::clone(&engine);
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    engine.grant_permission(grant());
+                    engine.revoke_module("device");
+                    engine.clear_expired();
+                }
+                engine.grant_permission(grant());
+            })
+        };
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        writer.join().unwrap();
+
+        assert_eq!(engine.get_permissions("device").len(), 1);
+        assert!(engine.check_intent(&intent).is_ok());
+    }
+
+    fn device_permission() -> Permission {
+        Permission {
+            active_hours: None,
+            ..windowed_permission(NaiveTime::MIN, NaiveTime::MIN)
+        }
+    }
+
+    fn message_intent() -> Intent {
+        Intent::new(
+            "message.send".to_string(),
+            0.9,
+            HashMap::new(),
+            "Send message".to_string(),
+        )
+        .with_permission(true)
+        .with_target_module("message".to_string())
+    }
+
+    #[test]
+    fn test_quota_permission_depletes() {
+        let engine = PolicyEngine::new(vec![]);
+        engine.grant_permission(Permission {
+            module: "message".to_string(),
+            actions: vec!["message.send".to_string()],
+            scope: vec![],
+            granted_at: Utc::now(),
+            expires_at: None,
+            active_hours: None,
+            max_uses: Some(5),
+            uses: 0,
+        });
+        engine.grant_permission(device_permission());
+
+        for sent in 1..=5 {
+            engine.check_intent(&message_intent()).unwrap();
+            if sent == 2 {
+                assert_eq!(engine.get_permissions("message")[0].remaining_uses(), Some(3));
+            }
+        }
+        assert!(matches!(
+            engine.check_intent(&message_intent()),
+            Err(AgentError::PolicyViolation(_))
+        ));
+        assert!(engine.get_permissions("message").is_empty());
+        assert!(!engine.is_action_permitted("message", "message.send"));
+
+        // The unlimited device grant is untouched
+        for _ in 0..10 {
+            engine.check_intent(&device_intent()).unwrap();
+        }
+        assert_eq!(engine.get_permissions("device")[0].remaining_uses(), None);
+    }
+
+    #[test]
+    fn test_unlimited_grant_preferred_over_quota() {
+        let engine = PolicyEngine::new(vec![]);
+        let quota = Permission {
+            max_uses: Some(1),
+            ..device_permission()
+        };
+        engine.grant_permission(quota);
+        engine.grant_permission(device_permission());
+
+        for _ in 0..3 {
+            engine.check_intent(&device_intent()).unwrap();
+        }
+        let quota = engine
+            .get_permissions("device")
+            .into_iter()
+            .find(|p| p.max_uses.is_some())
+            .unwrap();
+        assert_eq!(quota.remaining_uses(), Some(1));
+    }
+
+    #[test]
+    fn test_quota_persisted() {
+        let store = MemoryStore::in_memory().unwrap();
+        let engine = PolicyEngine::new(vec![]);
+        engine.grant_permission(Permission {
+            max_uses: Some(3),
+            ..device_permission()
+        });
+        engine.check_intent(&device_intent()).unwrap();
+        engine.save_to(&store).unwrap();
+
+        let restored = PolicyEngine::new(vec![]);
+        restored.load_from(&store).unwrap();
+        assert_eq!(restored.get_permissions("device")[0].remaining_uses(), Some(2));
+    }
+
+    #[test]
+    fn test_check_intent_rejects_expired() {
+        let engine = PolicyEngine::new(vec![]);
+        engine.grant_permission(device_permission());
+
+        // No TTL
+        assert!(engine.check_intent(&device_intent()).is_ok());
+
+        // Unexpired
+        let fresh = device_intent().with_ttl(Duration::minutes(5));
+        assert!(engine.check_intent(&fresh).is_ok());
+
+        // Expired
+        let mut stale = device_intent().with_ttl(Duration::minutes(5));
+        stale.created_at = Utc::now() - Duration::minutes(20);
+        stale.expires_at = Some(stale.created_at + Duration::minutes(5));
+        match engine.check_intent(&stale) {
+            Err(AgentError::PolicyViolation(msg)) => assert!(msg.contains("expired")),
+            other => panic!("expected expiry violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_intent_detailed_lists_all_violations() {
+        let mut engine = PolicyEngine::new(vec!["device".to_string()]);
+        engine.deny("message", "message.*");
+        engine.set_rate_limit("message.send", 0, Duration::minutes(1));
+
+        let mut intent = message_intent().with_ttl(Duration::minutes(5));
+        intent.created_at = Utc::now() - Duration::minutes(20);
+        intent.expires_at = Some(intent.created_at + Duration::minutes(5));
+
+        let decision = engine.check_intent_detailed(&intent);
+        assert!(!decision.allowed);
+        assert!(decision.matched_permission.is_none());
+
+        let expected = ["expired", "denied", "allowed modules", "No valid permission", "Rate limit"];
+        assert_eq!(decision.violations.len(), expected.len(), "{:?}", decision.violations);
+        for (violation, fragment) in decision.violations.iter().zip(expected) {
+            assert!(violation.contains(fragment), "{} should mention {}", violation, fragment);
+        }
+
+        // check_intent reports the first of them
+        match engine.check_intent(&intent) {
+            Err(AgentError::PolicyViolation(msg)) => assert_eq!(msg, decision.violations[0]),
+            other => panic!("expected policy violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_intent_detailed_reports_matched_permission() {
+        let engine = PolicyEngine::new(vec![]);
+        engine.grant_permission(Permission {
+            max_uses: Some(2),
+            ..device_permission()
+        });
+
+        let decision = engine.check_intent_detailed(&device_intent());
+        assert!(decision.allowed);
+        assert!(decision.violations.is_empty());
+        let matched = decision.matched_permission.unwrap();
+        assert_eq!(matched.module, "device");
+        assert_eq!(matched.remaining_uses(), Some(1));
+    }
+
+    #[test]
+    fn test_snapshot_restore_round_trip() {
+        let mut engine = PolicyEngine::new(vec!["device".to_string(), "message".to_string()]);
+        engine.grant_permission(device_permission());
+        engine.grant_permission(windowed_permission(
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+        ));
+        engine.grant_permission(Permission {
+            module: "message".to_string(),
+            actions: vec!["message.send".to_string()],
+            max_uses: Some(3),
+            uses: 1,
+            expires_at: Some(Utc::now() + Duration::days(1)),
+            ..device_permission()
+        });
+        engine.deny("device", "device.unlock");
+        engine.set_rate_limit("message.send", 5, Duration::minutes(10));
+
+        let before: Vec<Vec<Permission>> = ["device", "message"]
+            .iter()
+            .map(|module| engine.get_permissions(module))
+            .collect();
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.permissions.len(), 3);
+
+        // Backups survive serialization
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored_snapshot: PolicySnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored_snapshot, snapshot);
+
+        engine.revoke_module("device");
+        engine.revoke_module("message");
+        let mut cleared = PolicyEngine::new(vec![]);
+        cleared.restore(restored_snapshot);
+        engine.restore(cleared.snapshot());
+
+        let after: Vec<Vec<Permission>> = ["device", "message"]
+            .iter()
+            .map(|module| engine.get_permissions(module))
+            .collect();
+        assert_eq!(after, before);
+        assert!(engine.is_denied("device", "device.unlock"));
+        assert_eq!(engine.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn test_unlimited_grant_checked_under_read_lock() {
+        let engine = PolicyEngine::new(vec![]);
+        engine.grant_permission(device_permission());
+
+        // A held read lock would block a check that takes the write lock
+        let _grants = engine.permissions();
+        let (done, finished) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| done.send(engine.check_intent(&device_intent()).is_ok()).unwrap());
+            assert!(finished.recv_timeout(std::time::Duration::from_secs(5)).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_concurrent_checks_spend_quota_exactly() {
+        let engine = PolicyEngine::new(vec![]);
+        engine.grant_permission(Permission {
+            max_uses: Some(5),
+            ..device_permission()
+        });
+
+        let approved = std::sync::atomic::AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        if engine.check_intent(&device_intent()).is_ok() {
+                            approved.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(approved.into_inner(), 5);
+    }
 }
@@ -1,10 +1,43 @@
 //! Core type definitions for the agent
 
-use chrono::{DateTime, Utc};
+use crate::error::{AgentError, Result};
+use crate::memory::MemoryStore;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+/// User id assigned to events and habits that don't name a user
+pub const DEFAULT_USER_ID: &str = "default";
+
+fn default_user_id() -> String {
+    DEFAULT_USER_ID.to_string()
+}
+
+/// Offset-free layouts accepted by `parse_timestamp`, read as UTC
+const NAIVE_TIMESTAMP_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+/// Parse a stored or imported timestamp
+/// Accepts RFC3339, RFC3339 with a space instead of `T`, and
+/// `YYYY-MM-DD HH:MM:SS` without an offset (as written by SQLite's
+/// `datetime()`), which is taken to be UTC.
+pub fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%:z") {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    NAIVE_TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|naive| naive.and_utc())
+        .ok_or_else(|| AgentError::Other(format!("Invalid timestamp: {:?}", value)))
+}
+
 /// Structured intent emitted by the agent
 /// Agent emits intents but NEVER executes actions directly
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +50,15 @@ pub struct Intent {
     pub requires_permission: bool,
     pub target_module: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Relative importance for execution ordering (higher first)
+    #[serde(default)]
+    pub priority: u8,
+    /// Time after which the intent is stale and must not be acted on
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Events and habits that led to the intent
+    #[serde(default)]
+    pub evidence: Vec<EvidenceRef>,
 }
 
 impl Intent {
@@ -35,11 +77,32 @@ impl Intent {
             requires_permission: false,
             target_module: None,
             created_at: Utc::now(),
+            priority: Self::default_priority(confidence, false),
+            expires_at: None,
+            evidence: Vec::new(),
+        }
+    }
+
+    /// Derive a priority from confidence, boosted for permission-gated actions
+    pub fn default_priority(confidence: f32, requires_permission: bool) -> u8 {
+        let base = (confidence.clamp(0.0, 1.0) * 200.0) as u8;
+        if requires_permission {
+            base.saturating_add(50)
+        } else {
+            base
         }
     }
 
+    /// Set whether permission is required, re-deriving the default priority
+    /// Call `with_priority` afterwards to override it
     pub fn with_permission(mut self, requires: bool) -> Self {
         self.requires_permission = requires;
+        self.priority = Self::default_priority(self.confidence, requires);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
         self
     }
 
@@ -47,6 +110,54 @@ impl Intent {
         self.target_module = Some(module);
         self
     }
+
+    pub fn with_evidence(mut self, evidence: Vec<EvidenceRef>) -> Self {
+        self.evidence = evidence;
+        self
+    }
+
+    /// Expire the intent `ttl` after it was created
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.expires_at = Some(self.created_at + ttl);
+        self
+    }
+
+    /// Check if the intent has expired at `now`; intents without a TTL never expire
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires| now >= expires)
+    }
+}
+
+/// What a piece of evidence refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum EvidenceSource {
+    Event(Uuid),
+    Habit(Uuid),
+}
+
+/// Reference to an event or habit that supports an intent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvidenceRef {
+    pub source: EvidenceSource,
+    /// Short explanation of why it matters
+    pub note: String,
+}
+
+impl EvidenceRef {
+    pub fn event(id: Uuid, note: &str) -> Self {
+        Self {
+            source: EvidenceSource::Event(id),
+            note: note.to_string(),
+        }
+    }
+
+    pub fn habit(id: Uuid, note: &str) -> Self {
+        Self {
+            source: EvidenceSource::Habit(id),
+            note: note.to_string(),
+        }
+    }
 }
 
 /// Event in the user's life or system state
@@ -58,6 +169,17 @@ pub struct Event {
     pub importance: f32,
     pub metadata: HashMap<String, serde_json::Value>,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Household member the event belongs to
+    #[serde(default = "default_user_id")]
+    pub user_id: String,
+    /// Integration that produced the event, e.g. `calendar` or `email`
+    #[serde(default)]
+    pub source: Option<String>,
+    /// When the event was soft-deleted; such events are hidden from recall
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl Event {
@@ -69,13 +191,100 @@ impl Event {
             importance,
             metadata: HashMap::new(),
             timestamp: Utc::now(),
+            tags: Vec::new(),
+            user_id: default_user_id(),
+            source: None,
+            deleted_at: None,
         }
     }
 
+    pub fn with_user(mut self, user_id: String) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    pub fn with_source(mut self, source: String) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Create an event with importance guessed by `score_importance`
+    pub fn auto(event_type: &str, description: &str) -> Self {
+        let importance = score_importance(event_type, description);
+        Self::new(event_type.to_string(), description.to_string(), importance)
+    }
+
     pub fn with_metadata(mut self, key: String, value: serde_json::Value) -> Self {
         self.metadata.insert(key, value);
         self
     }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Importance decayed exponentially by age; halves every `half_life`
+    /// Events from the future and non-positive half-lives keep full importance.
+    pub fn decayed_importance(&self, now: DateTime<Utc>, half_life: Duration) -> f32 {
+        let age = now.signed_duration_since(self.timestamp).num_milliseconds();
+        let half_life = half_life.num_milliseconds();
+        if age <= 0 || half_life <= 0 {
+            return self.importance;
+        }
+
+        self.importance * 0.5f32.powf(age as f32 / half_life as f32)
+    }
+}
+
+/// Baseline importance by event type, matched on the lowercased type
+const TYPE_IMPORTANCE: &[(&str, f32)] = &[
+    ("error", 0.8),
+    ("alert", 0.8),
+    ("alarm", 0.8),
+    ("security", 0.8),
+    ("warning", 0.6),
+    ("appointment", 0.6),
+    ("reminder", 0.5),
+    ("habit", 0.5),
+    ("user_input", 0.5),
+    ("sensor", 0.3),
+    ("agent_response", 0.2),
+    ("heartbeat", 0.1),
+];
+
+/// Words in a description that suggest the event matters more than its type implies
+const URGENT_KEYWORDS: &[&str] = &["urgent", "critical", "emergency", "failed", "important", "asap"];
+
+/// Guess an importance score in [0, 1] for an event
+/// Starts from a per-type baseline (0.4 for unknown types), adds 0.1 per
+/// urgent keyword (at most 0.2) and nudges long descriptions up and very
+/// short ones down.
+pub fn score_importance(event_type: &str, description: &str) -> f32 {
+    let event_type = event_type.to_lowercase();
+    let baseline = TYPE_IMPORTANCE
+        .iter()
+        .find(|(name, _)| event_type.contains(name))
+        .map(|(_, score)| *score)
+        .unwrap_or(0.4);
+
+    let description = description.to_lowercase();
+    let keywords = URGENT_KEYWORDS
+        .iter()
+        .filter(|keyword| description.contains(*keyword))
+        .count();
+    let keyword_boost = (keywords as f32 * 0.1).min(0.2);
+
+    let length = description.trim().chars().count();
+    let length_nudge = if length > 100 {
+        0.05
+    } else if length < 10 {
+        -0.05
+    } else {
+        0.0
+    };
+
+    (baseline + keyword_boost + length_nudge).clamp(0.0, 1.0)
 }
 
 /// User habit/routine
@@ -89,7 +298,10 @@ pub struct Habit {
     pub completion_count: u32,
     pub last_completed: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
-    pub variance: Option<f32>, // Statistical variance in timing/completion
+    pub variance: Option<f32>, // Variance of completion intervals, in hours squared
+    /// Household member the habit belongs to
+    #[serde(default = "default_user_id")]
+    pub user_id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,9 +324,15 @@ impl Habit {
             last_completed: None,
             created_at: Utc::now(),
             variance: None,
+            user_id: default_user_id(),
         }
     }
 
+    pub fn with_user(mut self, user_id: String) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
     pub fn complete(&mut self) {
         self.completion_count += 1;
         self.last_completed = Some(Utc::now());
@@ -143,6 +361,46 @@ impl Context {
             timestamp: Utc::now(),
         }
     }
+
+    /// Load the user's recent events and active habits from a memory store
+    /// Events are in chronological order, oldest first, as the planner expects.
+    pub fn load_from(store: &MemoryStore, user_id: String, recent_limit: u32) -> Result<Self> {
+        let memory = store.for_user(&user_id);
+        let mut events = memory.get_recent_events(recent_limit)?;
+        events.reverse();
+        let habits = memory.get_active_habits()?;
+        Ok(Self::new(user_id).with_events(events).with_habits(habits))
+    }
+
+    /// Serialize the context to JSON so a planning run can be replayed
+    pub fn to_snapshot(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Restore a context captured with `to_snapshot`
+    pub fn from_snapshot(snapshot: &str) -> Result<Self> {
+        Ok(serde_json::from_str(snapshot)?)
+    }
+
+    pub fn with_location(mut self, location: String) -> Self {
+        self.current_location = Some(location);
+        self
+    }
+
+    pub fn with_activity(mut self, activity: String) -> Self {
+        self.current_activity = Some(activity);
+        self
+    }
+
+    pub fn with_events(mut self, events: Vec<Event>) -> Self {
+        self.recent_events = events;
+        self
+    }
+
+    pub fn with_habits(mut self, habits: Vec<Habit>) -> Self {
+        self.active_habits = habits;
+        self
+    }
 }
 
 /// Voice transcription result
@@ -176,6 +434,91 @@ impl Default for LlmOptions {
     }
 }
 
+impl LlmOptions {
+    /// Check that every option is within the range providers accept:
+    /// temperature in [0, 2], top_p in [0, 1] and at least one token
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(AgentError::Config(format!(
+                "LLM temperature {} is outside [0, 2]",
+                self.temperature
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.top_p) {
+            return Err(AgentError::Config(format!("LLM top_p {} is outside [0, 1]", self.top_p)));
+        }
+        if self.max_tokens == 0 {
+            return Err(AgentError::Config("LLM max_tokens must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Copy with every option pulled into the range `validate` accepts
+    /// NaN values fall back to the defaults.
+    pub fn clamped(&self) -> LlmOptions {
+        let defaults = Self::default();
+        let bound = |value: f32, min: f32, max: f32, default: f32| {
+            if value.is_nan() {
+                default
+            } else {
+                value.clamp(min, max)
+            }
+        };
+
+        LlmOptions {
+            temperature: bound(self.temperature, 0.0, 2.0, defaults.temperature),
+            max_tokens: self.max_tokens.max(1),
+            top_p: bound(self.top_p, 0.0, 1.0, defaults.top_p),
+            ..self.clone()
+        }
+    }
+}
+
+/// Role of a chat message author
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    /// Wire name used by chat APIs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+/// A single message in a chat conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: String) -> Self {
+        Self { role, content }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content.into())
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content.into())
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content.into())
+    }
+}
+
 /// LLM completion response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmResponse {
@@ -190,3 +533,255 @@ pub struct LlmUsage {
     pub completion_tokens: u32,
     pub total_tokens: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_decayed_importance() {
+        let now = Utc::now();
+        let half_life = Duration::hours(24);
+
+        let mut old = Event::new("note".to_string(), "Old".to_string(), 0.8);
+        old.timestamp = now - half_life;
+        assert!((old.decayed_importance(now, half_life) - 0.4).abs() < 1e-3);
+
+        old.timestamp = now - half_life * 2;
+        assert!((old.decayed_importance(now, half_life) - 0.2).abs() < 1e-3);
+
+        let mut fresh = Event::new("note".to_string(), "Fresh".to_string(), 0.8);
+        fresh.timestamp = now - Duration::seconds(1);
+        assert!(fresh.decayed_importance(now, half_life) > 0.799);
+    }
+
+    #[test]
+    fn test_context_builders() {
+        let context = Context::new("user".to_string())
+            .with_location("kitchen".to_string())
+            .with_activity("cooking".to_string());
+
+        assert_eq!(context.current_location.as_deref(), Some("kitchen"));
+        assert_eq!(context.current_activity.as_deref(), Some("cooking"));
+        assert!(context.recent_events.is_empty());
+    }
+
+    #[test]
+    fn test_context_load_from_store() {
+        let store = MemoryStore::in_memory().unwrap();
+        for i in 0..3 {
+            let mut event = Event::new("note".to_string(), format!("Event {}", i), 0.5);
+            event.timestamp = Utc::now() - Duration::minutes(i);
+            store.store_event(&event).unwrap();
+        }
+        store
+            .store_habit(&Habit::new(
+                "Walk".to_string(),
+                "Evening walk".to_string(),
+                HabitFrequency::Daily,
+            ))
+            .unwrap();
+
+        let context = Context::load_from(&store, DEFAULT_USER_ID.to_string(), 2).unwrap();
+
+        assert_eq!(context.user_id, DEFAULT_USER_ID);
+        // The two newest, oldest first
+        let descriptions: Vec<&str> = context.recent_events.iter().map(|e| e.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["Event 1", "Event 0"]);
+        assert_eq!(context.active_habits.len(), 1);
+        assert_eq!(context.active_habits[0].name, "Walk");
+    }
+
+    #[test]
+    fn test_context_load_from_filters_by_user() {
+        let store = MemoryStore::in_memory().unwrap();
+        store
+            .store_event(&Event::new("note".to_string(), "Alice's".to_string(), 0.5).with_user("alice".to_string()))
+            .unwrap();
+        store
+            .store_event(&Event::new("note".to_string(), "Bob's".to_string(), 0.5).with_user("bob".to_string()))
+            .unwrap();
+        store
+            .store_habit(
+                &Habit::new("Run".to_string(), "Morning run".to_string(), HabitFrequency::Daily)
+                    .with_user("bob".to_string()),
+            )
+            .unwrap();
+
+        let context = Context::load_from(&store, "alice".to_string(), 10).unwrap();
+
+        assert_eq!(context.recent_events.len(), 1);
+        assert_eq!(context.recent_events[0].description, "Alice's");
+        assert!(context.active_habits.is_empty());
+    }
+
+    #[test]
+    fn test_score_importance_by_type() {
+        let error = score_importance("error", "Backup job did not finish");
+        let response = score_importance("agent_response", "Here is the weather forecast");
+        assert!(error > response);
+
+        let unknown = score_importance("note", "Picked up groceries");
+        assert!(response < unknown && unknown < error);
+    }
+
+    #[test]
+    fn test_score_importance_nudges() {
+        let plain = score_importance("note", "Server restarted today");
+        let urgent = score_importance("note", "URGENT: server restart failed");
+        assert!(urgent > plain);
+
+        let terse = score_importance("note", "ok");
+        assert!(terse < plain);
+
+        let maxed = score_importance("alert", &"critical emergency ".repeat(10));
+        assert!(maxed <= 1.0);
+    }
+
+    #[test]
+    fn test_event_auto() {
+        let event = Event::auto("error", "Disk full");
+        assert_eq!(event.event_type, "error");
+        assert_eq!(event.importance, score_importance("error", "Disk full"));
+    }
+
+    #[test]
+    fn test_context_snapshot_round_trip() {
+        let mut habit = Habit::new("Walk".to_string(), "Evening walk".to_string(), HabitFrequency::Daily);
+        habit.last_completed = Some(Utc::now() - Duration::hours(30));
+        let context = Context::new("user".to_string())
+            .with_activity("working".to_string())
+            .with_events(vec![Event::new("note".to_string(), "Lunch".to_string(), 0.4)
+                .with_tags(vec!["food".to_string()])])
+            .with_habits(vec![habit]);
+
+        let snapshot = context.to_snapshot().unwrap();
+        let restored = Context::from_snapshot(&snapshot).unwrap();
+
+        assert_eq!(restored.user_id, context.user_id);
+        assert_eq!(restored.current_activity, context.current_activity);
+        assert_eq!(restored.timestamp, context.timestamp);
+        assert_eq!(restored.recent_events[0].id, context.recent_events[0].id);
+        assert_eq!(restored.recent_events[0].tags, vec!["food".to_string()]);
+        assert_eq!(restored.active_habits[0].last_completed, context.active_habits[0].last_completed);
+        assert_eq!(restored.to_snapshot().unwrap(), snapshot);
+
+        assert!(Context::from_snapshot("{not json").is_err());
+    }
+
+    #[test]
+    fn test_intent_ttl() {
+        let intent = Intent::new("device.control".to_string(), 0.9, HashMap::new(), "Porch light".to_string());
+        let now = intent.created_at;
+        assert!(!intent.is_expired(now + Duration::days(365)));
+
+        let intent = intent.with_ttl(Duration::minutes(5));
+        assert!(!intent.is_expired(now + Duration::minutes(4)));
+        assert!(intent.is_expired(now + Duration::minutes(5)));
+    }
+
+    #[test]
+    fn test_intent_without_expiry_deserializes() {
+        let intent = Intent::new("query.time".to_string(), 0.9, HashMap::new(), "Time".to_string());
+        let mut json = serde_json::to_value(&intent).unwrap();
+        json.as_object_mut().unwrap().remove("expires_at");
+
+        let parsed: Intent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.expires_at, None);
+    }
+
+    #[test]
+    fn test_intent_evidence_serialization() {
+        let intent = Intent::new("query.time".to_string(), 0.9, HashMap::new(), "Time".to_string());
+        let mut json = serde_json::to_value(&intent).unwrap();
+        json.as_object_mut().unwrap().remove("evidence");
+        let parsed: Intent = serde_json::from_value(json).unwrap();
+        assert!(parsed.evidence.is_empty());
+
+        let habit_id = Uuid::new_v4();
+        let intent = intent.with_evidence(vec![EvidenceRef::habit(habit_id, "Due")]);
+        let json = serde_json::to_value(&intent).unwrap();
+        assert_eq!(json["evidence"][0]["source"]["kind"], "habit");
+        let parsed: Intent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.evidence, intent.evidence);
+    }
+
+    #[test]
+    fn test_parse_timestamp_formats() {
+        let expected = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+        assert_eq!(parse_timestamp("2024-01-01T12:00:00Z").unwrap(), expected);
+        assert_eq!(parse_timestamp("2024-01-01T14:00:00+02:00").unwrap(), expected);
+        assert_eq!(parse_timestamp("2024-01-01 12:00:00").unwrap(), expected);
+        assert_eq!(parse_timestamp("2024-01-01 12:00:00+00:00").unwrap(), expected);
+        assert_eq!(parse_timestamp("2024-01-01T12:00:00").unwrap(), expected);
+        assert_eq!(
+            parse_timestamp("2024-01-01 12:00:00.250").unwrap(),
+            expected + Duration::milliseconds(250)
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp_invalid() {
+        for value in ["", "not-a-timestamp", "2024-13-01 12:00:00", "2024-01-01", "12:00:00"] {
+            assert!(matches!(parse_timestamp(value), Err(AgentError::Other(_))), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_llm_options_validate() {
+        assert!(LlmOptions::default().validate().is_ok());
+
+        let edges = LlmOptions {
+            temperature: 2.0,
+            top_p: 0.0,
+            max_tokens: 1,
+            ..LlmOptions::default()
+        };
+        assert!(edges.validate().is_ok());
+
+        for options in [
+            LlmOptions { temperature: 5.0, ..LlmOptions::default() },
+            LlmOptions { top_p: -0.1, ..LlmOptions::default() },
+            LlmOptions { max_tokens: 0, ..LlmOptions::default() },
+            LlmOptions { temperature: f32::NAN, ..LlmOptions::default() },
+        ] {
+            assert!(matches!(options.validate(), Err(AgentError::Config(_))), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn test_llm_options_clamped() {
+        let high = LlmOptions {
+            temperature: 5.0,
+            top_p: 1.5,
+            max_tokens: 0,
+            frequency_penalty: 0.4,
+            ..LlmOptions::default()
+        }
+        .clamped();
+        assert_eq!(high.temperature, 2.0);
+        assert_eq!(high.top_p, 1.0);
+        assert_eq!(high.max_tokens, 1);
+        assert_eq!(high.frequency_penalty, 0.4);
+        assert!(high.validate().is_ok());
+
+        let low = LlmOptions {
+            temperature: -1.0,
+            top_p: -0.5,
+            ..LlmOptions::default()
+        }
+        .clamped();
+        assert_eq!(low.temperature, 0.0);
+        assert_eq!(low.top_p, 0.0);
+        assert_eq!(low.max_tokens, 500);
+
+        let nan = LlmOptions {
+            temperature: f32::NAN,
+            ..LlmOptions::default()
+        }
+        .clamped();
+        assert_eq!(nan.temperature, LlmOptions::default().temperature);
+    }
+}
@@ -5,8 +5,20 @@
 //! - Speech-to-text (whisper.cpp)
 //! - Text-to-speech (Piper TTS)
 
+pub mod porcupine;
+
+use crate::config::VoiceConfig;
 use crate::error::{AgentError, Result};
 use crate::types::VoiceTranscription;
+use std::sync::Mutex;
+
+/// A wake word detection
+#[derive(Debug, Clone, PartialEq)]
+pub struct WakeWordMatch {
+    pub word: String,
+    /// Detector confidence (0.0 - 1.0)
+    pub confidence: f32,
+}
 
 /// Trait for wake word detection
 pub trait WakeWordDetector: Send + Sync {
@@ -14,6 +26,19 @@ pub trait WakeWordDetector: Send + Sync {
     /// Returns true if wake word detected
     fn detect(&self, audio_data: &[f32]) -> Result<bool>;
 
+    /// Detect wake word, reporting which word fired and how confidently
+    /// The default reports the first wake word with full confidence.
+    fn detect_detailed(&self, audio_data: &[f32]) -> Result<Option<WakeWordMatch>> {
+        if !self.detect(audio_data)? {
+            return Ok(None);
+        }
+
+        Ok(Some(WakeWordMatch {
+            word: self.wake_words().into_iter().next().unwrap_or_default(),
+            confidence: 1.0,
+        }))
+    }
+
     /// Get wake word(s) this detector recognizes
     fn wake_words(&self) -> Vec<String>;
 }
@@ -23,6 +48,58 @@ pub trait SpeechToText: Send + Sync {
     /// Transcribe audio to text
     fn transcribe(&self, audio_data: &[u8]) -> Result<VoiceTranscription>;
 
+    /// Transcribe with a preferred language (`None` lets the provider detect it)
+    /// The default implementation ignores the hint.
+    fn transcribe_with_language(
+        &self,
+        audio_data: &[u8],
+        _language: Option<&str>,
+    ) -> Result<VoiceTranscription> {
+        self.transcribe(audio_data)
+    }
+
+    /// Detect the spoken language of `audio_data`
+    /// The default implementation returns the first supported language.
+    fn detect_language(&self, _audio_data: &[u8]) -> Result<String> {
+        self.supported_languages()
+            .into_iter()
+            .next()
+            .ok_or_else(|| AgentError::Voice("No supported languages".to_string()))
+    }
+
+    /// Feed audio incrementally; returns partial results when available and
+    /// the full transcription once `is_final` is set
+    /// The default implementation buffers chunks in `stream_buffer` and only
+    /// emits on the final chunk.
+    fn transcribe_stream(&self, chunk: &[u8], is_final: bool) -> Result<Option<VoiceTranscription>> {
+        let Some(buffer) = self.stream_buffer() else {
+            if is_final {
+                return self.transcribe(chunk).map(Some);
+            }
+            return Err(AgentError::Voice(
+                "This speech-to-text provider does not support streaming".to_string(),
+            ));
+        };
+
+        let mut buffer = buffer
+            .lock()
+            .map_err(|_| AgentError::Voice("Stream buffer lock poisoned".to_string()))?;
+        buffer.extend_from_slice(chunk);
+
+        if !is_final {
+            return Ok(None);
+        }
+
+        let audio = std::mem::take(&mut *buffer);
+        drop(buffer);
+        self.transcribe(&audio).map(Some)
+    }
+
+    /// Buffer used by the default `transcribe_stream`
+    fn stream_buffer(&self) -> Option<&Mutex<Vec<u8>>> {
+        None
+    }
+
     /// Get supported languages
     fn supported_languages(&self) -> Vec<String>;
 }
@@ -32,6 +109,15 @@ pub trait TextToSpeech: Send + Sync {
     /// Synthesize text to audio
     fn speak(&self, text: &str, voice: Option<&str>) -> Result<Vec<u8>>;
 
+    /// Synthesize text, handing audio to `on_chunk` as it is produced so
+    /// playback can start before synthesis finishes
+    /// The default implementation delivers the whole `speak` output as one chunk.
+    fn speak_stream(&self, text: &str, voice: Option<&str>, on_chunk: &mut dyn FnMut(&[u8])) -> Result<()> {
+        let audio = self.speak(text, voice)?;
+        on_chunk(&audio);
+        Ok(())
+    }
+
     /// Get available voices
     fn available_voices(&self) -> Vec<String>;
 }
@@ -68,14 +154,27 @@ impl WakeWordDetector for MockWakeWordDetector {
 /// Mock speech-to-text for testing
 pub struct MockSpeechToText {
     languages: Vec<String>,
+    stream: Mutex<Vec<u8>>,
+    stream_chunks: Mutex<usize>,
+    partial_every: usize,
 }
 
 impl MockSpeechToText {
     pub fn new() -> Self {
+        Self::with_languages(vec!["en".to_string()])
+    }
+
+    /// Mock supporting the given languages; the first is reported as detected
+    pub fn with_languages(languages: Vec<String>) -> Self {
         Self {
-            languages: vec!["en".to_string()],
+            languages,
+            stream: Mutex::new(Vec::new()),
+            stream_chunks: Mutex::new(0),
+            partial_every: 3,
         }
     }
+
+    const TEXT: &'static str = "This is a mock transcription";
 }
 
 impl Default for MockSpeechToText {
@@ -86,20 +185,77 @@ impl Default for MockSpeechToText {
 
 impl SpeechToText for MockSpeechToText {
     fn transcribe(&self, audio_data: &[u8]) -> Result<VoiceTranscription> {
+        self.transcribe_with_language(audio_data, None)
+    }
+
+    /// Reports the requested language when it is supported
+    fn transcribe_with_language(
+        &self,
+        audio_data: &[u8],
+        language: Option<&str>,
+    ) -> Result<VoiceTranscription> {
+        let language = match language {
+            Some(lang) if self.languages.iter().any(|l| l == lang) => lang.to_string(),
+            Some(lang) => {
+                return Err(AgentError::Voice(format!("Unsupported language: {}", lang)))
+            }
+            None => self.detect_language(audio_data)?,
+        };
+
         // Mock implementation returns fixed transcription
         Ok(VoiceTranscription {
-            text: "This is a mock transcription".to_string(),
+            text: Self::TEXT.to_string(),
             confidence: 0.95,
-            language: "en".to_string(),
+            language,
             duration_ms: audio_data.len() as u32,
         })
     }
 
+    /// Emits a growing prefix of the mock text every few chunks
+    fn transcribe_stream(&self, chunk: &[u8], is_final: bool) -> Result<Option<VoiceTranscription>> {
+        let mut buffer = self
+            .stream
+            .lock()
+            .map_err(|_| AgentError::Voice("Stream buffer lock poisoned".to_string()))?;
+        let mut chunks = self
+            .stream_chunks
+            .lock()
+            .map_err(|_| AgentError::Voice("Stream buffer lock poisoned".to_string()))?;
+
+        buffer.extend_from_slice(chunk);
+        *chunks += 1;
+
+        if is_final {
+            let audio = std::mem::take(&mut *buffer);
+            *chunks = 0;
+            return self.transcribe(&audio).map(Some);
+        }
+
+        if *chunks % self.partial_every != 0 {
+            return Ok(None);
+        }
+
+        let words = *chunks / self.partial_every;
+        Ok(Some(VoiceTranscription {
+            text: Self::TEXT.split(' ').take(words).collect::<Vec<_>>().join(" "),
+            confidence: 0.5,
+            language: self.detect_language(&buffer)?,
+            duration_ms: buffer.len() as u32,
+        }))
+    }
+
+    fn stream_buffer(&self) -> Option<&Mutex<Vec<u8>>> {
+        Some(&self.stream)
+    }
+
     fn supported_languages(&self) -> Vec<String> {
         self.languages.clone()
     }
 }
 
+/// Chunk size used when streaming synthesized audio
+const STREAM_CHUNK_BYTES: usize = 4096;
+
 /// Mock text-to-speech for testing
 pub struct MockTextToSpeech {
     voices: Vec<String>,
@@ -126,6 +282,14 @@ impl TextToSpeech for MockTextToSpeech {
         Ok(vec![0u8; text.len() * 100])
     }
 
+    /// Emits the `speak` buffer in fixed-size chunks
+    fn speak_stream(&self, text: &str, voice: Option<&str>, on_chunk: &mut dyn FnMut(&[u8])) -> Result<()> {
+        for chunk in self.speak(text, voice)?.chunks(STREAM_CHUNK_BYTES) {
+            on_chunk(chunk);
+        }
+        Ok(())
+    }
+
     fn available_voices(&self) -> Vec<String> {
         self.voices.clone()
     }
@@ -137,6 +301,12 @@ impl TextToSpeech for MockTextToSpeech {
 pub struct SimpleWakeWordDetector {
     wake_words: Vec<String>,
     threshold: f32,
+    /// Multiple of ambient RMS that counts as speech
+    noise_multiplier: f32,
+    /// Rolling ambient RMS used by `detect_adaptive`
+    noise_floor: Option<f32>,
+    /// Measure energy in the speech band only
+    bandpass: bool,
 }
 
 impl SimpleWakeWordDetector {
@@ -146,20 +316,176 @@ impl SimpleWakeWordDetector {
         Self {
             wake_words,
             threshold,
+            noise_multiplier: 3.0,
+            noise_floor: None,
+            bandpass: false,
+        }
+    }
+
+    /// Create a detector with the wake words, threshold and filtering from a config
+    pub fn from_config(config: &VoiceConfig) -> Self {
+        Self::new(config.wake_words.clone(), config.wake_threshold).with_bandpass(config.bandpass)
+    }
+
+    /// Filter audio to the speech band (300 - 3400 Hz) before measuring
+    /// energy, so hum and rumble don't trigger detection. Expects 16kHz audio.
+    pub fn with_bandpass(mut self, enabled: bool) -> Self {
+        self.bandpass = enabled;
+        self
+    }
+
+    /// Current detection threshold
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Current rolling noise floor, if any has been measured
+    pub fn noise_floor(&self) -> Option<f32> {
+        self.noise_floor
+    }
+
+    /// Set the threshold to a multiple of the ambient noise level
+    /// `ambient` should be a recording of the room without speech.
+    pub fn calibrate(&mut self, ambient: &[f32]) {
+        let ambient_energy = self.calculate_energy(ambient);
+        self.noise_floor = Some(ambient_energy);
+        self.threshold = (ambient_energy * self.noise_multiplier).max(MIN_WAKE_THRESHOLD);
+    }
+
+    /// Detect while tracking the room's noise floor
+    /// Quiet buffers slowly pull the floor towards the current ambient level,
+    /// and speech must exceed both the threshold and a multiple of the floor.
+    pub fn detect_adaptive(&mut self, audio_data: &[f32]) -> Result<bool> {
+        let energy = self.calculate_energy(audio_data);
+        let adaptive_threshold = self
+            .noise_floor
+            .map(|floor| (floor * self.noise_multiplier).max(MIN_WAKE_THRESHOLD))
+            .unwrap_or(self.threshold);
+
+        let detected = energy > adaptive_threshold;
+        if !detected {
+            self.noise_floor = Some(match self.noise_floor {
+                Some(floor) => floor * (1.0 - NOISE_FLOOR_ALPHA) + energy * NOISE_FLOOR_ALPHA,
+                None => energy,
+            });
         }
+
+        Ok(detected)
     }
 
     /// Calculate RMS (Root Mean Square) energy of audio buffer
     fn calculate_energy(&self, audio_data: &[f32]) -> f32 {
-        if audio_data.is_empty() {
-            return 0.0;
+        if self.bandpass {
+            rms_energy(&speech_bandpass(audio_data, WHISPER_SAMPLE_RATE))
+        } else {
+            rms_energy(audio_data)
         }
-        
-        let sum: f32 = audio_data.iter().map(|x| x * x).sum();
-        (sum / audio_data.len() as f32).sqrt()
     }
 }
 
+/// Lowest threshold calibration will set, so digital silence doesn't trigger
+const MIN_WAKE_THRESHOLD: f32 = 0.01;
+
+/// Weight of each new quiet buffer in the rolling noise floor
+const NOISE_FLOOR_ALPHA: f32 = 0.1;
+
+/// RMS (Root Mean Square) energy of an audio buffer
+pub fn rms_energy(audio_data: &[f32]) -> f32 {
+    if audio_data.is_empty() {
+        return 0.0;
+    }
+
+    let sum: f32 = audio_data.iter().map(|x| x * x).sum();
+    (sum / audio_data.len() as f32).sqrt()
+}
+
+/// Edges of the telephone speech band
+const SPEECH_BAND_LOW_HZ: f32 = 300.0;
+const SPEECH_BAND_HIGH_HZ: f32 = 3400.0;
+
+/// Second-order IIR filter using the RBJ audio EQ cookbook coefficients
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    /// High-pass filter; `q` of `FRAC_1_SQRT_2` gives a Butterworth response
+    pub fn high_pass(sample_rate: u32, cutoff_hz: f32, q: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, cutoff_hz, q);
+        Self::normalized(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Low-pass filter; `q` of `FRAC_1_SQRT_2` gives a Butterworth response
+    pub fn low_pass(sample_rate: u32, cutoff_hz: f32, q: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, cutoff_hz, q);
+        Self::normalized(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    fn prewarp(sample_rate: u32, cutoff_hz: f32, q: f32) -> (f32, f32) {
+        let w0 = 2.0 * std::f32::consts::PI * cutoff_hz / sample_rate as f32;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    /// Filter one sample
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Keep only the 300 - 3400 Hz speech band of `samples`
+pub fn speech_bandpass(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    let q = std::f32::consts::FRAC_1_SQRT_2;
+    let mut high_pass = Biquad::high_pass(sample_rate, SPEECH_BAND_LOW_HZ, q);
+    let mut low_pass = Biquad::low_pass(sample_rate, SPEECH_BAND_HIGH_HZ, q);
+
+    samples
+        .iter()
+        .map(|&s| low_pass.process(high_pass.process(s)))
+        .collect()
+}
+
 impl Default for SimpleWakeWordDetector {
     fn default() -> Self {
         Self::new(vec!["hey agent".to_string()], 0.1)
@@ -168,12 +494,25 @@ impl Default for SimpleWakeWordDetector {
 
 impl WakeWordDetector for SimpleWakeWordDetector {
     fn detect(&self, audio_data: &[f32]) -> Result<bool> {
+        Ok(self.detect_detailed(audio_data)?.is_some())
+    }
+
+    /// Reports the first configured wake word, since an energy gate can't
+    /// tell words apart; confidence is the clamped RMS energy
+    fn detect_detailed(&self, audio_data: &[f32]) -> Result<Option<WakeWordMatch>> {
         // Simple energy-based detection
         // In production, this would use actual wake word recognition
         let energy = self.calculate_energy(audio_data);
         
         // Detect if energy exceeds threshold
-        Ok(energy > self.threshold)
+        if energy <= self.threshold {
+            return Ok(None);
+        }
+
+        Ok(Some(WakeWordMatch {
+            word: self.wake_words.first().cloned().unwrap_or_default(),
+            confidence: energy.min(1.0),
+        }))
     }
 
     fn wake_words(&self) -> Vec<String> {
@@ -181,26 +520,334 @@ impl WakeWordDetector for SimpleWakeWordDetector {
     }
 }
 
-/// Whisper.cpp STT provider (placeholder for actual integration)
+/// Energy-based voice activity detector
+/// Speech starts when a frame's RMS rises above `high_threshold` and ends
+/// once it falls below `low_threshold`, so brief dips don't split a segment.
+pub struct VoiceActivityDetector {
+    high_threshold: f32,
+    low_threshold: f32,
+    frame_ms: u32,
+    min_segment_ms: u32,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(high_threshold: f32, low_threshold: f32) -> Self {
+        Self {
+            high_threshold,
+            low_threshold: low_threshold.min(high_threshold),
+            frame_ms: 20,
+            min_segment_ms: 100,
+        }
+    }
+
+    /// Length of the analysis frames (default 20ms)
+    pub fn with_frame_ms(mut self, frame_ms: u32) -> Self {
+        self.frame_ms = frame_ms.max(1);
+        self
+    }
+
+    /// Drop segments shorter than this (default 100ms)
+    pub fn with_min_segment_ms(mut self, min_segment_ms: u32) -> Self {
+        self.min_segment_ms = min_segment_ms;
+        self
+    }
+
+    /// Speech regions as `(start, end)` sample indices, end exclusive
+    pub fn segments(&self, audio: &[f32], sample_rate: u32) -> Vec<(usize, usize)> {
+        let frame_len = ((sample_rate as u64 * self.frame_ms as u64 / 1000) as usize).max(1);
+        let min_len = (sample_rate as u64 * self.min_segment_ms as u64 / 1000) as usize;
+
+        let mut segments = Vec::new();
+        let mut start: Option<usize> = None;
+
+        for (i, frame) in audio.chunks(frame_len).enumerate() {
+            let energy = rms_energy(frame);
+            let frame_start = i * frame_len;
+
+            match start {
+                None if energy > self.high_threshold => start = Some(frame_start),
+                Some(s) if energy < self.low_threshold => {
+                    if frame_start - s >= min_len {
+                        segments.push((s, frame_start));
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        // Speech running to the end of the buffer
+        if let Some(s) = start {
+            if audio.len() - s >= min_len {
+                segments.push((s, audio.len()));
+            }
+        }
+
+        segments
+    }
+}
+
+impl Default for VoiceActivityDetector {
+    fn default() -> Self {
+        Self::new(0.1, 0.05)
+    }
+}
+
+/// Resample mono audio from `from_rate` to `to_rate` using linear interpolation
+/// Suitable for speech; no anti-aliasing filter is applied when downsampling.
+pub fn resample(input: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if input.is_empty() || from_rate == 0 || to_rate == 0 {
+        return Vec::new();
+    }
+    if from_rate == to_rate {
+        return input.to_vec();
+    }
+
+    let output_len = (input.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let step = from_rate as f64 / to_rate as f64;
+
+    (0..output_len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let frac = (position - index as f64) as f32;
+
+            let current = input[index.min(input.len() - 1)];
+            let next = input[(index + 1).min(input.len() - 1)];
+            current + (next - current) * frac
+        })
+        .collect()
+}
+
+/// Sample rate expected by Whisper models
+pub const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Decode 16-bit little-endian PCM into `f32` samples in [-1.0, 1.0]
+/// A RIFF/WAVE header is skipped if present.
+pub fn decode_pcm16(audio_data: &[u8]) -> Vec<f32> {
+    wav_data_chunk(audio_data)
+        .unwrap_or(audio_data)
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+        .collect()
+}
+
+/// Encode `f32` samples in [-1.0, 1.0] as 16-bit little-endian PCM
+/// Out-of-range samples are clipped.
+pub fn encode_pcm16(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes())
+        .collect()
+}
+
+/// Wrap raw 16-bit little-endian mono PCM in a WAV container
+pub fn pcm16_to_wav(pcm: &[u8], sample_rate: u32) -> Vec<u8> {
+    let mut wav = wav_header(pcm.len() as u32, sample_rate);
+    wav.extend_from_slice(pcm);
+    wav
+}
+
+/// 44-byte header for 16-bit mono PCM WAV with `data_len` bytes of samples
+/// Pass `u32::MAX` when the length isn't known, as for a streamed file.
+fn wav_header(data_len: u32, sample_rate: u32) -> Vec<u8> {
+    let mut wav = Vec::with_capacity(44);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&data_len.saturating_add(36).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    // fmt chunk: PCM, mono, 16-bit
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    wav
+}
+
+/// Locate the `data` chunk of a RIFF/WAVE buffer
+fn wav_data_chunk(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let start = offset + 8;
+        if id == b"data" {
+            return Some(&bytes[start..(start + size).min(bytes.len())]);
+        }
+        // Chunks are padded to an even length
+        offset = start + size + (size % 2);
+    }
+
+    None
+}
+
+/// Whisper.cpp STT provider
+/// Real transcription requires the `whisper` feature
 pub struct WhisperSttProvider {
     model_path: String,
+    stream: Mutex<Vec<u8>>,
+    #[cfg(feature = "whisper")]
+    context: Mutex<Option<std::sync::Arc<whisper_rs::WhisperContext>>>,
 }
 
 impl WhisperSttProvider {
     pub fn new(model_path: String) -> Self {
-        Self { model_path }
+        Self {
+            model_path,
+            stream: Mutex::new(Vec::new()),
+            #[cfg(feature = "whisper")]
+            context: Mutex::new(None),
+        }
+    }
+
+    /// Path to the Whisper model file
+    pub fn model_path(&self) -> &str {
+        &self.model_path
+    }
+
+    /// Load the model on first use and reuse it afterwards
+    #[cfg(feature = "whisper")]
+    fn context(&self) -> Result<std::sync::Arc<whisper_rs::WhisperContext>> {
+        let mut cached = self
+            .context
+            .lock()
+            .map_err(|_| AgentError::Voice("Whisper model lock poisoned".to_string()))?;
+
+        if let Some(context) = cached.as_ref() {
+            return Ok(context.clone());
+        }
+
+        if !std::path::Path::new(&self.model_path).exists() {
+            return Err(AgentError::Voice(format!(
+                "Whisper model not found at {}",
+                self.model_path
+            )));
+        }
+
+        let context = whisper_rs::WhisperContext::new_with_params(
+            &self.model_path,
+            whisper_rs::WhisperContextParameters::default(),
+        )
+        .map_err(|e| AgentError::Voice(format!("Failed to load Whisper model: {}", e)))?;
+        let context = std::sync::Arc::new(context);
+        *cached = Some(context.clone());
+
+        Ok(context)
     }
 }
 
 impl SpeechToText for WhisperSttProvider {
+    /// Transcribe 16kHz mono 16-bit PCM (raw or WAV)
+    #[cfg(feature = "whisper")]
+    fn transcribe(&self, audio_data: &[u8]) -> Result<VoiceTranscription> {
+        self.transcribe_with_language(audio_data, None)
+    }
+
+    #[cfg(feature = "whisper")]
+    fn transcribe_with_language(
+        &self,
+        audio_data: &[u8],
+        language: Option<&str>,
+    ) -> Result<VoiceTranscription> {
+        use whisper_rs::{FullParams, SamplingStrategy};
+
+        let voice_err = |what: &str, e: whisper_rs::WhisperError| {
+            AgentError::Voice(format!("Whisper {} failed: {}", what, e))
+        };
+
+        let samples = decode_pcm16(audio_data);
+        if samples.is_empty() {
+            return Err(AgentError::Voice("No audio samples to transcribe".to_string()));
+        }
+
+        let context = self.context()?;
+        let mut state = context
+            .create_state()
+            .map_err(|e| voice_err("state creation", e))?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(language.unwrap_or("auto")));
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+
+        state
+            .full(params, &samples)
+            .map_err(|e| voice_err("transcription", e))?;
+
+        let mut text = String::new();
+        let mut prob_sum = 0.0f32;
+        let mut token_count = 0u32;
+        let segments = state
+            .full_n_segments()
+            .map_err(|e| voice_err("segment count", e))?;
+        for segment in 0..segments {
+            text.push_str(
+                &state
+                    .full_get_segment_text(segment)
+                    .map_err(|e| voice_err("segment text", e))?,
+            );
+
+            let tokens = state.full_n_tokens(segment).unwrap_or(0);
+            for token in 0..tokens {
+                if let Ok(p) = state.full_get_token_prob(segment, token) {
+                    prob_sum += p;
+                    token_count += 1;
+                }
+            }
+        }
+
+        let language = state
+            .full_lang_id_from_state()
+            .ok()
+            .and_then(whisper_rs::get_lang_str)
+            .unwrap_or("en")
+            .to_string();
+
+        Ok(VoiceTranscription {
+            text: text.trim().to_string(),
+            confidence: if token_count > 0 {
+                prob_sum / token_count as f32
+            } else {
+                0.0
+            },
+            language,
+            duration_ms: (samples.len() as u64 * 1000 / WHISPER_SAMPLE_RATE as u64) as u32,
+        })
+    }
+
+    #[cfg(not(feature = "whisper"))]
     fn transcribe(&self, _audio_data: &[u8]) -> Result<VoiceTranscription> {
-        // TODO: Integrate with whisper.cpp
         Err(AgentError::Voice(
-            "Whisper.cpp integration not yet implemented. Use MockSpeechToText for testing."
+            "Whisper support is not enabled. Rebuild with the `whisper` feature or use MockSpeechToText for testing."
                 .to_string(),
         ))
     }
 
+    /// Whisper reports the detected language as part of transcription
+    #[cfg(feature = "whisper")]
+    fn detect_language(&self, audio_data: &[u8]) -> Result<String> {
+        Ok(self.transcribe_with_language(audio_data, None)?.language)
+    }
+
+    fn stream_buffer(&self) -> Option<&Mutex<Vec<u8>>> {
+        Some(&self.stream)
+    }
+
     fn supported_languages(&self) -> Vec<String> {
         // Whisper supports many languages
         vec!["en", "es", "fr", "de", "it", "pt", "nl", "pl", "ru", "zh"]
@@ -210,29 +857,214 @@ impl SpeechToText for WhisperSttProvider {
     }
 }
 
-/// Piper TTS provider (placeholder for actual integration)
+/// Piper TTS provider
+/// Synthesis shells out to the `piper` executable and requires the `piper` feature
 pub struct PiperTtsProvider {
     model_path: String,
+    binary: String,
 }
 
 impl PiperTtsProvider {
+    /// `model_path` points at a voice model such as `voices/en_US-lessac-medium.onnx`
     pub fn new(model_path: String) -> Self {
-        Self { model_path }
+        Self {
+            model_path,
+            binary: "piper".to_string(),
+        }
+    }
+
+    /// Use a specific `piper` executable instead of the one on `PATH`
+    pub fn with_binary(mut self, binary: String) -> Self {
+        self.binary = binary;
+        self
+    }
+
+    /// Path to the Piper voice model file
+    pub fn model_path(&self) -> &str {
+        &self.model_path
+    }
+
+    fn voice_dir(&self) -> &std::path::Path {
+        std::path::Path::new(&self.model_path)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."))
+    }
+
+    /// Model file for `voice`, or the default model
+    #[cfg(feature = "piper")]
+    fn resolve_model(&self, voice: Option<&str>) -> Result<std::path::PathBuf> {
+        let model = match voice {
+            Some(voice) => self.voice_dir().join(format!("{}.onnx", voice)),
+            None => std::path::PathBuf::from(&self.model_path),
+        };
+
+        if !model.exists() {
+            return Err(AgentError::Voice(format!(
+                "Piper voice model not found at {}",
+                model.display()
+            )));
+        }
+
+        Ok(model)
+    }
+
+    /// Read the output sample rate from the voice's `.onnx.json` config
+    #[cfg(feature = "piper")]
+    fn sample_rate(model: &std::path::Path) -> u32 {
+        let config = format!("{}.json", model.display());
+        std::fs::read_to_string(config)
+            .ok()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+            .and_then(|v| v["audio"]["sample_rate"].as_u64())
+            .map(|rate| rate as u32)
+            .unwrap_or(22_050)
+    }
+
+    /// Start Piper on `text` with `model`, writing raw PCM to its stdout
+    /// stderr is drained on its own thread so a chatty Piper can't fill the
+    /// pipe and stall while stdout is being read.
+    #[cfg(feature = "piper")]
+    fn spawn_piper(&self, model: &std::path::Path, text: &str) -> Result<PiperProcess> {
+        use std::io::{Read, Write};
+        use std::process::{Command, Stdio};
+
+        let mut child = Command::new(&self.binary)
+            .arg("--model")
+            .arg(model)
+            .arg("--output_raw")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| AgentError::Voice(format!("Failed to start {}: {}", self.binary, e)))?;
+
+        let mut stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| AgentError::Voice("Piper stderr unavailable".to_string()))?;
+        let stderr = std::thread::spawn(move || {
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message);
+            message
+        });
+
+        // Dropping stdin after writing closes it so Piper starts synthesizing
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| AgentError::Voice("Piper stdin unavailable".to_string()))?
+            .write_all(text.as_bytes())?;
+
+        Ok(PiperProcess { child, stderr })
+    }
+}
+
+/// A running `piper` process
+#[cfg(feature = "piper")]
+struct PiperProcess {
+    child: std::process::Child,
+    stderr: std::thread::JoinHandle<String>,
+}
+
+#[cfg(feature = "piper")]
+impl PiperProcess {
+    fn stdout(&mut self) -> Result<std::process::ChildStdout> {
+        self.child
+            .stdout
+            .take()
+            .ok_or_else(|| AgentError::Voice("Piper stdout unavailable".to_string()))
+    }
+
+    /// Wait for Piper to exit, failing with its stderr if it didn't succeed
+    fn finish(mut self) -> Result<()> {
+        let status = self.child.wait()?;
+        let stderr = self.stderr.join().unwrap_or_default();
+        if !status.success() {
+            return Err(AgentError::Voice(format!(
+                "Piper synthesis failed: {}",
+                stderr.trim()
+            )));
+        }
+
+        Ok(())
     }
 }
 
 impl TextToSpeech for PiperTtsProvider {
+    /// Synthesize `text` to 16-bit mono PCM WAV bytes
+    #[cfg(feature = "piper")]
+    fn speak(&self, text: &str, voice: Option<&str>) -> Result<Vec<u8>> {
+        use std::io::Read;
+
+        let model = self.resolve_model(voice)?;
+        let mut piper = self.spawn_piper(&model, text)?;
+
+        let mut pcm = Vec::new();
+        piper.stdout()?.read_to_end(&mut pcm)?;
+        piper.finish()?;
+        if pcm.is_empty() {
+            return Err(AgentError::Voice("Piper produced no audio".to_string()));
+        }
+
+        Ok(pcm16_to_wav(&pcm, Self::sample_rate(&model)))
+    }
+
+    /// Stream 16-bit mono PCM WAV as Piper produces it
+    /// The first chunk starts with a WAV header whose length fields are
+    /// `u32::MAX`, since the length isn't known up front; apart from those
+    /// two fields the joined chunks equal the output of `speak`.
+    #[cfg(feature = "piper")]
+    fn speak_stream(&self, text: &str, voice: Option<&str>, on_chunk: &mut dyn FnMut(&[u8])) -> Result<()> {
+        use std::io::Read;
+
+        let model = self.resolve_model(voice)?;
+        let mut piper = self.spawn_piper(&model, text)?;
+
+        let mut stdout = piper.stdout()?;
+        let mut buf = vec![0u8; STREAM_CHUNK_BYTES];
+        let mut produced = false;
+        loop {
+            let n = stdout.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            if !produced {
+                on_chunk(&wav_header(u32::MAX, Self::sample_rate(&model)));
+                produced = true;
+            }
+            on_chunk(&buf[..n]);
+        }
+
+        piper.finish()?;
+        if !produced {
+            return Err(AgentError::Voice("Piper produced no audio".to_string()));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "piper"))]
     fn speak(&self, _text: &str, _voice: Option<&str>) -> Result<Vec<u8>> {
-        // TODO: Integrate with Piper TTS
         Err(AgentError::Voice(
-            "Piper TTS integration not yet implemented. Use MockTextToSpeech for testing."
+            "Piper support is not enabled. Rebuild with the `piper` feature or use MockTextToSpeech for testing."
                 .to_string(),
         ))
     }
 
+    /// Voices are the `.onnx` models stored next to `model_path`
     fn available_voices(&self) -> Vec<String> {
-        // Piper has various voices
-        vec!["en_US-lessac-medium".to_string()]
+        let Ok(entries) = std::fs::read_dir(self.voice_dir()) else {
+            return Vec::new();
+        };
+
+        let mut voices: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "onnx"))
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+            .collect();
+        voices.sort();
+        voices
     }
 }
 
@@ -289,4 +1121,434 @@ mod tests {
         let audio = tts.speak(text, None).unwrap();
         assert!(!audio.is_empty());
     }
+
+    /// Minimal 16-bit mono WAV with the given samples
+    fn wav_bytes(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+        let pcm: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        pcm16_to_wav(&pcm, sample_rate)
+    }
+
+    #[test]
+    fn test_decode_pcm16() {
+        let raw: Vec<u8> = [0i16, 16384, -32768]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(decode_pcm16(&raw), vec![0.0, 0.5, -1.0]);
+
+        // The WAV header is skipped
+        let wav = wav_bytes(&[0, 16384, -32768], WHISPER_SAMPLE_RATE);
+        assert_eq!(decode_pcm16(&wav), vec![0.0, 0.5, -1.0]);
+    }
+
+    #[test]
+    fn test_encode_pcm16_round_trip() {
+        let samples = [0.0, 0.5, -0.5, 1.0];
+        let decoded = decode_pcm16(&encode_pcm16(&samples));
+        for (original, decoded) in samples.iter().zip(&decoded) {
+            assert!((original - decoded).abs() < 1e-4);
+        }
+
+        // Clipped rather than wrapped
+        assert_eq!(encode_pcm16(&[2.0, -2.0]), encode_pcm16(&[1.0, -1.0]));
+    }
+
+    #[cfg(not(feature = "whisper"))]
+    #[test]
+    fn test_whisper_requires_feature() {
+        let stt = WhisperSttProvider::new("models/ggml-base.en.bin".to_string());
+        assert!(matches!(stt.transcribe(&[0u8; 64]), Err(AgentError::Voice(_))));
+    }
+
+    #[cfg(feature = "whisper")]
+    #[test]
+    fn test_whisper_missing_model() {
+        let stt = WhisperSttProvider::new("/nonexistent/ggml-base.en.bin".to_string());
+        match stt.transcribe(&[0u8; 64]) {
+            Err(AgentError::Voice(msg)) => assert!(msg.contains("not found")),
+            other => panic!("expected Voice error, got {:?}", other),
+        }
+    }
+
+    /// Needs a Whisper model and a short English recording:
+    /// WHISPER_MODEL=... WHISPER_SAMPLE_WAV=... cargo test --features whisper -- --ignored
+    #[cfg(feature = "whisper")]
+    #[test]
+    #[ignore]
+    fn test_whisper_transcribes_sample() {
+        let model = std::env::var("WHISPER_MODEL").expect("WHISPER_MODEL not set");
+        let wav_path = std::env::var("WHISPER_SAMPLE_WAV").expect("WHISPER_SAMPLE_WAV not set");
+        let audio = std::fs::read(wav_path).unwrap();
+
+        let stt = WhisperSttProvider::new(model);
+        let transcription = stt.transcribe(&audio).unwrap();
+        assert!(!transcription.text.is_empty());
+        assert!(transcription.duration_ms > 0);
+    }
+
+    /// Check the RIFF/WAVE/fmt layout written by `pcm16_to_wav`
+    fn assert_valid_wav(wav: &[u8]) {
+        assert!(wav.len() >= 44);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(&wav[12..16], b"fmt ");
+        assert_eq!(u16::from_le_bytes([wav[20], wav[21]]), 1); // PCM
+        assert_eq!(u16::from_le_bytes([wav[34], wav[35]]), 16); // bits per sample
+        assert!(wav_data_chunk(wav).is_some());
+    }
+
+    #[test]
+    fn test_pcm16_to_wav() {
+        let wav = wav_bytes(&[1, -1, 300], 22_050);
+        assert_valid_wav(&wav);
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 22_050);
+        assert_eq!(wav_data_chunk(&wav).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_piper_available_voices() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["en_US-lessac-medium.onnx", "en_GB-alan-low.onnx", "en_GB-alan-low.onnx.json"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let model = dir.path().join("en_US-lessac-medium.onnx");
+        let tts = PiperTtsProvider::new(model.to_string_lossy().into_owned());
+        assert_eq!(
+            tts.available_voices(),
+            vec!["en_GB-alan-low".to_string(), "en_US-lessac-medium".to_string()]
+        );
+    }
+
+    #[cfg(feature = "piper")]
+    #[test]
+    fn test_piper_missing_voice() {
+        let dir = tempfile::tempdir().unwrap();
+        let model = dir.path().join("en_US-lessac-medium.onnx");
+        let tts = PiperTtsProvider::new(model.to_string_lossy().into_owned());
+
+        match tts.speak("hello", Some("de_DE-thorsten-low")) {
+            Err(AgentError::Voice(msg)) => assert!(msg.contains("not found")),
+            other => panic!("expected Voice error, got {:?}", other.map(|a| a.len())),
+        }
+    }
+
+    /// Stand-in `piper` that echoes its input as PCM after flooding stderr
+    #[cfg(all(feature = "piper", unix))]
+    fn fake_piper(dir: &std::path::Path) -> PiperTtsProvider {
+        use std::os::unix::fs::PermissionsExt;
+
+        let binary = dir.join("piper");
+        std::fs::write(&binary, "#!/bin/sh\nhead -c 200000 /dev/zero >&2\ncat\n").unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let model = dir.join("voice.onnx");
+        std::fs::write(&model, b"").unwrap();
+
+        PiperTtsProvider::new(model.to_string_lossy().into_owned())
+            .with_binary(binary.to_string_lossy().into_owned())
+    }
+
+    #[cfg(all(feature = "piper", unix))]
+    #[test]
+    fn test_piper_stream_matches_speak() {
+        let dir = tempfile::tempdir().unwrap();
+        let tts = fake_piper(dir.path());
+
+        let wav = tts.speak("hello!", None).unwrap();
+        assert_valid_wav(&wav);
+        assert_eq!(wav_data_chunk(&wav).unwrap(), b"hello!");
+
+        let mut streamed = Vec::new();
+        tts.speak_stream("hello!", None, &mut |chunk| streamed.extend_from_slice(chunk))
+            .unwrap();
+        assert_eq!(streamed.len(), wav.len());
+        assert_eq!(&streamed[4..8], &u32::MAX.to_le_bytes());
+        assert_eq!(&streamed[40..44], &u32::MAX.to_le_bytes());
+        assert_eq!(&streamed[8..40], &wav[8..40]);
+        assert_eq!(&streamed[44..], &wav[44..]);
+    }
+
+    /// Needs the `piper` executable and a voice model:
+    /// PIPER_MODEL=... cargo test --features piper -- --ignored
+    #[cfg(feature = "piper")]
+    #[test]
+    #[ignore]
+    fn test_piper_synthesizes_hello() {
+        let model = std::env::var("PIPER_MODEL").expect("PIPER_MODEL not set");
+        let tts = PiperTtsProvider::new(model);
+
+        let wav = tts.speak("hello", None).unwrap();
+        assert_valid_wav(&wav);
+        assert!(!wav_data_chunk(&wav).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_vad_single_segment() {
+        let sample_rate = 16_000;
+        let mut audio = vec![0.01f32; 8_000];
+        // 0.5s of "speech" with a short dip that hysteresis should bridge
+        audio.extend((0..8_000).map(|i| {
+            let level = if (3_000..3_200).contains(&i) { 0.07 } else { 0.4 };
+            if i % 2 == 0 { level } else { -level }
+        }));
+        audio.extend(vec![0.01f32; 8_000]);
+
+        let vad = VoiceActivityDetector::default();
+        let segments = vad.segments(&audio, sample_rate);
+
+        assert_eq!(segments.len(), 1);
+        let (start, end) = segments[0];
+        assert!((7_680..=8_320).contains(&start), "start {}", start);
+        assert!((15_680..=16_320).contains(&end), "end {}", end);
+    }
+
+    #[test]
+    fn test_vad_ignores_short_bursts() {
+        let mut audio = vec![0.0f32; 16_000];
+        // 40ms click, below the 100ms minimum
+        for sample in audio.iter_mut().skip(4_000).take(640) {
+            *sample = 0.5;
+        }
+
+        let vad = VoiceActivityDetector::default();
+        assert!(vad.segments(&audio, 16_000).is_empty());
+        assert!(vad.with_min_segment_ms(20).segments(&audio, 16_000).len() == 1);
+    }
+
+    #[test]
+    fn test_resample_downsample_sine() {
+        let input: Vec<f32> = (0..48_000)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48_000.0).sin() * 0.5)
+            .collect();
+
+        let output = resample(&input, 48_000, 16_000);
+        assert_eq!(output.len(), 16_000);
+
+        let expected_rms = 0.5 / 2f32.sqrt();
+        assert!((rms_energy(&output) - expected_rms).abs() < 0.01);
+        assert!((rms_energy(&output) - rms_energy(&input)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_resample_edge_cases() {
+        assert!(resample(&[], 48_000, 16_000).is_empty());
+        assert_eq!(resample(&[0.1, 0.2], 16_000, 16_000), vec![0.1, 0.2]);
+        assert_eq!(resample(&[0.0, 1.0], 8_000, 16_000), vec![0.0, 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_mock_stt_stream_partials_then_final() {
+        let stt = MockSpeechToText::new();
+        let chunk = vec![0u8; 100];
+
+        let mut partials = Vec::new();
+        for _ in 0..7 {
+            if let Some(partial) = stt.transcribe_stream(&chunk, false).unwrap() {
+                partials.push(partial.text);
+            }
+        }
+        let last = stt.transcribe_stream(&chunk, true).unwrap().unwrap();
+
+        assert_eq!(partials, vec!["This".to_string(), "This is".to_string()]);
+        assert_eq!(last.text, "This is a mock transcription");
+        assert_eq!(last.duration_ms, 800);
+
+        // The buffer is reset for the next utterance
+        let next = stt.transcribe_stream(&chunk, true).unwrap().unwrap();
+        assert_eq!(next.duration_ms, 100);
+    }
+
+    #[test]
+    fn test_default_transcribe_stream_buffers() {
+        struct LengthStt(Mutex<Vec<u8>>);
+
+        impl SpeechToText for LengthStt {
+            fn transcribe(&self, audio_data: &[u8]) -> Result<VoiceTranscription> {
+                Ok(VoiceTranscription {
+                    text: format!("{} bytes", audio_data.len()),
+                    confidence: 1.0,
+                    language: "en".to_string(),
+                    duration_ms: 0,
+                })
+            }
+
+            fn stream_buffer(&self) -> Option<&Mutex<Vec<u8>>> {
+                Some(&self.0)
+            }
+
+            fn supported_languages(&self) -> Vec<String> {
+                vec!["en".to_string()]
+            }
+        }
+
+        let stt = LengthStt(Mutex::new(Vec::new()));
+        assert!(stt.transcribe_stream(&[0; 10], false).unwrap().is_none());
+        assert!(stt.transcribe_stream(&[0; 10], false).unwrap().is_none());
+        let result = stt.transcribe_stream(&[0; 5], true).unwrap().unwrap();
+        assert_eq!(result.text, "25 bytes");
+    }
+
+    #[test]
+    fn test_wake_word_calibration() {
+        let mut detector = SimpleWakeWordDetector::default();
+        let moderate = vec![0.2f32; 1000];
+        assert!(detector.detect(&moderate).unwrap());
+
+        // Loud room: ambient RMS of 0.15
+        detector.calibrate(&vec![0.15f32; 1000]);
+        assert!((detector.threshold() - 0.45).abs() < 1e-4);
+        assert!(!detector.detect(&moderate).unwrap());
+        assert!(detector.detect(&vec![0.6f32; 1000]).unwrap());
+
+        // Quiet room lowers the threshold again, with a floor
+        detector.calibrate(&vec![0.0f32; 1000]);
+        assert_eq!(detector.threshold(), MIN_WAKE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_wake_word_adaptive_noise_floor() {
+        let mut detector = SimpleWakeWordDetector::default();
+        let moderate = vec![0.2f32; 1000];
+
+        // Noise gets steadily louder; the floor follows it
+        for _ in 0..50 {
+            assert!(!detector.detect_adaptive(&vec![0.08f32; 1000]).unwrap());
+        }
+        assert!((detector.noise_floor().unwrap() - 0.08).abs() < 0.01);
+        assert!(!detector.detect_adaptive(&moderate).unwrap());
+        assert!(detector.detect_adaptive(&vec![0.5f32; 1000]).unwrap());
+    }
+
+    #[test]
+    fn test_default_detect_language() {
+        struct Fixed;
+
+        impl SpeechToText for Fixed {
+            fn transcribe(&self, _audio_data: &[u8]) -> Result<VoiceTranscription> {
+                Err(AgentError::Voice("unused".to_string()))
+            }
+
+            fn supported_languages(&self) -> Vec<String> {
+                vec!["de".to_string(), "en".to_string()]
+            }
+        }
+
+        let stt = Fixed;
+        let language = stt.detect_language(&[0u8; 10]).unwrap();
+        assert!(stt.supported_languages().contains(&language));
+        assert_eq!(language, "de");
+    }
+
+    #[test]
+    fn test_mock_stt_language_hint() {
+        let stt = MockSpeechToText::with_languages(vec!["fr".to_string(), "en".to_string()]);
+        let audio = vec![0u8; 100];
+
+        assert_eq!(stt.detect_language(&audio).unwrap(), "fr");
+        assert_eq!(stt.transcribe(&audio).unwrap().language, "fr");
+        assert_eq!(stt.transcribe_with_language(&audio, Some("en")).unwrap().language, "en");
+        assert!(stt.transcribe_with_language(&audio, Some("ja")).is_err());
+    }
+
+    #[test]
+    fn test_simple_wake_word_detailed() {
+        let detector = SimpleWakeWordDetector::new(vec!["computer".to_string()], 0.3);
+
+        assert_eq!(detector.detect_detailed(&vec![0.01f32; 1000]).unwrap(), None);
+
+        let detection = detector.detect_detailed(&vec![0.5f32; 1000]).unwrap().unwrap();
+        assert_eq!(detection.word, "computer");
+        assert!(detection.confidence > detector.threshold());
+        assert!(detection.confidence <= 1.0);
+    }
+
+    #[test]
+    fn test_detect_detailed_default() {
+        // Mock only implements `detect`, so the default never reports a match
+        let detector = MockWakeWordDetector::default();
+        assert_eq!(detector.detect_detailed(&[0.9f32; 100]).unwrap(), None);
+    }
+
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..WHISPER_SAMPLE_RATE)
+            .map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / WHISPER_SAMPLE_RATE as f32).sin() * amplitude)
+            .collect()
+    }
+
+    #[test]
+    fn test_bandpass_ignores_low_frequency_rumble() {
+        let hum = sine(60.0, 0.5);
+        let voice = sine(1000.0, 0.5);
+
+        let full_band = SimpleWakeWordDetector::new(vec!["hey agent".to_string()], 0.1);
+        assert!(full_band.detect(&hum).unwrap());
+
+        let detector = SimpleWakeWordDetector::new(vec!["hey agent".to_string()], 0.1).with_bandpass(true);
+        assert!(!detector.detect(&hum).unwrap());
+        assert!(detector.detect(&voice).unwrap());
+    }
+
+    #[test]
+    fn test_speech_bandpass_response() {
+        let passed = rms_energy(&speech_bandpass(&sine(1000.0, 0.5), WHISPER_SAMPLE_RATE));
+        let low = rms_energy(&speech_bandpass(&sine(60.0, 0.5), WHISPER_SAMPLE_RATE));
+        let high = rms_energy(&speech_bandpass(&sine(7000.0, 0.5), WHISPER_SAMPLE_RATE));
+
+        assert!((passed - 0.5 / 2f32.sqrt()).abs() < 0.05);
+        assert!(low < 0.05);
+        assert!(high < 0.1);
+    }
+
+    #[test]
+    fn test_mock_tts_speak_stream() {
+        let tts = MockTextToSpeech::new();
+        let text = "This is a longer response that should arrive in several pieces.";
+
+        let mut chunks = Vec::new();
+        tts.speak_stream(text, None, &mut |chunk| chunks.push(chunk.to_vec())).unwrap();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), tts.speak(text, None).unwrap());
+    }
+
+    #[test]
+    fn test_default_speak_stream_single_chunk() {
+        struct OneShot;
+
+        impl TextToSpeech for OneShot {
+            fn speak(&self, text: &str, _voice: Option<&str>) -> Result<Vec<u8>> {
+                Ok(text.as_bytes().to_vec())
+            }
+
+            fn available_voices(&self) -> Vec<String> {
+                Vec::new()
+            }
+        }
+
+        let mut calls = 0;
+        let mut audio = Vec::new();
+        OneShot
+            .speak_stream("hi", None, &mut |chunk| {
+                calls += 1;
+                audio.extend_from_slice(chunk);
+            })
+            .unwrap();
+
+        assert_eq!(calls, 1);
+        assert_eq!(audio, b"hi");
+    }
+
+    #[test]
+    fn test_mock_stt_stream_partials_report_detected_language() {
+        let stt = MockSpeechToText::with_languages(vec!["fr".to_string()]);
+        let chunk = vec![0u8; 100];
+
+        let partial = (0..3)
+            .find_map(|_| stt.transcribe_stream(&chunk, false).unwrap())
+            .unwrap();
+        let last = stt.transcribe_stream(&chunk, true).unwrap().unwrap();
+
+        assert_eq!(partial.language, "fr");
+        assert_eq!(last.language, "fr");
+    }
 }
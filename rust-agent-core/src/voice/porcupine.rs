@@ -0,0 +1,302 @@
+//! Porcupine wake word detection
+//!
+//! Binds the Porcupine v3 C library (`libpv_porcupine`) directly; enable the
+//! `porcupine` feature and make the library visible to the linker. Keywords
+//! are `.ppn` files created in the Picovoice console.
+
+use super::{WakeWordDetector, WakeWordMatch};
+use crate::error::{AgentError, Result};
+#[cfg(feature = "porcupine")]
+use std::sync::Mutex;
+
+#[cfg(feature = "porcupine")]
+mod ffi {
+    use std::os::raw::{c_char, c_float, c_int};
+
+    /// Opaque `pv_porcupine_t`
+    #[repr(C)]
+    pub struct PvPorcupine {
+        _private: [u8; 0],
+    }
+
+    pub const PV_STATUS_SUCCESS: c_int = 0;
+
+    #[link(name = "pv_porcupine")]
+    extern "C" {
+        pub fn pv_porcupine_init(
+            access_key: *const c_char,
+            model_path: *const c_char,
+            num_keywords: i32,
+            keyword_paths: *const *const c_char,
+            sensitivities: *const c_float,
+            object: *mut *mut PvPorcupine,
+        ) -> c_int;
+        pub fn pv_porcupine_delete(object: *mut PvPorcupine);
+        pub fn pv_porcupine_process(
+            object: *mut PvPorcupine,
+            pcm: *const i16,
+            keyword_index: *mut i32,
+        ) -> c_int;
+        pub fn pv_porcupine_frame_length() -> i32;
+        pub fn pv_sample_rate() -> i32;
+        pub fn pv_status_to_string(status: c_int) -> *const c_char;
+    }
+
+    /// Owned Porcupine instance, deleted on drop
+    pub struct Handle(pub *mut PvPorcupine);
+
+    // Porcupine instances may move between threads; access is serialized by a mutex
+    unsafe impl Send for Handle {}
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe { pv_porcupine_delete(self.0) }
+        }
+    }
+
+    pub fn status_message(status: c_int) -> String {
+        unsafe {
+            let message = pv_status_to_string(status);
+            if message.is_null() {
+                return format!("status {}", status);
+            }
+            std::ffi::CStr::from_ptr(message).to_string_lossy().into_owned()
+        }
+    }
+}
+
+/// Porcupine instance plus samples left over from the last call that didn't
+/// fill a whole frame
+#[cfg(feature = "porcupine")]
+struct Stream {
+    handle: ffi::Handle,
+    pending: Vec<i16>,
+}
+
+/// Porcupine wake word detector
+/// Expects 16kHz mono audio; real detection requires the `porcupine` feature
+pub struct PorcupineDetector {
+    keywords: Vec<String>,
+    #[cfg(feature = "porcupine")]
+    stream: Mutex<Stream>,
+    #[cfg(feature = "porcupine")]
+    frame_length: usize,
+}
+
+impl PorcupineDetector {
+    /// Load the Porcupine model and keyword files
+    /// `sensitivity` (0.0 - 1.0) trades misses for false alarms and applies to every keyword
+    #[cfg(feature = "porcupine")]
+    pub fn new(
+        access_key: &str,
+        model_path: &str,
+        keyword_paths: &[String],
+        sensitivity: f32,
+    ) -> Result<Self> {
+        use std::ffi::CString;
+
+        if keyword_paths.is_empty() {
+            return Err(AgentError::Config("Porcupine needs at least one keyword".to_string()));
+        }
+        if !(0.0..=1.0).contains(&sensitivity) {
+            return Err(AgentError::Config(format!(
+                "Porcupine sensitivity must be between 0.0 and 1.0, got {}",
+                sensitivity
+            )));
+        }
+        if unsafe { ffi::pv_sample_rate() } != super::WHISPER_SAMPLE_RATE as i32 {
+            return Err(AgentError::Voice("Unsupported Porcupine sample rate".to_string()));
+        }
+
+        let c_string = |s: &str| {
+            CString::new(s).map_err(|_| AgentError::Config(format!("Invalid Porcupine argument: {}", s)))
+        };
+        let access_key = c_string(access_key)?;
+        let model_path = c_string(model_path)?;
+        let keyword_cstrings = keyword_paths
+            .iter()
+            .map(|path| c_string(path))
+            .collect::<Result<Vec<_>>>()?;
+        let keyword_ptrs: Vec<_> = keyword_cstrings.iter().map(|path| path.as_ptr()).collect();
+        let sensitivities = vec![sensitivity; keyword_paths.len()];
+
+        let mut object = std::ptr::null_mut();
+        let status = unsafe {
+            ffi::pv_porcupine_init(
+                access_key.as_ptr(),
+                model_path.as_ptr(),
+                keyword_ptrs.len() as i32,
+                keyword_ptrs.as_ptr(),
+                sensitivities.as_ptr(),
+                &mut object,
+            )
+        };
+        if status != ffi::PV_STATUS_SUCCESS {
+            return Err(AgentError::Voice(format!(
+                "Failed to initialize Porcupine: {}",
+                ffi::status_message(status)
+            )));
+        }
+
+        Ok(Self {
+            keywords: keyword_paths.iter().map(|path| keyword_name(path)).collect(),
+            stream: Mutex::new(Stream {
+                handle: ffi::Handle(object),
+                pending: Vec::new(),
+            }),
+            frame_length: unsafe { ffi::pv_porcupine_frame_length() } as usize,
+        })
+    }
+
+    #[cfg(not(feature = "porcupine"))]
+    pub fn new(
+        _access_key: &str,
+        _model_path: &str,
+        keyword_paths: &[String],
+        _sensitivity: f32,
+    ) -> Result<Self> {
+        Ok(Self {
+            keywords: keyword_paths.iter().map(|path| keyword_name(path)).collect(),
+        })
+    }
+}
+
+impl WakeWordDetector for PorcupineDetector {
+    fn detect(&self, audio_data: &[f32]) -> Result<bool> {
+        Ok(self.detect_detailed(audio_data)?.is_some())
+    }
+
+    /// Runs Porcupine over every full frame of `audio_data`; a trailing partial
+    /// frame is carried over and completed by the next call. Porcupine makes
+    /// binary decisions, so confidence is 1.0.
+    #[cfg(feature = "porcupine")]
+    fn detect_detailed(&self, audio_data: &[f32]) -> Result<Option<WakeWordMatch>> {
+        let mut stream = self
+            .stream
+            .lock()
+            .map_err(|_| AgentError::Voice("Porcupine lock poisoned".to_string()))?;
+        let mut samples = std::mem::take(&mut stream.pending);
+        samples.extend(to_pcm16(audio_data));
+
+        let mut frames = samples.chunks_exact(self.frame_length);
+        for frame in &mut frames {
+            let mut keyword_index = -1;
+            let status = unsafe {
+                ffi::pv_porcupine_process(stream.handle.0, frame.as_ptr(), &mut keyword_index)
+            };
+            if status != ffi::PV_STATUS_SUCCESS {
+                return Err(AgentError::Voice(format!(
+                    "Porcupine processing failed: {}",
+                    ffi::status_message(status)
+                )));
+            }
+
+            if let Some(word) = usize::try_from(keyword_index)
+                .ok()
+                .and_then(|index| self.keywords.get(index))
+            {
+                // Audio after the wake word belongs to whatever listens next
+                return Ok(Some(WakeWordMatch {
+                    word: word.clone(),
+                    confidence: 1.0,
+                }));
+            }
+        }
+
+        stream.pending = frames.remainder().to_vec();
+        Ok(None)
+    }
+
+    #[cfg(not(feature = "porcupine"))]
+    fn detect_detailed(&self, _audio_data: &[f32]) -> Result<Option<WakeWordMatch>> {
+        Err(AgentError::Voice(
+            "Porcupine support is not enabled. Rebuild with the `porcupine` feature or use SimpleWakeWordDetector."
+                .to_string(),
+        ))
+    }
+
+    fn wake_words(&self) -> Vec<String> {
+        self.keywords.clone()
+    }
+}
+
+/// Keyword name from a `.ppn` path, e.g. `jarvis_linux_v3_0_0.ppn` -> `jarvis`
+fn keyword_name(path: &str) -> String {
+    let stem = std::path::Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    match stem.split_once('_') {
+        Some((name, _)) => name.to_string(),
+        None => stem,
+    }
+}
+
+/// Convert `[-1.0, 1.0]` float samples to 16-bit PCM, clipping out-of-range
+/// values and rounding like `encode_pcm16`
+#[cfg(any(feature = "porcupine", test))]
+fn to_pcm16(samples: &[f32]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_name() {
+        assert_eq!(keyword_name("keywords/jarvis_linux_v3_0_0.ppn"), "jarvis");
+        assert_eq!(keyword_name("hey computer_raspberry-pi.ppn"), "hey computer");
+        assert_eq!(keyword_name("bumblebee.ppn"), "bumblebee");
+    }
+
+    #[test]
+    fn test_to_pcm16() {
+        assert_eq!(to_pcm16(&[0.0, 1.0, -1.0, 2.0]), vec![0, 32767, -32767, 32767]);
+        // Rounds to the nearest sample rather than truncating toward zero
+        assert_eq!(to_pcm16(&[0.9 / 32767.0, -0.9 / 32767.0]), vec![1, -1]);
+
+        let samples = [0.25, -0.5, 0.123];
+        let encoded: Vec<u8> = to_pcm16(&samples)
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(encoded, crate::voice::encode_pcm16(&samples));
+    }
+
+    #[cfg(not(feature = "porcupine"))]
+    #[test]
+    fn test_porcupine_requires_feature() {
+        let detector = PorcupineDetector::new(
+            "key",
+            "porcupine_params.pv",
+            &["jarvis_linux_v3_0_0.ppn".to_string()],
+            0.5,
+        )
+        .unwrap();
+
+        assert_eq!(detector.wake_words(), vec!["jarvis"]);
+        assert!(matches!(detector.detect(&[0.0; 512]), Err(AgentError::Voice(_))));
+    }
+
+    /// Needs the Porcupine library, model and a keyword file:
+    /// PORCUPINE_ACCESS_KEY=... PORCUPINE_MODEL=... PORCUPINE_KEYWORD=...
+    /// cargo test --features porcupine -- --ignored
+    #[cfg(feature = "porcupine")]
+    #[test]
+    #[ignore]
+    fn test_porcupine_ignores_silence() {
+        let access_key = std::env::var("PORCUPINE_ACCESS_KEY").expect("PORCUPINE_ACCESS_KEY not set");
+        let model = std::env::var("PORCUPINE_MODEL").expect("PORCUPINE_MODEL not set");
+        let keyword = std::env::var("PORCUPINE_KEYWORD").expect("PORCUPINE_KEYWORD not set");
+
+        let detector = PorcupineDetector::new(&access_key, &model, std::slice::from_ref(&keyword), 0.5).unwrap();
+
+        assert_eq!(detector.wake_words(), vec![keyword_name(&keyword)]);
+        assert_eq!(detector.detect_detailed(&[0.0; 16_000]).unwrap(), None);
+    }
+}